@@ -1,26 +1,263 @@
 use crate::DeriveType;
-use proc_macro_error::{abort, emit_error};
-use syn::{GenericParam, Ident};
+use proc_macro_error::emit_error;
+use syn::{
+	punctuated::Punctuated, Attribute, ExprPath, Field, GenericParam, Generics, Ident, Lifetime,
+	LitStr, Path, Token, TypePath, WherePredicate,
+};
+
+/// Attributes placed on the item itself, i.e. `#[shallowclone(...)]` above the struct/enum
+#[derive(Default)]
+pub struct ContainerAttrs {
+	/// `bound = "..."`, replaces the bounds that would otherwise be generated for the generics
+	pub bound: Option<Punctuated<WherePredicate, Token![,]>>,
+	/// `crate = "..."`, path to the `shallowclone` crate
+	pub krate: Option<Path>,
+	/// `target = "..."` for `ShallowClone` or `owned = "..."` for `MakeOwned`, a different type to convert to
+	pub target: Option<TypePath>,
+	/// `remote = "..."`, the item is a copy of a foreign type, and free functions are generated for it
+	pub remote: Option<Path>,
+	/// `keep = 'a`, lifetimes which are not detached by `MakeOwned`, implementing `MakeOwnedFor` instead
+	pub keep: Vec<Lifetime>,
+	/// `gat`, implements `ShallowCloneGat` as well as `ShallowClone`
+	pub gat: bool,
+	/// `to_owned_static`, implements `ToOwnedStatic` as well as `MakeOwned`
+	pub to_owned_static: bool,
+	/// Not an attribute, set by the `TryMakeOwned` derive, which shares the attributes of `MakeOwned`
+	pub fallible: bool,
+	/// Not an attribute, set by the `MakeOwnedIn` derive, which shares the attributes of `MakeOwned`
+	pub arena: bool,
+	/// Not an attribute, set by the `Rebase` derive, which shares the attributes of `MakeOwned`
+	pub rebase: bool,
+}
+
+impl ContainerAttrs {
+	pub fn parse(derive_type: DeriveType, attrs: &[Attribute]) -> Self {
+		let mut result = Self::default();
+
+		for attr in attrs {
+			if !attr.path().is_ident(derive_type.attr_name()) {
+				continue;
+			}
+
+			let res = attr.parse_nested_meta(|meta| {
+				if meta.path.is_ident("bound") {
+					let bound: LitStr = meta.value()?.parse()?;
+					result.bound = Some(bound.parse_with(Punctuated::parse_terminated)?);
+
+					Ok(())
+				} else if meta.path.is_ident("crate") {
+					let path: LitStr = meta.value()?.parse()?;
+					result.krate = Some(path.parse()?);
+
+					Ok(())
+				} else if meta.path.is_ident("remote") {
+					let path: LitStr = meta.value()?.parse()?;
+					result.remote = Some(path.parse()?);
+
+					Ok(())
+				} else if meta.path.is_ident("keep") {
+					if derive_type != DeriveType::MakeOwned {
+						return Err(meta.error("`keep` is only supported by `MakeOwned`"));
+					}
+					result.keep.push(meta.value()?.parse()?);
+
+					Ok(())
+				} else if meta.path.is_ident("to_owned_static") {
+					if derive_type != DeriveType::MakeOwned {
+						return Err(
+							meta.error("`to_owned_static` is only supported by `MakeOwned`")
+						);
+					}
+					result.to_owned_static = true;
+
+					Ok(())
+				} else if meta.path.is_ident("gat") {
+					if derive_type != DeriveType::ShallowClone {
+						return Err(meta.error("`gat` is only supported by `ShallowClone`"));
+					}
+					result.gat = true;
+
+					Ok(())
+				} else if meta.path.is_ident(derive_type.target_attr_name()) {
+					let target: LitStr = meta.value()?.parse()?;
+					result.target = Some(target.parse().map_err(|e| {
+						syn::Error::new(e.span(), "expected a path to a struct or enum")
+					})?);
+
+					Ok(())
+				} else {
+					Err(meta.error("Unknown attribute"))
+				}
+			});
+			if let Err(e) = res {
+				emit_error!(e.span(), "{}", e);
+			}
+		}
+
+		result
+	}
+}
+
+/// How a single field is converted
+pub enum FieldMode {
+	/// Using the trait that is being derived
+	Trait,
+	/// `clone`, using [`Clone`], only for `ShallowClone`
+	Clone,
+	/// `skip`, moving the value as it is, only for `MakeOwned`
+	Skip,
+	/// `with = "path"`, using a custom function
+	With(ExprPath),
+	/// `default`, discarding the value and using [`Default`]
+	Default,
+}
+
+/// Attributes placed on fields
+pub struct FieldAttrs {
+	pub mode: FieldMode,
+	/// `no_inspect`, the field is left out by `BorrowState`, `HeapSize` and `VisitCows`, only for `MakeOwned`
+	pub no_inspect: bool,
+}
+
+impl FieldAttrs {
+	pub fn parse(derive_type: DeriveType, field: &Field) -> Self {
+		let mut result = Self {
+			mode: FieldMode::Trait,
+			no_inspect: false,
+		};
+
+		for attr in &field.attrs {
+			if !attr.path().is_ident(derive_type.attr_name()) {
+				continue;
+			}
+
+			let res = attr.parse_nested_meta(|meta| {
+				// not a mode, since it doesn't change how the field is converted
+				if meta.path.is_ident("no_inspect") {
+					if derive_type != DeriveType::MakeOwned {
+						return Err(meta.error("`no_inspect` is only supported by `MakeOwned`"));
+					}
+					result.no_inspect = true;
+					return Ok(());
+				}
+
+				let mode = if meta.path.is_ident("clone") {
+					if derive_type != DeriveType::ShallowClone {
+						return Err(meta.error("`clone` is only supported by `ShallowClone`, use `skip` to move the field as it is"));
+					}
+					FieldMode::Clone
+				} else if meta.path.is_ident("skip") {
+					if derive_type != DeriveType::MakeOwned {
+						return Err(meta.error("`skip` is only supported by `MakeOwned`, use `clone` to clone the field"));
+					}
+					FieldMode::Skip
+				} else if meta.path.is_ident("with") {
+					let path: LitStr = meta.value()?.parse()?;
+					FieldMode::With(path.parse()?)
+				} else if meta.path.is_ident("default") {
+					FieldMode::Default
+				} else {
+					return Err(meta.error("Unknown attribute"));
+				};
+
+				if !matches!(result.mode, FieldMode::Trait) {
+					return Err(meta.error("only one of `clone`, `skip`, `with` and `default` can be used on a field"));
+				}
+				result.mode = mode;
+
+				Ok(())
+			});
+			if let Err(e) = res {
+				emit_error!(e.span(), "{}", e);
+			}
+		}
+
+		result
+	}
+}
+
+/// Attributes placed on the item for `ShallowView`, i.e. `#[shallowview(...)]` above the struct/enum
+#[derive(Default)]
+pub struct ViewAttrs {
+	/// `derive(...)`, traits to derive for the view type
+	pub derives: Vec<Path>,
+}
+
+impl ViewAttrs {
+	pub fn parse(attrs: &[Attribute]) -> Self {
+		let mut result = Self::default();
+
+		for attr in attrs {
+			if !attr.path().is_ident("shallowview") {
+				continue;
+			}
+
+			let res = attr.parse_nested_meta(|meta| {
+				if meta.path.is_ident("derive") {
+					meta.parse_nested_meta(|meta| {
+						result.derives.push(meta.path);
+						Ok(())
+					})
+				} else {
+					Err(meta.error("Unknown attribute"))
+				}
+			});
+			if let Err(e) = res {
+				emit_error!(e.span(), "{}", e);
+			}
+		}
+
+		result
+	}
+}
+
+/// Whether the field has `#[shallowview(clone)]`, i.e. it's cloned into the view instead of being borrowed
+pub fn is_view_field_cloned(field: &Field) -> bool {
+	let mut cloned = false;
+	for attr in &field.attrs {
+		if !attr.path().is_ident("shallowview") {
+			continue;
+		}
+
+		let res = attr.parse_nested_meta(|meta| {
+			if meta.path.is_ident("clone") {
+				cloned = true;
+				Ok(())
+			} else {
+				Err(meta.error("Unknown attribute"))
+			}
+		});
+		if let Err(e) = res {
+			emit_error!(e.span(), "{}", e);
+		}
+	}
+
+	cloned
+}
 
 pub fn is_generic_skipped(derive_type: DeriveType, input: &GenericParam) -> bool {
 	let attrs = match input {
 		GenericParam::Lifetime(lifetime_param) => &lifetime_param.attrs,
 		GenericParam::Type(type_param) => &type_param.attrs,
-		GenericParam::Const(_) => abort!(input, "const generics cannot be skipped"),
+		// const generics are never changed, so there is nothing to skip
+		GenericParam::Const(const_param) => {
+			for attr in &const_param.attrs {
+				if attr.path().is_ident(derive_type.attr_name()) {
+					emit_error!(attr, "const generics cannot be skipped");
+				}
+			}
+			return false;
+		}
 	};
 
 	for attr in attrs {
-		let root_tag = match derive_type {
-			DeriveType::ShallowClone => "shallowclone",
-			DeriveType::MakeOwned => "makeowned",
-		};
-		if !attr.path().is_ident(root_tag) {
+		if !attr.path().is_ident(derive_type.attr_name()) {
 			continue;
 		}
 
 		if let syn::Meta::List(list) = &attr.meta {
 			if let Ok(parsed) = list.parse_args::<Ident>() {
-				if parsed.to_string() == "skip" {
+				if parsed == "skip" {
 					return true;
 				} else {
 					emit_error!(parsed, "Unknown attribute");
@@ -34,3 +271,40 @@ pub fn is_generic_skipped(derive_type: DeriveType, input: &GenericParam) -> bool
 
 	false
 }
+
+/// Whether the item has `#[repr(packed)]` (or `packed(N)`), in which case its fields can't be borrowed
+pub fn is_packed(attrs: &[Attribute]) -> bool {
+	let mut packed = false;
+	for attr in attrs {
+		if !attr.path().is_ident("repr") {
+			continue;
+		}
+
+		// other repr hints are irrelevant, and the compiler reports any errors in them
+		let _ = attr.parse_nested_meta(|meta| {
+			if meta.path.is_ident("packed") {
+				packed = true;
+			}
+			if meta.input.peek(syn::token::Paren) {
+				let _content;
+				syn::parenthesized!(_content in meta.input);
+			}
+			Ok(())
+		});
+	}
+
+	packed
+}
+
+/// The generics without any of our attributes, which would not compile in the generated code
+pub fn without_attrs(generics: &Generics) -> Generics {
+	let mut generics = generics.clone();
+	for param in &mut generics.params {
+		match param {
+			GenericParam::Lifetime(param) => param.attrs.clear(),
+			GenericParam::Type(param) => param.attrs.clear(),
+			GenericParam::Const(param) => param.attrs.clear(),
+		}
+	}
+	generics
+}
@@ -0,0 +1,60 @@
+use crate::attributes::ContainerAttrs;
+use crate::walk::{gen_walk, gen_walk_impl, is_inspected};
+use crate::{DeriveType, Names};
+use proc_macro2::TokenStream;
+use proc_macro_error::abort;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::DeriveInput;
+
+/// Derives `BorrowState`, adding up the stats of the fields and checking whether any of them borrows from
+/// the buffer
+pub fn derive_borrow_state(input: &DeriveInput) -> TokenStream {
+	let container_attrs = ContainerAttrs::parse(DeriveType::MakeOwned, &input.attrs);
+	if let Some(remote) = &container_attrs.remote {
+		abort!(
+			remote,
+			"`BorrowState` can't be derived together with `remote`"
+		);
+	}
+	let names = Names::new(input, &container_attrs);
+	let krate = &names.krate;
+	let bound = quote! { #krate::BorrowState };
+
+	let mut inferred_bounds = Vec::new();
+	let add_stats = gen_walk(input, &bound, &mut inferred_bounds, |walked| {
+		let binding = &walked.binding;
+		is_inspected(walked.field).then(|| {
+			quote_spanned! {walked.field.ty.span()=>
+				#krate::BorrowState::add_borrow_stats(#binding, __stats);
+			}
+		})
+	});
+	let borrows_from = gen_walk(input, &bound, &mut inferred_bounds, |walked| {
+		let binding = &walked.binding;
+		is_inspected(walked.field).then(|| {
+			quote_spanned! {walked.field.ty.span()=>
+				if #krate::BorrowState::borrows_from(#binding, __buffer) {
+					return true;
+				}
+			}
+		})
+	});
+
+	gen_walk_impl(
+		input,
+		&container_attrs,
+		&bound,
+		inferred_bounds,
+		quote! {
+			fn add_borrow_stats(&self, __stats: &mut #krate::BorrowStats) {
+				#add_stats
+			}
+
+			fn borrows_from(&self, __buffer: &[u8]) -> bool {
+				#borrows_from
+				false
+			}
+		},
+	)
+}
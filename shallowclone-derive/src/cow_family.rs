@@ -0,0 +1,238 @@
+use crate::attributes::{self, ContainerAttrs};
+use crate::gen_impl::tuple_field;
+use crate::target_type::get_target_type;
+use crate::usage::Usage;
+use crate::{derive, DeriveType, Names};
+use proc_macro2::TokenStream;
+use proc_macro_error::abort;
+use quote::{format_ident, quote};
+use syn::{parse_quote, Data, DeriveInput, Fields, GenericParam};
+
+/// Derives `ShallowClone`, `MakeOwned` and `Clone`, plus an alias for the owned type and some helpers
+pub fn derive_cow_family(input: &DeriveInput) -> TokenStream {
+	let shallowclone_attrs = ContainerAttrs::parse(DeriveType::ShallowClone, &input.attrs);
+	let makeowned_attrs = ContainerAttrs::parse(DeriveType::MakeOwned, &input.attrs);
+	if let Some(remote) = shallowclone_attrs
+		.remote
+		.as_ref()
+		.or(makeowned_attrs.remote.as_ref())
+	{
+		abort!(remote, "`remote` is not supported by `CowFamily`");
+	}
+	if let Some(keep) = makeowned_attrs.keep.first() {
+		abort!(
+			keep,
+			"`keep` is not supported by `CowFamily`, since the owned type must be `'static`"
+		);
+	}
+
+	let shallowclone_impl = derive(input, DeriveType::ShallowClone, &shallowclone_attrs);
+	let makeowned_impl = derive(input, DeriveType::MakeOwned, &makeowned_attrs);
+	let clone_impl = gen_clone(input);
+	let helpers = gen_helpers(input, &shallowclone_attrs, &makeowned_attrs);
+
+	quote! {
+		#shallowclone_impl
+		#makeowned_impl
+		#clone_impl
+		#helpers
+	}
+}
+
+/// The same as `#[derive(Clone)]`
+fn gen_clone(input: &DeriveInput) -> TokenStream {
+	let ident = &input.ident;
+	let mut generics = attributes::without_attrs(&input.generics);
+	for type_param in generics.type_params_mut() {
+		type_param
+			.bounds
+			.push(parse_quote! { ::core::clone::Clone });
+	}
+	let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+
+	let body = match &input.data {
+		Data::Struct(data) => {
+			let (pattern, value) = clone_fields(&data.fields);
+			quote! {
+				let Self #pattern = self;
+				Self #value
+			}
+		}
+		Data::Enum(data) => {
+			let variants = data.variants.iter().map(|variant| {
+				let variant_name = &variant.ident;
+				let (pattern, value) = clone_fields(&variant.fields);
+				quote! { Self::#variant_name #pattern => Self::#variant_name #value }
+			});
+			quote! {
+				match self {
+					#(#variants),*
+				}
+			}
+		}
+		Data::Union(_) => abort!(input, "CowFamily can't be derived for unions"),
+	};
+
+	quote! {
+		impl #impl_generics ::core::clone::Clone for #ident #type_generics #where_clause {
+			fn clone(&self) -> Self {
+				#body
+			}
+		}
+	}
+}
+
+/// Returns the pattern for destructuring the fields by reference and the cloned fields
+fn clone_fields(fields: &Fields) -> (TokenStream, TokenStream) {
+	match fields {
+		Fields::Named(fields) => {
+			let names = fields
+				.named
+				.iter()
+				.map(|field| &field.ident)
+				.collect::<Vec<_>>();
+			(
+				quote! { { #(#names),* } },
+				quote! { { #(#names: ::core::clone::Clone::clone(#names)),* } },
+			)
+		}
+		Fields::Unnamed(fields) => {
+			let names = (0..fields.unnamed.len())
+				.map(tuple_field)
+				.collect::<Vec<_>>();
+			(
+				quote! { ( #(#names),* ) },
+				quote! { ( #(::core::clone::Clone::clone(#names)),* ) },
+			)
+		}
+		Fields::Unit => (quote! {}, quote! {}),
+	}
+}
+
+/// Generates `type FooOwned`, `Foo::into_static`, `Foo::reborrow` and `From<&Foo> for Foo`
+fn gen_helpers(
+	input: &DeriveInput,
+	shallowclone_attrs: &ContainerAttrs,
+	makeowned_attrs: &ContainerAttrs,
+) -> TokenStream {
+	let ident = &input.ident;
+	let vis = &input.vis;
+	let generics = attributes::without_attrs(&input.generics);
+	let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+
+	let shallowclone_names = Names::new(input, shallowclone_attrs);
+	let makeowned_names = Names::new(input, makeowned_attrs);
+	let makeowned_trait = makeowned_names.trait_path(DeriveType::MakeOwned);
+	let lifetime = &shallowclone_names.lifetime;
+	let krate = &shallowclone_names.krate;
+	// `ShallowClone` is derived with `gat` too, and unlike `ShallowCloneGat` its target isn't generic
+	let shallowclone_trait = quote! { #krate::ShallowClone<#lifetime> };
+
+	// the owned type alias, generic over everything except the lifetimes, which all become 'static
+	let alias = format_ident!("{}Owned", ident);
+	let alias_doc = format!("The `'static` version of [`{ident}`]");
+	let mut alias_params = Vec::new();
+	for generic in &input.generics.params {
+		match generic {
+			GenericParam::Lifetime(_) => {}
+			GenericParam::Type(param) => {
+				let name = &param.ident;
+				alias_params.push(quote! { #name });
+			}
+			GenericParam::Const(param) => {
+				let name = &param.ident;
+				let ty = &param.ty;
+				alias_params.push(quote! { const #name: #ty });
+			}
+		}
+	}
+	let owned_type = match &makeowned_attrs.target {
+		// written as a projection so that all generics are used
+		Some(_) => {
+			let generics = input.generics.params.iter().map(|generic| match generic {
+				GenericParam::Lifetime(_) => quote! { 'static },
+				GenericParam::Type(param) => {
+					let name = &param.ident;
+					quote! { #name }
+				}
+				GenericParam::Const(param) => {
+					let name = &param.ident;
+					quote! { #name }
+				}
+			});
+			quote! { <#ident<#(#generics),*> as #makeowned_trait>::Owned }
+		}
+		None => get_target_type(
+			input,
+			&quote! { #ident },
+			DeriveType::MakeOwned,
+			&makeowned_names,
+		),
+	};
+
+	// From<&Foo> can only be implemented if the target type doesn't use any generics in projections,
+	// otherwise they would be unconstrained
+	let usage = Usage::analyze(input, DeriveType::ShallowClone, &[]);
+	let has_mapped_type_params = input.generics.params.iter().any(|generic| match generic {
+		GenericParam::Type(param) => {
+			!attributes::is_generic_skipped(DeriveType::ShallowClone, generic)
+				&& usage.param(&param.ident).is_mapped()
+		}
+		_ => false,
+	}) || !usage.projections.is_empty();
+	let from_impl = if has_mapped_type_params {
+		quote! {}
+	} else {
+		let target_type = match &shallowclone_attrs.target {
+			Some(target) => quote! { #target },
+			None => get_target_type(
+				input,
+				&quote! { #ident },
+				DeriveType::ShallowClone,
+				&shallowclone_names,
+			),
+		};
+
+		let mut from_generics = generics.clone();
+		from_generics.params.insert(0, parse_quote! { #lifetime });
+		let (from_impl_generics, _, _) = from_generics.split_for_impl();
+		let orig_predicates = where_clause.iter().flat_map(|c| &c.predicates);
+
+		quote! {
+			impl #from_impl_generics ::core::convert::From<&#lifetime #ident #type_generics> for #target_type
+			where
+				#(#orig_predicates,)*
+				#ident #type_generics: #krate::ShallowClone<#lifetime, Target = #target_type>,
+			{
+				fn from(value: &#lifetime #ident #type_generics) -> Self {
+					<#ident #type_generics as #shallowclone_trait>::shallow_clone(value)
+				}
+			}
+		}
+	};
+
+	quote! {
+		#[doc = #alias_doc]
+		#vis type #alias<#(#alias_params),*> = #owned_type;
+
+		impl #impl_generics #ident #type_generics #where_clause {
+			/// Converts this value to be `'static`, using `MakeOwned`.
+			#vis fn into_static(self) -> <Self as #makeowned_trait>::Owned
+			where
+				Self: #makeowned_trait,
+			{
+				<Self as #makeowned_trait>::make_owned(self)
+			}
+
+			/// Borrows this value, using `ShallowClone`.
+			#vis fn reborrow<#lifetime>(&#lifetime self) -> <Self as #shallowclone_trait>::Target
+			where
+				Self: #shallowclone_trait,
+			{
+				<Self as #shallowclone_trait>::shallow_clone(self)
+			}
+		}
+
+		#from_impl
+	}
+}
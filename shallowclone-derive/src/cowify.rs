@@ -0,0 +1,140 @@
+use proc_macro2::TokenStream;
+use proc_macro_error::{abort, emit_error};
+use quote::quote;
+use syn::meta::ParseNestedMeta;
+use syn::visit_mut::{self, VisitMut};
+use syn::{parse_quote, Attribute, Data, DeriveInput, Field, LitStr, Path, Type};
+
+/// Common unsized types, which are converted to a [`Cow`][std::borrow::Cow] instead of a `CoCow`
+const UNSIZED: &[&str] = &["str", "Path", "OsStr", "CStr"];
+
+/// Arguments of the attribute, i.e. `#[cowify(...)]`
+#[derive(Default)]
+pub struct CowifyArgs {
+	/// `crate = "..."`, path to the `shallowclone` crate
+	krate: Option<LitStr>,
+}
+
+impl CowifyArgs {
+	pub fn parse(&mut self, meta: ParseNestedMeta) -> syn::Result<()> {
+		if meta.path.is_ident("crate") {
+			self.krate = Some(meta.value()?.parse()?);
+
+			Ok(())
+		} else {
+			Err(meta.error("Unknown attribute"))
+		}
+	}
+}
+
+/// Rewrites the references in the fields to copy-on-write types, and derives `ShallowClone` and `MakeOwned`
+pub fn cowify(args: CowifyArgs, mut input: DeriveInput) -> TokenStream {
+	let krate: Path = match &args.krate {
+		Some(krate) => match krate.parse() {
+			Ok(krate) => krate,
+			Err(e) => abort!(krate, "{}", e),
+		},
+		None => parse_quote! { ::shallowclone },
+	};
+	let mut rewriter = Rewriter { krate: &krate };
+
+	let fields: Vec<&mut Field> = match &mut input.data {
+		Data::Struct(data) => data.fields.iter_mut().collect(),
+		Data::Enum(data) => data
+			.variants
+			.iter_mut()
+			.flat_map(|variant| variant.fields.iter_mut())
+			.collect(),
+		Data::Union(_) => abort!(input, "`cowify` can't be used on unions"),
+	};
+	for field in fields {
+		if !take_skip_attr(&mut field.attrs) {
+			rewriter.visit_type_mut(&mut field.ty);
+		}
+	}
+
+	// the derives must come first, so that their helper attributes are recognized
+	input.attrs.insert(
+		0,
+		parse_quote! { #[derive(#krate::ShallowClone, #krate::MakeOwned)] },
+	);
+	if let Some(krate) = &args.krate {
+		input
+			.attrs
+			.push(parse_quote! { #[shallowclone(crate = #krate)] });
+		input
+			.attrs
+			.push(parse_quote! { #[makeowned(crate = #krate)] });
+	}
+
+	quote! { #input }
+}
+
+/// Removes `#[cowify(skip)]` from the attributes, returning whether it was there
+fn take_skip_attr(attrs: &mut Vec<Attribute>) -> bool {
+	let mut skip = false;
+	attrs.retain(|attr| {
+		if !attr.path().is_ident("cowify") {
+			return true;
+		}
+
+		let res = attr.parse_nested_meta(|meta| {
+			if meta.path.is_ident("skip") {
+				skip = true;
+				Ok(())
+			} else {
+				Err(meta.error("Unknown attribute"))
+			}
+		});
+		if let Err(e) = res {
+			emit_error!(e.span(), "{}", e);
+		}
+		false
+	});
+
+	skip
+}
+
+struct Rewriter<'a> {
+	krate: &'a Path,
+}
+
+impl VisitMut for Rewriter<'_> {
+	fn visit_type_mut(&mut self, ty: &mut Type) {
+		match ty {
+			// mutable references are left alone, they can't be replaced with copy-on-write types
+			Type::Reference(reference) if reference.mutability.is_none() => {
+				let lifetime = match &reference.lifetime {
+					Some(lifetime) => lifetime,
+					None => return,
+				};
+				let krate = self.krate;
+				let elem = &reference.elem;
+
+				*ty = match &**elem {
+					Type::Slice(slice) => {
+						let elem = &slice.elem;
+						parse_quote! { #krate::CoCowSlice<#lifetime, #elem> }
+					}
+					// unsized types can't be stored in a `CoCow`
+					Type::Path(path)
+						if path.qself.is_none()
+							&& path.path.segments.last().is_some_and(|segment| {
+								UNSIZED.iter().any(|name| segment.ident == name)
+							}) =>
+					{
+						parse_quote! { ::std::borrow::Cow<#lifetime, #elem> }
+					}
+					_ => parse_quote! { #krate::CoCow<#lifetime, #elem> },
+				};
+			}
+			Type::Path(path)
+				if path
+					.path
+					.segments
+					.last()
+					.is_some_and(|s| s.ident == "PhantomData") => {}
+			_ => visit_mut::visit_type_mut(self, ty),
+		}
+	}
+}
@@ -0,0 +1,44 @@
+use crate::{collect_lifetimes, unique_lifetime};
+use proc_macro2::TokenStream;
+use quote::quote;
+use std::collections::HashSet;
+use syn::parse::{Parse, ParseStream};
+use syn::{Lifetime, Macro, Token};
+
+/// Input of `__fresh_lifetimes!`, i.e. `'a 'b => path::to::macro! { ... }`
+pub struct FreshInput {
+	bases: Vec<Lifetime>,
+	callback: Macro,
+}
+
+impl Parse for FreshInput {
+	fn parse(input: ParseStream) -> syn::Result<Self> {
+		let mut bases = Vec::new();
+		while input.peek(syn::Lifetime) {
+			bases.push(input.parse()?);
+		}
+		input.parse::<Token![=>]>()?;
+		let callback = input.parse()?;
+
+		Ok(Self { bases, callback })
+	}
+}
+
+/// Implements `__fresh_lifetimes!`, calling the macro with lifetimes named like the given ones, but not
+/// used anywhere in its input, prepended to the input. This is how `macro_rules!` macros get lifetimes
+/// which don't collide with the ones of the user's types, since their lifetimes aren't hygienic.
+pub fn fresh_lifetimes(input: FreshInput) -> TokenStream {
+	let mut used = HashSet::new();
+	collect_lifetimes(input.callback.tokens.clone(), &mut used);
+	let fresh = input
+		.bases
+		.iter()
+		.map(|base| unique_lifetime(&mut used, &base.ident.to_string()))
+		.collect::<Vec<_>>();
+
+	let path = &input.callback.path;
+	let tokens = &input.callback.tokens;
+	quote! {
+		#path! { #(#fresh)* #tokens }
+	}
+}
@@ -1,18 +1,97 @@
-use crate::DeriveType;
+use crate::attributes::{self, ContainerAttrs, FieldAttrs, FieldMode};
+use crate::target_type::GenericsMapper;
+use crate::usage::{self, Detach};
+use crate::{DeriveType, Names};
 use proc_macro2::{Ident, Span, TokenStream};
-use quote::quote;
-use syn::{Data, DeriveInput, Fields, Index};
+use proc_macro_error::{abort, emit_error};
+use quote::{quote, quote_spanned};
+use syn::ext::IdentExt;
+use syn::spanned::Spanned;
+use syn::visit_mut::VisitMut;
+use syn::{Data, DeriveInput, Fields, Generics, Index, Member, PathArguments, Type};
 
-fn tuple_field(i: usize) -> Ident {
+pub fn tuple_field(i: usize) -> Ident {
 	Ident::new(&format!("f{i}"), Span::call_site())
 }
 
-pub fn gen_impl(derive_type: DeriveType, input: &DeriveInput) -> TokenStream {
-	let item_name = &input.ident;
+/// The method that the body is generated for
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Method {
+	/// `shallow_clone`, `make_owned` or `make_owned_for`
+	Convert,
+	/// `ToOwnedStatic::to_owned_static`, converting the fields from references
+	ByRef,
+	/// `TryMakeOwned::try_make_owned`, which takes the budget as `__budget` and returns early on errors
+	Fallible,
+	/// `MakeOwnedIn::make_owned_in`, which takes the arena as `__arena`
+	Arena,
+	/// `Rebase::rebase`, which takes the buffers as `__old` and `__new`
+	Rebase,
+}
+
+/// Generates the body of the trait method, and pushes any bounds required by the fields to `bounds`
+pub fn gen_impl(
+	derive_type: DeriveType,
+	method: Method,
+	input: &DeriveInput,
+	container_attrs: &ContainerAttrs,
+	names: &Names,
+	bounds: &mut Vec<TokenStream>,
+) -> TokenStream {
+	let mut mapper = GenericsMapper::new(input, derive_type, names);
+	let trait_path = names.trait_path(derive_type);
+	let type_params = input
+		.generics
+		.type_params()
+		.map(|param| &param.ident)
+		.collect::<Vec<_>>();
+
+	// the field types of a custom target type are unknown, so no bounds can be inferred for them
+	let mut discarded_bounds = Vec::new();
+	let bounds = match &container_attrs.target {
+		Some(_) => &mut discarded_bounds,
+		None => bounds,
+	};
+
+	// remote derives generate a free function taking `value` instead of a method on `self`
+	let (receiver, source) = match &container_attrs.remote {
+		Some(remote) => (quote! { value }, quote! { #remote }),
+		None => {
+			let ident = &input.ident;
+			(quote! { self }, quote! { #ident })
+		}
+	};
+
+	// the path used to construct the target value, generics are left for the compiler to infer
+	let item_name = match &container_attrs.target {
+		Some(target) => {
+			let mut path = target.path.clone();
+			for segment in &mut path.segments {
+				segment.arguments = PathArguments::None;
+			}
+			match &target.qself {
+				Some(_) => abort!(target, "expected a path to a struct or enum"),
+				None => quote! { #path },
+			}
+		}
+		None => source.clone(),
+	};
 
 	match &input.data {
 		Data::Struct(data) => {
-			let inner = gen_fields(derive_type, &data.fields, false);
+			let (_, inner) = gen_fields(
+				(derive_type, method),
+				&data.fields,
+				FieldsOf::Struct {
+					receiver: &receiver,
+					packed: attributes::is_packed(&input.attrs),
+				},
+				&trait_path,
+				&mut mapper,
+				&type_params,
+				(names, &input.generics),
+				bounds,
+			);
 
 			match &data.fields {
 				Fields::Named(_) => quote! {
@@ -25,82 +104,269 @@ pub fn gen_impl(derive_type: DeriveType, input: &DeriveInput) -> TokenStream {
 			}
 		}
 		Data::Enum(data) => {
-			let variants = data.variants.iter().map(|variant| {
-				let variant_name = &variant.ident;
+			let variants = data
+				.variants
+				.iter()
+				.map(|variant| {
+					let variant_name = &variant.ident;
 
-				let inner = gen_fields(derive_type, &variant.fields, true);
-
-				match &variant.fields {
-					Fields::Named(fields_named) => {
-						let fields_pat = fields_named.named.iter().map(|field| {
-							let field_name = &field.ident;
-							quote! { #field_name }
-						});
+					let (fields_pat, inner) = gen_fields(
+						(derive_type, method),
+						&variant.fields,
+						FieldsOf::Variant,
+						&trait_path,
+						&mut mapper,
+						&type_params,
+						(names, &input.generics),
+						bounds,
+					);
 
-						quote! {
-							Self::#variant_name { #(#fields_pat),* } => #item_name::#variant_name { #inner }
-						}
-					}
-					Fields::Unnamed(fields_unnamed) => {
-						let fields = fields_unnamed
-							.unnamed
-							.iter()
-							.enumerate()
-							.map(|(i, _)| tuple_field(i))
-							.collect::<Vec<_>>();
-						quote! {
-							Self::#variant_name ( #(#fields),* ) => #item_name::#variant_name ( #inner )
-						}
+					match &variant.fields {
+						Fields::Named(_) => quote! {
+							#source::#variant_name { #fields_pat } => #item_name::#variant_name { #inner }
+						},
+						Fields::Unnamed(_) => quote! {
+							#source::#variant_name ( #fields_pat ) => #item_name::#variant_name ( #inner )
+						},
+						Fields::Unit => quote! {
+						   #source::#variant_name => #item_name::#variant_name
+						},
 					}
-					Fields::Unit => quote! {
-					   Self::#variant_name => #item_name::#variant_name
-					},
-				}
-			});
+				})
+				.collect::<Vec<_>>();
 
 			quote! {
-				match self {
+				match #receiver {
 					#(#variants),*
 				}
 			}
 		}
-		Data::Union(_) => unimplemented!(),
+		Data::Union(data) => {
+			// the fields of a union can't be converted, since we don't know which one is active, so
+			// only `Copy` unions are supported, and they are simply copied
+			if let Some(target) = &container_attrs.target {
+				abort!(target, "a custom target type is not supported for unions");
+			}
+			if derive_type == DeriveType::MakeOwned {
+				if let Some(lifetime) = input.generics.lifetimes().next() {
+					abort!(
+						lifetime,
+						"unions with lifetimes can't implement `MakeOwned`";
+						help = "only `Copy` unions without references are supported"
+					);
+				}
+			}
+
+			let union_token = data.union_token;
+			let (_, type_generics, _) = input.generics.split_for_impl();
+			bounds.push(
+				quote_spanned! {union_token.span=> #source #type_generics: ::core::marker::Copy },
+			);
+			match (derive_type, method) {
+				(DeriveType::MakeOwned, Method::ByRef) | (DeriveType::ShallowClone, _) => {
+					quote! { *#receiver }
+				}
+				(DeriveType::MakeOwned, _) => quote! { #receiver },
+			}
+		}
 	}
 }
 
-fn gen_fields(derive_type: DeriveType, fields: &Fields, is_enum: bool) -> TokenStream {
-	let inner = fields.iter().enumerate().map(|(i, field)| {
-		let field_ident = match (derive_type, is_enum, &field.ident) {
-			(_, true, Some(ident)) => quote! { #ident },
-			(DeriveType::ShallowClone, false, Some(ident)) => quote! { &self.#ident },
-			(DeriveType::MakeOwned, false, Some(ident)) => quote! { self.#ident },
+/// Where the fields come from, which determines how they are accessed
+#[derive(Clone, Copy)]
+enum FieldsOf<'a> {
+	/// Accessed through the receiver (usually `self`), packed structs can only be accessed by value
+	Struct {
+		receiver: &'a TokenStream,
+		packed: bool,
+	},
+	/// Bound by the pattern of the match arm
+	Variant,
+}
+
+/// Returns the pattern for destructuring the fields (only used for enums) and the converted fields
+#[allow(clippy::too_many_arguments)]
+fn gen_fields(
+	(derive_type, method): (DeriveType, Method),
+	fields: &Fields,
+	fields_of: FieldsOf<'_>,
+	trait_path: &TokenStream,
+	mapper: &mut GenericsMapper,
+	type_params: &[&Ident],
+	(names, generics): (&Names, &Generics),
+	bounds: &mut Vec<TokenStream>,
+) -> (TokenStream, TokenStream) {
+	let mut patterns = Vec::new();
+	let mut values = Vec::new();
+	for (i, field) in fields.iter().enumerate() {
+		let attrs = FieldAttrs::parse(derive_type, field);
+
+		let member = match &field.ident {
+			Some(ident) => Member::Named(ident.clone()),
+			None => Member::Unnamed(Index::from(i)),
+		};
+		// fields of packed structs are copied, as they can't be borrowed
+		let packed = matches!(fields_of, FieldsOf::Struct { packed: true, .. })
+			&& derive_type == DeriveType::ShallowClone;
+		let field_ident = match (derive_type, fields_of) {
+			(_, FieldsOf::Variant) => match &field.ident {
+				Some(ident) => quote! { #ident },
+				None => {
+					let x = tuple_field(i);
+					quote! { #x }
+				}
+			},
+			(DeriveType::ShallowClone, FieldsOf::Struct { receiver, packed }) => {
+				if packed {
+					quote! { #receiver.#member }
+				} else {
+					quote! { &#receiver.#member }
+				}
+			}
+			(DeriveType::MakeOwned, FieldsOf::Struct { receiver, .. }) => match method {
+				Method::ByRef => quote! { &#receiver.#member },
+				_ => quote! { #receiver.#member },
+			},
+		};
+
+		let ty = &field.ty;
+		let mut target_ty = field.ty.clone();
+		mapper.changed = false;
+		mapper.visit_type_mut(&mut target_ty);
+		// bounds are only needed if the field type depends on the generics
+		let generic = mapper.changed
+			|| type_params
+				.iter()
+				.any(|param| usage::mentions(&field.ty, param));
 
-			(DeriveType::ShallowClone, false, None) => {
-				let i = Index::from(i);
-				quote! { &self.#i }
+		// spanned to the field, so that errors about missing impls point at it
+		let span = ty.span();
+		let value = match &attrs.mode {
+			FieldMode::Trait | FieldMode::Clone if packed => {
+				if generic {
+					bounds.push(quote_spanned! {span=> #ty: ::core::marker::Copy });
+				}
+				quote_spanned! {span=> { #field_ident } }
 			}
-			(DeriveType::MakeOwned, false, None) => {
-				let i = Index::from(i);
-				quote! { self.#i }
+			FieldMode::Trait => match derive_type {
+				DeriveType::ShallowClone if names.gat => {
+					quote_spanned! {span=> <_ as #trait_path>::shallow_clone_gat(#field_ident) }
+				}
+				DeriveType::ShallowClone => {
+					quote_spanned! {span=> <_ as #trait_path>::shallow_clone(#field_ident) }
+				}
+				DeriveType::MakeOwned if method == Method::ByRef => {
+					let krate = &names.krate;
+					if generic {
+						bounds.push(quote_spanned! {span=> #ty: #krate::ToOwnedStatic });
+					}
+					quote_spanned! {span=> <_ as #krate::ToOwnedStatic>::to_owned_static(#field_ident) }
+				}
+				DeriveType::MakeOwned => match usage::detach(ty, generics, &names.kept) {
+					Detach::All => {
+						if let Type::Reference(_) = strip_parens(ty) {
+							emit_error!(
+								ty,
+								"references can't be made owned";
+								help = "use `Cow` or `CoCow` instead, or convert the field with `#[makeowned(with = \"...\")]`"
+							);
+						}
+						match method {
+							Method::Fallible => {
+								let krate = &names.krate;
+								let name = match &field.ident {
+									Some(ident) => ident.unraw().to_string(),
+									None => i.to_string(),
+								};
+								quote_spanned! {span=>
+									<_ as #krate::TryMakeOwned>::try_make_owned(#field_ident, __budget)
+										.map_err(|e| e.in_field(#name))?
+								}
+							}
+							Method::Arena => quote_spanned! {span=>
+								<_ as #trait_path>::make_owned_in(#field_ident, __arena)
+							},
+							Method::Rebase => quote_spanned! {span=>
+								<_ as #trait_path>::rebase(#field_ident, __old, __new)
+							},
+							_ => {
+								quote_spanned! {span=> <_ as #trait_path>::make_owned(#field_ident) }
+							}
+						}
+					}
+					// only borrows for the kept lifetimes, so it already outlives 'keep
+					Detach::Nothing => quote! { #field_ident },
+					Detach::Partial => {
+						let impl_path = names.impl_path(derive_type);
+						let krate = &names.krate;
+						let keep = &names.keep;
+						bounds.push(quote_spanned! {span=>
+							#ty: #krate::MakeOwnedFor<#keep, Owned = #target_ty>
+						});
+						quote_spanned! {span=> <_ as #impl_path>::make_owned_for(#field_ident) }
+					}
+				},
+			},
+			FieldMode::Clone => {
+				if generic {
+					bounds.push(quote_spanned! {span=> #ty: ::core::clone::Clone });
+				}
+				quote_spanned! {span=> ::core::clone::Clone::clone(#field_ident) }
 			}
-			(_, true, None) => {
-				let x = tuple_field(i);
-				quote! { #x }
+			// the value can't be moved out of the reference, so it's cloned instead
+			FieldMode::Skip | FieldMode::With(_) if method == Method::ByRef => {
+				if generic {
+					bounds.push(quote_spanned! {span=> #ty: ::core::clone::Clone });
+				}
+				let value = quote_spanned! {span=> ::core::clone::Clone::clone(#field_ident) };
+				match &attrs.mode {
+					FieldMode::With(path) => quote! { #path(#value) },
+					_ => value,
+				}
+			}
+			FieldMode::Skip => quote! { #field_ident },
+			FieldMode::With(path) => {
+				if packed {
+					emit_error!(
+						path,
+						"`with` can't be used on fields of packed structs, since they can't be borrowed"
+					);
+				}
+				quote! { #path(#field_ident) }
+			}
+			FieldMode::Default => {
+				if generic {
+					bounds.push(quote_spanned! {span=> #target_ty: ::core::default::Default });
+				}
+				quote! { ::core::default::Default::default() }
 			}
 		};
 
-		let value = match derive_type {
-			DeriveType::ShallowClone => quote! { ShallowClone::shallow_clone(#field_ident) },
-			DeriveType::MakeOwned => quote! { MakeOwned::make_owned(#field_ident) },
-		};
+		// fields that are not used must not be bound, to avoid unused variable warnings
+		let is_used = !matches!(attrs.mode, FieldMode::Default);
+		patterns.push(match (&field.ident, is_used) {
+			(Some(field_name), true) => quote! { #field_name },
+			(Some(field_name), false) => quote! { #field_name: _ },
+			(None, true) => {
+				let x = tuple_field(i);
+				quote! { #x }
+			}
+			(None, false) => quote! { _ },
+		});
 
-		match &field.ident {
+		values.push(match &field.ident {
 			Some(field_name) => quote! { #field_name: #value },
 			None => quote! { #value },
-		}
-	});
+		});
+	}
+
+	(quote! { #(#patterns),* }, quote! { #(#values),* })
+}
 
-	quote! {
-		#(#inner),*
+fn strip_parens(ty: &Type) -> &Type {
+	match ty {
+		Type::Paren(paren) => strip_parens(&paren.elem),
+		Type::Group(group) => strip_parens(&group.elem),
+		_ => ty,
 	}
 }
@@ -0,0 +1,41 @@
+use crate::attributes::ContainerAttrs;
+use crate::walk::{gen_walk, gen_walk_impl, is_inspected};
+use crate::{DeriveType, Names};
+use proc_macro2::TokenStream;
+use proc_macro_error::abort;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::DeriveInput;
+
+/// Derives `HeapSize`, adding up the heap usage of the fields
+pub fn derive_heap_size(input: &DeriveInput) -> TokenStream {
+	let container_attrs = ContainerAttrs::parse(DeriveType::MakeOwned, &input.attrs);
+	if let Some(remote) = &container_attrs.remote {
+		abort!(remote, "`HeapSize` can't be derived together with `remote`");
+	}
+	let names = Names::new(input, &container_attrs);
+	let krate = &names.krate;
+	let bound = quote! { #krate::HeapSize };
+
+	let mut inferred_bounds = Vec::new();
+	let body = gen_walk(input, &bound, &mut inferred_bounds, |walked| {
+		let binding = &walked.binding;
+		is_inspected(walked.field).then(|| {
+			quote_spanned! {walked.field.ty.span()=>
+				#krate::HeapSize::add_heap_size(#binding, __usage);
+			}
+		})
+	});
+
+	gen_walk_impl(
+		input,
+		&container_attrs,
+		&bound,
+		inferred_bounds,
+		quote! {
+			fn add_heap_size(&self, __usage: &mut #krate::HeapUsage) {
+				#body
+			}
+		},
+	)
+}
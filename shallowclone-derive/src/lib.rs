@@ -1,14 +1,31 @@
 mod attributes;
+mod borrow_state;
+mod cow_family;
+mod cowify;
+mod fresh_lifetimes;
 mod gen_impl;
+mod heap_size;
+mod own_in_place;
+mod owned_args;
+mod shallow_view;
+mod subtype;
 mod target_type;
+mod usage;
+mod visit_cows;
+mod walk;
 
-use gen_impl::gen_impl;
+use attributes::ContainerAttrs;
+use gen_impl::{gen_impl, Method};
 use proc_macro::TokenStream;
-use proc_macro_error::proc_macro_error;
-use quote::quote;
-use syn::parse_macro_input;
+use proc_macro2::{Span, TokenTree};
+use proc_macro_error::{abort, emit_error, proc_macro_error};
+use quote::{format_ident, quote, ToTokens};
+use std::collections::HashSet;
+use syn::visit_mut::VisitMut;
+use syn::{parse_macro_input, parse_quote, Ident, Lifetime, Path, TypeParamBound, WherePredicate};
 use syn::{DeriveInput, GenericParam};
-use target_type::get_target_type;
+use target_type::{get_target_type, GenericsMapper};
+use usage::Usage;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 enum DeriveType {
@@ -16,26 +33,355 @@ enum DeriveType {
 	MakeOwned,
 }
 
+impl DeriveType {
+	/// The name of the helper attribute, i.e. `#[shallowclone(...)]`
+	fn attr_name(self) -> &'static str {
+		match self {
+			DeriveType::ShallowClone => "shallowclone",
+			DeriveType::MakeOwned => "makeowned",
+		}
+	}
+
+	/// The name of the attribute specifying a custom target type
+	fn target_attr_name(self) -> &'static str {
+		match self {
+			DeriveType::ShallowClone => "target",
+			DeriveType::MakeOwned => "owned",
+		}
+	}
+}
+
+/// Paths and lifetimes used in the generated code, chosen so that they don't collide with the input
+struct Names {
+	/// Path to the `shallowclone` crate, `::shallowclone` unless specified with the `crate` attribute
+	krate: Path,
+	/// The lifetime parameter of [`ShallowClone`], `'shallowclone` unless the input already uses it
+	lifetime: Lifetime,
+	/// Lifetimes which are not detached by `MakeOwned`, specified with the `keep` attribute
+	kept: Vec<Lifetime>,
+	/// The lifetime parameter of `MakeOwnedFor`, `'keep` unless the input already uses it
+	keep: Lifetime,
+	/// The lifetime that the lifetimes are changed to instead of `'static`, the parameter of
+	/// `MakeOwnedIn<'arena>` or `Rebase<'new>`, only set when deriving them
+	target_lifetime: Option<Lifetime>,
+	/// Whether `Rebase` is derived rather than `MakeOwnedIn`, if `target_lifetime` is set
+	rebase: bool,
+	/// Whether the fields are converted with `ShallowCloneGat`, set by the `gat` attribute
+	gat: bool,
+}
+
+impl Names {
+	fn new(input: &DeriveInput, container_attrs: &ContainerAttrs) -> Self {
+		let mut used = used_lifetimes(input);
+
+		Self {
+			krate: container_attrs
+				.krate
+				.clone()
+				.unwrap_or_else(|| parse_quote! { ::shallowclone }),
+			lifetime: unique_lifetime(&mut used, "shallowclone"),
+			kept: container_attrs.keep.clone(),
+			keep: unique_lifetime(&mut used, "keep"),
+			target_lifetime: match (container_attrs.arena, container_attrs.rebase) {
+				(true, _) => Some(unique_lifetime(&mut used, "arena")),
+				(_, true) => Some(unique_lifetime(&mut used, "new")),
+				_ => None,
+			},
+			rebase: container_attrs.rebase,
+			gat: container_attrs.gat,
+		}
+	}
+
+	/// The associated type of [`Names::trait_path`] for `MakeOwned`, `Rebased` for `Rebase` and `Owned`
+	/// otherwise
+	fn owned_type(&self) -> Ident {
+		match self.rebase {
+			true => format_ident!("Rebased"),
+			false => format_ident!("Owned"),
+		}
+	}
+
+	/// `ShallowClone<'shallowclone>` (`ShallowCloneGat` with `gat`), `MakeOwned`, `MakeOwnedIn<'arena>` or
+	/// `Rebase<'new>`, fully qualified
+	///
+	/// Note that with kept lifetimes `MakeOwnedFor<'keep>` is implemented instead, see [`Names::impl_path`]
+	fn trait_path(&self, derive_type: DeriveType) -> proc_macro2::TokenStream {
+		let krate = &self.krate;
+		let lifetime = &self.lifetime;
+
+		match (derive_type, &self.target_lifetime) {
+			(DeriveType::ShallowClone, _) if self.gat => quote! { #krate::ShallowCloneGat },
+			(DeriveType::ShallowClone, _) => quote! { #krate::ShallowClone<#lifetime> },
+			(DeriveType::MakeOwned, Some(new)) if self.rebase => quote! { #krate::Rebase<#new> },
+			(DeriveType::MakeOwned, Some(arena)) => quote! { #krate::MakeOwnedIn<#arena> },
+			(DeriveType::MakeOwned, None) => quote! { #krate::MakeOwned },
+		}
+	}
+
+	/// The trait that is actually implemented, `MakeOwnedFor<'keep>` if some lifetimes are kept
+	fn impl_path(&self, derive_type: DeriveType) -> proc_macro2::TokenStream {
+		let krate = &self.krate;
+		let keep = &self.keep;
+
+		match derive_type {
+			DeriveType::MakeOwned if !self.kept.is_empty() => {
+				quote! { #krate::MakeOwnedFor<#keep> }
+			}
+			_ => self.trait_path(derive_type),
+		}
+	}
+}
+
+/// Whether the predicate only has lifetime bounds, like `T: 'a` or `'a: 'b`
+fn is_outlives_only(predicate: &WherePredicate) -> bool {
+	match predicate {
+		WherePredicate::Lifetime(_) => true,
+		WherePredicate::Type(predicate) => predicate
+			.bounds
+			.iter()
+			.all(|bound| matches!(bound, TypeParamBound::Lifetime(_))),
+		_ => false,
+	}
+}
+
+/// The names of all lifetimes used anywhere in the input
+fn used_lifetimes(input: &DeriveInput) -> HashSet<String> {
+	let mut used = HashSet::new();
+	collect_lifetimes(input.to_token_stream(), &mut used);
+	used
+}
+
+/// Picks a lifetime named `base` (or `base1`, `base2`...) that is not in `used`, and marks it as used
+fn unique_lifetime(used: &mut HashSet<String>, base: &str) -> Lifetime {
+	let mut name = base.to_owned();
+	let mut i = 0;
+	while used.contains(&name) {
+		i += 1;
+		name = format!("{base}{i}");
+	}
+	let lifetime = Lifetime::new(&format!("'{name}"), Span::call_site());
+	used.insert(name);
+	lifetime
+}
+
+/// Collects the names of all lifetimes used anywhere in the tokens
+fn collect_lifetimes(tokens: proc_macro2::TokenStream, used: &mut HashSet<String>) {
+	let mut after_apostrophe = false;
+	for token in tokens {
+		match &token {
+			TokenTree::Ident(ident) if after_apostrophe => {
+				used.insert(ident.to_string());
+			}
+			TokenTree::Group(group) => collect_lifetimes(group.stream(), used),
+			_ => {}
+		}
+		after_apostrophe = matches!(&token, TokenTree::Punct(p) if p.as_char() == '\'');
+	}
+}
+
 #[proc_macro_error]
 #[proc_macro_derive(ShallowClone, attributes(shallowclone))]
 pub fn derive_shallowclone(input: TokenStream) -> TokenStream {
-	derive(input, DeriveType::ShallowClone)
+	let input = parse_macro_input!(input as DeriveInput);
+	let container_attrs = ContainerAttrs::parse(DeriveType::ShallowClone, &input.attrs);
+	derive(&input, DeriveType::ShallowClone, &container_attrs).into()
 }
 
 #[proc_macro_error]
 #[proc_macro_derive(MakeOwned, attributes(makeowned))]
 pub fn derive_makeowned(input: TokenStream) -> TokenStream {
-	derive(input, DeriveType::MakeOwned)
+	let input = parse_macro_input!(input as DeriveInput);
+	let container_attrs = ContainerAttrs::parse(DeriveType::MakeOwned, &input.attrs);
+	derive(&input, DeriveType::MakeOwned, &container_attrs).into()
+}
+
+#[proc_macro_error]
+#[proc_macro_derive(TryMakeOwned, attributes(makeowned))]
+pub fn derive_try_makeowned(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	let mut container_attrs = ContainerAttrs::parse(DeriveType::MakeOwned, &input.attrs);
+	container_attrs.fallible = true;
+	derive(&input, DeriveType::MakeOwned, &container_attrs).into()
 }
 
-fn derive(input: TokenStream, derive_type: DeriveType) -> TokenStream {
+#[proc_macro_error]
+#[proc_macro_derive(MakeOwnedIn, attributes(makeowned))]
+pub fn derive_makeowned_in(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	let mut container_attrs = ContainerAttrs::parse(DeriveType::MakeOwned, &input.attrs);
+	container_attrs.arena = true;
+	derive(&input, DeriveType::MakeOwned, &container_attrs).into()
+}
+
+#[proc_macro_error]
+#[proc_macro_derive(Rebase, attributes(makeowned))]
+pub fn derive_rebase(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	let mut container_attrs = ContainerAttrs::parse(DeriveType::MakeOwned, &input.attrs);
+	container_attrs.rebase = true;
+	derive(&input, DeriveType::MakeOwned, &container_attrs).into()
+}
+
+#[proc_macro_error]
+#[proc_macro_derive(OwnInPlace, attributes(makeowned))]
+pub fn derive_own_in_place(input: TokenStream) -> TokenStream {
 	let input = parse_macro_input!(input as DeriveInput);
+	own_in_place::derive_own_in_place(&input).into()
+}
 
+#[proc_macro_error]
+#[proc_macro_derive(BorrowState, attributes(makeowned))]
+pub fn derive_borrow_state(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	borrow_state::derive_borrow_state(&input).into()
+}
+
+#[proc_macro_error]
+#[proc_macro_derive(HeapSize, attributes(makeowned))]
+pub fn derive_heap_size(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	heap_size::derive_heap_size(&input).into()
+}
+
+#[proc_macro_error]
+#[proc_macro_derive(VisitCows, attributes(makeowned))]
+pub fn derive_visit_cows(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	visit_cows::derive_visit_cows(&input).into()
+}
+
+#[proc_macro_error]
+#[proc_macro_derive(CowFamily, attributes(shallowclone, makeowned))]
+pub fn derive_cow_family(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	cow_family::derive_cow_family(&input).into()
+}
+
+#[proc_macro_error]
+#[proc_macro_derive(ShallowView, attributes(shallowview))]
+pub fn derive_shallow_view(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	shallow_view::derive_shallow_view(&input).into()
+}
+
+#[proc_macro_error]
+#[proc_macro_attribute]
+pub fn cowify(args: TokenStream, input: TokenStream) -> TokenStream {
+	let mut cowify_args = cowify::CowifyArgs::default();
+	let parser = syn::meta::parser(|meta| cowify_args.parse(meta));
+	parse_macro_input!(args with parser);
+	let input = parse_macro_input!(input as DeriveInput);
+	cowify::cowify(cowify_args, input).into()
+}
+
+#[proc_macro_error]
+#[proc_macro_attribute]
+pub fn owned_args(args: TokenStream, input: TokenStream) -> TokenStream {
+	let mut owned_args_args = owned_args::OwnedArgsArgs::default();
+	let parser = syn::meta::parser(|meta| owned_args_args.parse(meta));
+	parse_macro_input!(args with parser);
+	let input = parse_macro_input!(input as syn::ItemFn);
+	owned_args::owned_args(owned_args_args, input).into()
+}
+
+#[proc_macro_error]
+#[proc_macro]
+pub fn assert_makeowned_subtype(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as subtype::AssertInput);
+	subtype::assert_makeowned_subtype(input).into()
+}
+
+#[doc(hidden)]
+#[proc_macro]
+pub fn __fresh_lifetimes(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as fresh_lifetimes::FreshInput);
+	fresh_lifetimes::fresh_lifetimes(input).into()
+}
+
+fn derive(
+	input: &DeriveInput,
+	derive_type: DeriveType,
+	container_attrs: &ContainerAttrs,
+) -> proc_macro2::TokenStream {
 	let ident = &input.ident;
+	let names = Names::new(input, container_attrs);
+	let lifetime = &names.lifetime;
+	let trait_path = names.trait_path(derive_type);
+	let impl_path = names.impl_path(derive_type);
+	let keep = &names.keep;
+
+	for kept in &names.kept {
+		if !input
+			.generics
+			.lifetimes()
+			.any(|param| param.lifetime == *kept)
+		{
+			emit_error!(kept, "`{}` is not a lifetime parameter of this type", kept);
+		}
+	}
+	if let (Some(remote), Some(_)) = (&container_attrs.remote, names.kept.first()) {
+		abort!(remote, "`keep` is not supported together with `remote`");
+	}
+	if let (Some(remote), true) = (&container_attrs.remote, container_attrs.gat) {
+		abort!(remote, "`gat` is not supported together with `remote`");
+	}
+	if let (Some(remote), true) = (&container_attrs.remote, container_attrs.fallible) {
+		abort!(
+			remote,
+			"`TryMakeOwned` can't be derived together with `remote`"
+		);
+	}
+	if let (Some(kept), true) = (names.kept.first(), container_attrs.fallible) {
+		abort!(kept, "`TryMakeOwned` can't be derived together with `keep`");
+	}
+	if let (Some(remote), true) = (&container_attrs.remote, container_attrs.arena) {
+		abort!(
+			remote,
+			"`MakeOwnedIn` can't be derived together with `remote`"
+		);
+	}
+	if let (Some(kept), true) = (names.kept.first(), container_attrs.arena) {
+		abort!(kept, "`MakeOwnedIn` can't be derived together with `keep`");
+	}
+	// the custom owned type is `'static`, and not the type with the lifetimes changed to 'arena
+	if let (Some(target), true) = (&container_attrs.target, container_attrs.arena) {
+		abort!(
+			target,
+			"`MakeOwnedIn` can't be derived together with `owned`"
+		);
+	}
+	if let (Some(remote), true) = (&container_attrs.remote, container_attrs.rebase) {
+		abort!(remote, "`Rebase` can't be derived together with `remote`");
+	}
+	if let (Some(kept), true) = (names.kept.first(), container_attrs.rebase) {
+		abort!(kept, "`Rebase` can't be derived together with `keep`");
+	}
+	if let (Some(target), true) = (&container_attrs.target, container_attrs.rebase) {
+		abort!(target, "`Rebase` can't be derived together with `owned`");
+	}
 
-	let target_type = match derive_type {
-		DeriveType::ShallowClone => get_target_type(&input, derive_type),
-		DeriveType::MakeOwned => get_target_type(&input, derive_type),
+	// the bound for converted types, optionally requiring the converted type to be `owned`,
+	// `TryMakeOwned` implies `MakeOwned`
+	let convert_bound = |owned: Option<&dyn ToTokens>| {
+		let krate = &names.krate;
+		let owned_type = names.owned_type();
+		let owned = owned.map(|owned| quote! { #owned_type = #owned });
+		match (&names.target_lifetime, container_attrs.fallible) {
+			(Some(new), _) if names.rebase => quote! { #krate::Rebase<#new, #owned> },
+			(Some(arena), _) => quote! { #krate::MakeOwnedIn<#arena, #owned> },
+			(None, true) => quote! { #krate::TryMakeOwned<#owned> },
+			(None, false) => quote! { #krate::MakeOwned<#owned> },
+		}
+	};
+
+	// for remote derives the conversion is between values of the foreign type
+	let item_path = match &container_attrs.remote {
+		Some(remote) => quote! { #remote },
+		None => quote! { #ident },
+	};
+	let target_type = match &container_attrs.target {
+		Some(target) => quote! { #target },
+		None => get_target_type(input, &item_path, derive_type, &names),
 	};
 
 	// i am actually at a loss of words. why do i have to reinvent the wheel every single
@@ -47,18 +393,33 @@ fn derive(input: TokenStream, derive_type: DeriveType) -> TokenStream {
 
 	let mut impl_generics = Vec::new();
 	let mut extra_bounds = Vec::new();
+	// bounds on type parameters, which can be overriden with the `bound` attribute
+	let mut inferred_bounds = Vec::new();
+	let usage = Usage::analyze(input, derive_type, &names.kept);
+	let krate = &names.krate;
 	for generic in &input.generics.params {
 		let skip = attributes::is_generic_skipped(derive_type, generic);
 
 		match generic {
 			GenericParam::Lifetime(lifetime_param) => {
-				let lifetime = &lifetime_param.lifetime;
+				let param_lifetime = &lifetime_param.lifetime;
 				let bounds = &lifetime_param.bounds;
 
-				impl_generics.push(quote! { #lifetime: #bounds });
+				impl_generics.push(quote! { #param_lifetime: #bounds });
 
-				if !skip && derive_type == DeriveType::ShallowClone {
-					extra_bounds.push(quote! { #lifetime: 'shallowclone });
+				// with `gat` this is implied by `Self: 'shallowclone`
+				if !skip && derive_type == DeriveType::ShallowClone && !container_attrs.gat {
+					extra_bounds.push(quote! { #param_lifetime: #lifetime });
+				}
+				if skip && derive_type == DeriveType::MakeOwned {
+					emit_error!(
+						param_lifetime,
+						"lifetimes can't be skipped by `MakeOwned`, since the owned type must be `'static`";
+						help = "use `#[makeowned(keep = {})]` on the type to keep it and implement `MakeOwnedFor` instead", param_lifetime
+					);
+				}
+				if names.kept.contains(param_lifetime) {
+					extra_bounds.push(quote! { #param_lifetime: #keep });
 				}
 			}
 			GenericParam::Type(type_param) => {
@@ -67,24 +428,38 @@ fn derive(input: TokenStream, derive_type: DeriveType) -> TokenStream {
 
 				impl_generics.push(quote! { #name: #bounds });
 
-				if skip {
+				// only the type parameters which are converted in the fields need to implement the trait
+				let param_usage = usage.param(name);
+				if skip || !param_usage.converted {
 					if derive_type == DeriveType::MakeOwned {
-						extra_bounds.push(quote! { #name: 'static });
+						// the owned type only has to outlive 'keep, 'arena or 'new
+						match (&names.target_lifetime, names.kept.is_empty()) {
+							(Some(target_lifetime), _) => {
+								inferred_bounds.push(quote! { #name: #target_lifetime })
+							}
+							(None, true) => inferred_bounds.push(quote! { #name: 'static }),
+							(None, false) => inferred_bounds.push(quote! { #name: #keep }),
+						}
 					}
-				} else {
+				} else if param_usage.kept {
+					// used both converted and as it is, so the conversion must not change it
 					match derive_type {
-						DeriveType::ShallowClone => {
-							extra_bounds.push(quote! { #name: ShallowClone<'shallowclone> });
-						}
+						DeriveType::ShallowClone => inferred_bounds.push(
+							quote! { #name: #krate::ShallowClone<#lifetime, Target = #name> },
+						),
 						DeriveType::MakeOwned => {
-							// the <T as MakeOwned>::Owned must be bound by the same bounds as T
-							// since we are gonna be using it in place of T
-							let orig_bounds = &type_param.bounds;
-
-							extra_bounds.push(quote! { #name: MakeOwned });
-							extra_bounds.push(quote! { <#name as MakeOwned>::Owned: #orig_bounds });
+							let bound = convert_bound(Some(name));
+							inferred_bounds.push(quote! { #name: #bound })
 						}
 					}
+				} else if container_attrs.gat {
+					inferred_bounds.push(quote! { #name: #krate::ShallowCloneGat });
+				} else {
+					let bound = match derive_type {
+						DeriveType::ShallowClone => trait_path.clone(),
+						DeriveType::MakeOwned => convert_bound(None),
+					};
+					inferred_bounds.push(quote! { #name: #bound });
 				}
 			}
 			GenericParam::Const(const_param) => {
@@ -96,71 +471,313 @@ fn derive(input: TokenStream, derive_type: DeriveType) -> TokenStream {
 		}
 	}
 
-	if derive_type == DeriveType::MakeOwned {
-		// Since MakeOwned extends Clone, we want to implement it only if Self: Clone
-		// but we cant just write this bound due to whatever reasons when there are lifetimes
-		// because Self in this context comes with the specific lifetimes, and basically
-		// Self<'static> ends up not included in the bound and then it in turn fucks up
-		// the associated type MakeOwned::Owned, which must be Clone, but when we write
-		// Self: Clone, the compiler starts assuming that Self<'static> is not Clone
-		// (as its not included in the bound)
-		// then you would think you could add another bound Self<'static>: Clone, but no,
-		// compiler complains with a bunch of other weird errors.
-		// Long story short this is most likely a bug in the compiler
+	// The target type must satisfy the same bounds as the original type, since we are gonna be
+	// using <T as MakeOwned>::Owned in place of T, so we duplicate all the bounds, both inline
+	// and in the where clause, replacing the generics accordingly
+	//
+	// A custom target type has its own bounds, which will be checked anyway
+	if container_attrs.target.is_none() {
+		for predicate in target_type::mirror_predicates(input, derive_type, &names) {
+			// with `gat` the outlives bounds follow from `Self: 'shallowclone`, which the compiler checks
+			if container_attrs.gat && is_outlives_only(&predicate) {
+				continue;
+			}
+			inferred_bounds.push(quote! { #predicate });
+		}
+	}
 
-		// get the generics with all lifetimes changed to 'any
-		let generics = input.generics.params.iter().map(|param| match param {
-			GenericParam::Lifetime(_) => quote! { 'any },
-			GenericParam::Type(t) => {
-				let ident = &t.ident;
-				quote! { #ident }
+	// projections like <T as Iterator>::Item can't be mapped by just mapping T, so they are bound directly
+	let mut mapper = GenericsMapper::new(input, derive_type, &names);
+	for projection in &usage.projections {
+		let mut mapped = projection.clone();
+		mapper.visit_type_mut(&mut mapped);
+		match derive_type {
+			DeriveType::ShallowClone => inferred_bounds
+				.push(quote! { #projection: #krate::ShallowClone<#lifetime, Target = #mapped> }),
+			DeriveType::MakeOwned => {
+				let bound = convert_bound(Some(&mapped));
+				inferred_bounds.push(quote! { #projection: #bound })
 			}
-			GenericParam::Const(c) => {
-				let ident = &c.ident;
-				quote! { #ident }
+		}
+	}
+
+	// fields with attributes may need some additional bounds
+	let impl_code = gen_impl(
+		derive_type,
+		match (
+			container_attrs.fallible,
+			container_attrs.arena,
+			container_attrs.rebase,
+		) {
+			(true, _, _) => Method::Fallible,
+			(_, true, _) => Method::Arena,
+			(_, _, true) => Method::Rebase,
+			_ => Method::Convert,
+		},
+		input,
+		container_attrs,
+		&names,
+		&mut inferred_bounds,
+	);
+
+	// only generated on request, since converting the fields from references requires them to be `Clone`.
+	// The other derives reading the `MakeOwned` attributes ignore it
+	let by_ref = match derive_type {
+		DeriveType::MakeOwned
+			if container_attrs.to_owned_static
+				&& !container_attrs.fallible
+				&& names.target_lifetime.is_none() =>
+		{
+			if let Some(remote) = &container_attrs.remote {
+				abort!(
+					remote,
+					"`to_owned_static` is not supported together with `remote`"
+				);
 			}
-		});
-		extra_bounds.push(quote! { for<'any> #ident <#(#generics),*>: Clone });
+			if let Some(kept) = names.kept.first() {
+				abort!(
+					kept,
+					"`to_owned_static` is not supported together with `keep`"
+				);
+			}
+			// fields of packed structs can't be borrowed
+			if attributes::is_packed(&input.attrs) {
+				abort!(
+					input.ident,
+					"`to_owned_static` is not supported for packed structs"
+				);
+			}
+
+			let mut by_ref_bounds = Vec::new();
+			let code = gen_impl(
+				derive_type,
+				Method::ByRef,
+				input,
+				container_attrs,
+				&names,
+				&mut by_ref_bounds,
+			);
+			Some((code, by_ref_bounds))
+		}
+		_ => None,
+	};
+
+	match &container_attrs.bound {
+		Some(bound) => extra_bounds.extend(bound.iter().map(|predicate| quote! { #predicate })),
+		None => extra_bounds.extend(inferred_bounds),
 	}
 
-	// For the MakeOwned:
-	//   We should also duplicate all bounds in the where clause, replacing T with <T as MakeOwned>::Owned
-	//   but thats quite complicated, so for now we just dont support where clauses
-	//
-	//   Another solution would be to use a #[shallowclone(bound = "")] attribute to specify the bounds
-	//   instead of trying to parse the where clause. hard to tell without any specific cases in mind
+	// the bounds of a `ShallowCloneGat` impl can't depend on the lifetime, since it's a parameter of the
+	// associated type and not of the impl
+	if container_attrs.gat {
+		let depends_on_lifetime = extra_bounds.iter().any(|bound| {
+			let mut used = HashSet::new();
+			collect_lifetimes(bound.clone(), &mut used);
+			used.contains(&lifetime.ident.to_string())
+		});
+		if depends_on_lifetime {
+			emit_error!(
+				input.generics,
+				"`gat` can't be used when the bounds of the implementation depend on the lifetime";
+				note = "this happens with trait bounds on converted type parameters, and type parameters which are also left as they are";
+				help = "remove `gat` to only implement `ShallowClone`"
+			);
+		}
+	}
 
-	let where_clause = where_clause
-		.map(|c| quote! { #c })
-		.unwrap_or(quote! { where });
+	let orig_predicates = where_clause.iter().flat_map(|c| &c.predicates);
 	let where_clause = quote! {
-		#where_clause
-		#(#extra_bounds),*
+		where #(#orig_predicates,)* #(#extra_bounds),*
+	};
+
+	let to_owned_static = match by_ref {
+		Some((code, by_ref_bounds)) => {
+			let orig_predicates = input
+				.generics
+				.where_clause
+				.iter()
+				.flat_map(|c| &c.predicates);
+			quote! {
+				impl<#(#impl_generics),*> #krate::ToOwnedStatic for #ident #type_generics
+				where #(#orig_predicates,)* #(#extra_bounds,)* #(#by_ref_bounds),* {
+					fn to_owned_static(&self) -> <Self as #trait_path>::Owned {
+						#code
+					}
+				}
+			}
+		}
+		None => quote! {},
+	};
+
+	// a custom owned type breaks the subtype contract on purpose, so it's not checked, and the
+	// `TryMakeOwned` derive leaves it to the `MakeOwned` one
+	let subtype_assertion = match derive_type {
+		DeriveType::MakeOwned
+			if container_attrs.target.is_none()
+				&& container_attrs.remote.is_none()
+				&& !container_attrs.fallible
+				&& names.target_lifetime.is_none() =>
+		{
+			// the subtype relation can only hold if the converted type parameters don't change
+			let unchanged_params = input.generics.type_params().filter_map(|param| {
+				let name = &param.ident;
+				let skip =
+					attributes::is_generic_skipped(derive_type, &GenericParam::Type(param.clone()));
+				(!skip && usage.param(name).is_mapped())
+					.then(|| quote! { #name: #krate::MakeOwned<Owned = #name> })
+			});
+			let orig_predicates = input
+				.generics
+				.where_clause
+				.iter()
+				.flat_map(|c| &c.predicates)
+				.map(|predicate| quote! { #predicate });
+			let params = match names.kept.is_empty() {
+				true => quote! { #(#impl_generics),* },
+				false => quote! { #keep, #(#impl_generics),* },
+			};
+			subtype::gen_subtype_assertion(
+				&impl_path,
+				&params,
+				&quote! { #ident #type_generics },
+				orig_predicates
+					.chain(extra_bounds.iter().cloned())
+					.chain(unchanged_params),
+			)
+		}
+		_ => quote! {},
 	};
 
-	let impl_code = gen_impl(derive_type, &input);
+	if container_attrs.remote.is_some() {
+		// serde-like remote derive, the functions can be used with the `with` field attribute
+		let vis = &input.vis;
+		let orig_predicates = input
+			.generics
+			.where_clause
+			.iter()
+			.flat_map(|c| &c.predicates);
+		let remote_type = quote! { #item_path #type_generics };
+		let function = match derive_type {
+			DeriveType::ShallowClone => quote! {
+				#vis fn shallow_clone<#lifetime>(value: &#lifetime #remote_type) -> #target_type
+				#where_clause {
+					#impl_code
+				}
+			},
+			DeriveType::MakeOwned => quote! {
+				#vis fn make_owned(value: #remote_type) -> #target_type
+				#where_clause {
+					#impl_code
+				}
+			},
+		};
+
+		return quote! {
+			impl<#(#impl_generics),*> #ident #type_generics
+			where #(#orig_predicates),* {
+				#function
+			}
+		};
+	}
 
 	match derive_type {
+		// the budget is named so that it doesn't collide with the fields bound by the patterns of enums
+		DeriveType::MakeOwned if container_attrs.fallible => quote! {
+			impl<#(#impl_generics),*> #krate::TryMakeOwned for #ident #type_generics
+			#where_clause {
+				fn try_make_owned(self, __budget: &mut #krate::Budget) -> ::core::result::Result<<Self as #trait_path>::Owned, #krate::OwnError> {
+					::core::result::Result::Ok(#impl_code)
+				}
+			}
+		},
+		DeriveType::MakeOwned if container_attrs.rebase => {
+			let new = &names.target_lifetime;
+			quote! {
+				impl<#new, #(#impl_generics),*> #trait_path for #ident #type_generics
+				#where_clause {
+					type Rebased = #target_type;
+
+					fn rebase(self, __old: &[u8], __new: &#new [u8]) -> <Self as #trait_path>::Rebased {
+						#impl_code
+					}
+				}
+			}
+		}
+		DeriveType::MakeOwned if container_attrs.arena => {
+			let arena = &names.target_lifetime;
+			quote! {
+				impl<#arena, #(#impl_generics),*> #trait_path for #ident #type_generics
+				#where_clause {
+					type Owned = #target_type;
+
+					fn make_owned_in(self, __arena: &#arena #krate::__private::Bump) -> <Self as #trait_path>::Owned {
+						#impl_code
+					}
+				}
+			}
+		}
+		// `ShallowClone` is implemented too, since the traits are independent
+		DeriveType::ShallowClone if container_attrs.gat => {
+			let orig_predicates = input
+				.generics
+				.where_clause
+				.iter()
+				.flat_map(|c| &c.predicates);
+			quote! {
+				impl<#(#impl_generics),*> #krate::ShallowCloneGat for #ident #type_generics
+				#where_clause {
+					type Target<#lifetime> = #target_type where Self: #lifetime;
+
+					fn shallow_clone_gat<#lifetime>(&#lifetime self) -> <Self as #krate::ShallowCloneGat>::Target<#lifetime> {
+						#impl_code
+					}
+				}
+
+				impl<#lifetime, #(#impl_generics),*> #krate::ShallowClone<#lifetime> for #ident #type_generics
+				where #(#orig_predicates,)* #(#extra_bounds,)* Self: #lifetime {
+					type Target = <Self as #krate::ShallowCloneGat>::Target<#lifetime>;
+
+					fn shallow_clone(&#lifetime self) -> <Self as #krate::ShallowClone<#lifetime>>::Target {
+						<Self as #krate::ShallowCloneGat>::shallow_clone_gat(self)
+					}
+				}
+			}
+		}
 		DeriveType::ShallowClone => quote! {
-			impl<'shallowclone, #(#impl_generics),*> ShallowClone<'shallowclone> for #ident #type_generics
+			impl<#lifetime, #(#impl_generics),*> #trait_path for #ident #type_generics
 			#where_clause {
 				type Target = #target_type;
 
-				fn shallow_clone(&'shallowclone self) -> <Self as ShallowClone<'shallowclone>>::Target {
+				fn shallow_clone(&#lifetime self) -> <Self as #trait_path>::Target {
 					#impl_code
 				}
 			}
 		},
+		DeriveType::MakeOwned if !names.kept.is_empty() => quote! {
+			impl<#keep, #(#impl_generics),*> #impl_path for #ident #type_generics
+			#where_clause {
+				type Owned = #target_type;
+
+				fn make_owned_for(self) -> <Self as #impl_path>::Owned {
+					#impl_code
+				}
+			}
+
+			#subtype_assertion
+		},
 		DeriveType::MakeOwned => quote! {
-			impl<#(#impl_generics),*> MakeOwned for #ident #type_generics
+			impl<#(#impl_generics),*> #trait_path for #ident #type_generics
 			#where_clause {
 				type Owned = #target_type;
 
-				fn make_owned(self) -> <Self as MakeOwned>::Owned {
+				fn make_owned(self) -> <Self as #trait_path>::Owned {
 					#impl_code
 				}
 			}
+
+			#to_owned_static
+
+			#subtype_assertion
 		},
 	}
-	.into()
 }
@@ -0,0 +1,55 @@
+use crate::attributes::{ContainerAttrs, FieldAttrs, FieldMode};
+use crate::usage::{self, Detach};
+use crate::walk::{gen_walk, gen_walk_impl};
+use crate::{DeriveType, Names};
+use proc_macro2::TokenStream;
+use proc_macro_error::abort;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::DeriveInput;
+
+/// Derives `OwnInPlace`, owning the fields in place in the same way as `MakeOwned` would convert them
+pub fn derive_own_in_place(input: &DeriveInput) -> TokenStream {
+	let container_attrs = ContainerAttrs::parse(DeriveType::MakeOwned, &input.attrs);
+	if let Some(remote) = &container_attrs.remote {
+		abort!(
+			remote,
+			"`OwnInPlace` can't be derived together with `remote`"
+		);
+	}
+	let names = Names::new(input, &container_attrs);
+	let krate = &names.krate;
+
+	let mut inferred_bounds = Vec::new();
+	let body = gen_walk(
+		input,
+		&quote! { #krate::OwnInPlace },
+		&mut inferred_bounds,
+		|walked| {
+			// fields with attributes are not converted by `MakeOwned` either, and neither are the
+			// fields which only borrow for kept lifetimes
+			let attrs = FieldAttrs::parse(DeriveType::MakeOwned, walked.field);
+			let ty = &walked.field.ty;
+			if !matches!(attrs.mode, FieldMode::Trait)
+				|| usage::detach(ty, &input.generics, &names.kept) == Detach::Nothing
+			{
+				return None;
+			}
+
+			let binding = &walked.binding;
+			Some(quote_spanned! {ty.span()=> #krate::OwnInPlace::own_in_place(#binding); })
+		},
+	);
+
+	gen_walk_impl(
+		input,
+		&container_attrs,
+		&quote! { #krate::OwnInPlace },
+		inferred_bounds,
+		quote! {
+			fn own_in_place(&mut self) {
+				#body
+			}
+		},
+	)
+}
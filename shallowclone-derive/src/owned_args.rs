@@ -0,0 +1,143 @@
+use proc_macro2::TokenStream;
+use proc_macro_error::{abort, emit_error};
+use quote::{format_ident, quote, quote_spanned, ToTokens};
+use syn::meta::ParseNestedMeta;
+use syn::spanned::Spanned;
+use syn::{
+	parse_quote, Attribute, FnArg, GenericParam, ItemFn, LitStr, Pat, PatIdent, Path, ReturnType,
+	Type,
+};
+
+/// Arguments of the attribute, i.e. `#[owned_args(...)]`
+#[derive(Default)]
+pub struct OwnedArgsArgs {
+	/// `crate = "..."`, path to the `shallowclone` crate
+	krate: Option<LitStr>,
+}
+
+impl OwnedArgsArgs {
+	pub fn parse(&mut self, meta: ParseNestedMeta) -> syn::Result<()> {
+		if meta.path.is_ident("crate") {
+			self.krate = Some(meta.value()?.parse()?);
+
+			Ok(())
+		} else {
+			Err(meta.error("Unknown attribute"))
+		}
+	}
+}
+
+/// Converts the arguments of the function with `MakeOwned` at entry, and makes async functions return a
+/// `'static` future
+pub fn owned_args(args: OwnedArgsArgs, mut input: ItemFn) -> TokenStream {
+	let krate: Path = match &args.krate {
+		Some(krate) => match krate.parse() {
+			Ok(krate) => krate,
+			Err(e) => abort!(krate, "{}", e),
+		},
+		None => parse_quote! { ::shallowclone },
+	};
+
+	let mut conversions = Vec::new();
+	let mut has_receiver = false;
+	let mut has_impl_trait = false;
+	for (i, arg) in input.sig.inputs.iter_mut().enumerate() {
+		let arg = match arg {
+			// the receiver can't be replaced, so it's left as it is
+			FnArg::Receiver(_) => {
+				has_receiver = true;
+				continue;
+			}
+			FnArg::Typed(arg) => arg,
+		};
+		if matches!(*arg.ty, Type::ImplTrait(_)) {
+			has_impl_trait = true;
+		}
+		if take_borrowed_attr(&mut arg.attrs) {
+			continue;
+		}
+
+		// the argument is bound to a plain identifier, and the original pattern is used for the converted value
+		let ident = match &*arg.pat {
+			Pat::Ident(PatIdent {
+				by_ref: None,
+				ident,
+				subpat: None,
+				..
+			}) => ident.clone(),
+			pat => format_ident!("__arg{}", i, span = pat.span()),
+		};
+		let pat = std::mem::replace(&mut *arg.pat, parse_quote! { #ident });
+
+		// spanned to the type, so that a missing impl points at it
+		let span = arg.ty.span();
+		let krate = krate.to_token_stream().into_iter().map(|mut token| {
+			token.set_span(span);
+			token
+		});
+		conversions.push(quote_spanned! {span=>
+			let #pat = #(#krate)*::MakeOwned::make_owned(#ident);
+		});
+	}
+
+	let block = &input.block;
+	if input.sig.asyncness.is_none() {
+		input.block = parse_quote! {{
+			#(#conversions)*
+			#block
+		}};
+
+		return quote! { #input };
+	}
+
+	// async fn foo(..) -> R  becomes  fn foo(..) -> impl Future<Output = R> + 'static
+	let output = match &input.sig.output {
+		ReturnType::Default => quote! { () },
+		ReturnType::Type(_, ty) => quote! { #ty },
+	};
+	// the future must not capture the lifetimes of the arguments, which it would by default in edition 2024,
+	// but the type parameters of the impl block of a method can't be listed here, so methods are left alone
+	let captures = if has_receiver || has_impl_trait {
+		quote! {}
+	} else {
+		let params = input
+			.sig
+			.generics
+			.params
+			.iter()
+			.filter_map(|param| match param {
+				GenericParam::Type(param) => Some(&param.ident),
+				GenericParam::Const(param) => Some(&param.ident),
+				GenericParam::Lifetime(_) => None,
+			});
+		quote! { + use<#(#params),*> }
+	};
+	let async_token = input.sig.asyncness.take();
+	input.sig.output = parse_quote! {
+		-> impl ::core::future::Future<Output = #output> + 'static #captures
+	};
+	input.block = parse_quote! {{
+		#(#conversions)*
+		#async_token move #block
+	}};
+
+	quote! { #input }
+}
+
+/// Removes `#[borrowed]` from the attributes of an argument, returning whether it was there
+fn take_borrowed_attr(attrs: &mut Vec<Attribute>) -> bool {
+	let mut borrowed = false;
+	attrs.retain(|attr| {
+		if !attr.path().is_ident("borrowed") {
+			return true;
+		}
+
+		if let Err(e) = attr.meta.require_path_only() {
+			emit_error!(e.span(), "{}", e);
+		}
+		borrowed = true;
+		false
+	});
+
+	borrowed
+}
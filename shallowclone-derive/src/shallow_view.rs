@@ -0,0 +1,470 @@
+use crate::attributes::{self, ViewAttrs};
+use crate::gen_impl::tuple_field;
+use crate::{collect_lifetimes, unique_lifetime, used_lifetimes};
+use proc_macro2::{Ident, TokenStream};
+use proc_macro_error::{abort, emit_error};
+use quote::{format_ident, quote, ToTokens};
+use std::collections::HashSet;
+use syn::visit_mut::{self, VisitMut};
+use syn::{
+	parse_quote, Data, DeriveInput, Fields, GenericArgument, GenericParam, Lifetime, PathArguments,
+	Type, WherePredicate,
+};
+
+/// Derives `FooView<'view>`, a borrowed version of `Foo`, plus `Foo::view` and `FooView::to_owned`
+pub fn derive_shallow_view(input: &DeriveInput) -> TokenStream {
+	let attrs = ViewAttrs::parse(&input.attrs);
+	let ident = &input.ident;
+	let vis = &input.vis;
+	let view_ident = format_ident!("{}View", ident);
+	let view_doc = format!("A borrowed view of [`{ident}`], see [`{ident}::view`]");
+
+	let mut used = used_lifetimes(input);
+	let lifetime = unique_lifetime(&mut used, "view");
+	let mut mapper = LifetimeMapper {
+		lifetimes: input
+			.generics
+			.lifetimes()
+			.map(|param| param.lifetime.ident.clone())
+			.collect(),
+		view: lifetime.clone(),
+	};
+	let type_params = input
+		.generics
+		.type_params()
+		.map(|param| &param.ident)
+		.collect::<Vec<_>>();
+	let mut converter = Converter {
+		lifetime: &lifetime,
+		mapper: &mut mapper,
+		type_params: &type_params,
+		view_bounds: Vec::new(),
+		owned_bounds: Vec::new(),
+	};
+
+	// the view type definition, and the bodies of `view` and `to_owned`
+	let (definition, view_body, owned_body) = match &input.data {
+		Data::Struct(data) => {
+			let fields = converter.convert_fields(&data.fields);
+			let (definition, pattern, view_value, owned_value) = fields.into_parts();
+			(
+				definition,
+				quote! {
+					let #ident #pattern = self;
+					#view_ident #view_value
+				},
+				quote! {
+					let #view_ident #pattern = self;
+					#ident #owned_value
+				},
+			)
+		}
+		Data::Enum(data) => {
+			let mut definitions = Vec::new();
+			let mut view_arms = Vec::new();
+			let mut owned_arms = Vec::new();
+			for variant in &data.variants {
+				let variant_name = &variant.ident;
+				let fields = converter.convert_fields(&variant.fields);
+				let (definition, pattern, view_value, owned_value) = fields.into_parts();
+
+				let variant_attrs = variant
+					.attrs
+					.iter()
+					.filter(|attr| attr.path().is_ident("doc"));
+				definitions.push(quote! { #(#variant_attrs)* #variant_name #definition });
+				view_arms.push(quote! {
+					#ident::#variant_name #pattern => #view_ident::#variant_name #view_value
+				});
+				owned_arms.push(quote! {
+					#view_ident::#variant_name #pattern => #ident::#variant_name #owned_value
+				});
+			}
+			(
+				quote! { { #(#definitions),* } },
+				quote! { match self { #(#view_arms),* } },
+				quote! { match self { #(#owned_arms),* } },
+			)
+		}
+		Data::Union(_) => abort!(input, "ShallowView can't be derived for unions"),
+	};
+	let Converter {
+		view_bounds,
+		owned_bounds,
+		..
+	} = converter;
+
+	// the view lifetime is only a parameter of the view type if some field actually borrows
+	let mut view_lifetimes = HashSet::new();
+	collect_lifetimes(definition.clone(), &mut view_lifetimes);
+	let borrows = view_lifetimes.contains(&lifetime.ident.to_string());
+
+	// the generics of the view, the view lifetime replaces all lifetimes of the input
+	let mut view_generics = input.generics.clone();
+	view_generics.params = view_generics
+		.params
+		.into_iter()
+		.filter_map(|mut param| match &mut param {
+			GenericParam::Lifetime(_) => None,
+			GenericParam::Type(type_param) => {
+				type_param.attrs.clear();
+				Some(param)
+			}
+			GenericParam::Const(const_param) => {
+				const_param.attrs.clear();
+				Some(param)
+			}
+		})
+		.collect();
+	mapper.visit_generics_mut(&mut view_generics);
+	if let Some(where_clause) = &mut view_generics.where_clause {
+		where_clause.predicates = std::mem::take(&mut where_clause.predicates)
+			.into_iter()
+			.filter(|predicate| !matches!(predicate, WherePredicate::Lifetime(_)))
+			.collect();
+	}
+	if borrows {
+		view_generics.params.insert(0, parse_quote! { #lifetime });
+		let where_clause = view_generics.make_where_clause();
+		for param in &type_params {
+			where_clause
+				.predicates
+				.push(parse_quote! { #param: #lifetime });
+		}
+	}
+	let (view_impl_generics, view_type_generics, view_where_clause) =
+		view_generics.split_for_impl();
+	let view_predicates = view_where_clause.iter().flat_map(|c| &c.predicates);
+
+	let mut owned_type: Type = {
+		let (_, type_generics, _) = input.generics.split_for_impl();
+		parse_quote! { #ident #type_generics }
+	};
+	mapper.visit_type_mut(&mut owned_type);
+
+	let definition = match &input.data {
+		Data::Struct(data) => match &data.fields {
+			Fields::Named(_) => quote! {
+				#vis struct #view_ident #view_impl_generics #view_where_clause #definition
+			},
+			_ => quote! {
+				#vis struct #view_ident #view_impl_generics #definition #view_where_clause;
+			},
+		},
+		_ => quote! {
+			#vis enum #view_ident #view_impl_generics #view_where_clause #definition
+		},
+	};
+	let derives = &attrs.derives;
+	let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+	let view_fn_lifetime = match borrows {
+		true => quote! { <#lifetime> },
+		false => quote! {},
+	};
+	let view_self = match borrows {
+		true => quote! { &#lifetime self },
+		false => quote! { &self },
+	};
+
+	quote! {
+		#[doc = #view_doc]
+		#[derive(#(#derives),*)]
+		#definition
+
+		impl #impl_generics #ident #type_generics #where_clause {
+			/// Borrows this value as a view, without cloning anything that is stored on the heap.
+			#vis fn view #view_fn_lifetime(#view_self) -> #view_ident #view_type_generics
+			where
+				#(#view_predicates,)*
+				#(#view_bounds),*
+			{
+				#view_body
+			}
+		}
+
+		impl #view_impl_generics #view_ident #view_type_generics #view_where_clause {
+			/// Clones the view into an owned value.
+			#vis fn to_owned(&self) -> #owned_type
+			where
+				#(#owned_bounds),*
+			{
+				#owned_body
+			}
+		}
+	}
+}
+
+/// Replaces all lifetimes of the input with the view lifetime
+struct LifetimeMapper {
+	lifetimes: HashSet<Ident>,
+	view: Lifetime,
+}
+
+impl VisitMut for LifetimeMapper {
+	fn visit_lifetime_mut(&mut self, lifetime: &mut Lifetime) {
+		if self.lifetimes.contains(&lifetime.ident) {
+			*lifetime = self.view.clone();
+		}
+	}
+}
+
+/// How a single field is converted
+struct Conversion {
+	/// The type of the field in the view
+	ty: Type,
+	/// Converts `value: &Field` to the view field
+	view: TokenStream,
+	/// Converts `value: &ViewField` back to the owned field
+	owned: TokenStream,
+}
+
+/// The converted fields of a struct or a variant
+struct ConvertedFields<'a> {
+	fields: &'a Fields,
+	conversions: Vec<Conversion>,
+}
+
+impl ConvertedFields<'_> {
+	/// Returns the definition of the view fields, the pattern binding all fields, and the values of the
+	/// view and owned fields
+	fn into_parts(self) -> (TokenStream, TokenStream, TokenStream, TokenStream) {
+		let mut definitions = Vec::new();
+		let mut bindings = Vec::new();
+		let mut views = Vec::new();
+		let mut owned = Vec::new();
+		for (i, (field, conversion)) in self.fields.iter().zip(self.conversions).enumerate() {
+			let field_vis = &field.vis;
+			let docs = field
+				.attrs
+				.iter()
+				.filter(|attr| attr.path().is_ident("doc"));
+			let ty = &conversion.ty;
+			let view = &conversion.view;
+			let owned_value = &conversion.owned;
+
+			let binding = match &field.ident {
+				Some(name) => {
+					definitions.push(quote! { #(#docs)* #field_vis #name: #ty });
+					name.clone()
+				}
+				None => {
+					definitions.push(quote! { #(#docs)* #field_vis #ty });
+					tuple_field(i)
+				}
+			};
+			let (view, owned_value) = (
+				quote! { { let value = #binding; #view } },
+				quote! { { let value = #binding; #owned_value } },
+			);
+			match &field.ident {
+				Some(name) => {
+					views.push(quote! { #name: #view });
+					owned.push(quote! { #name: #owned_value });
+				}
+				None => {
+					views.push(view);
+					owned.push(owned_value);
+				}
+			}
+			bindings.push(binding);
+		}
+
+		match self.fields {
+			Fields::Named(_) => (
+				quote! { { #(#definitions),* } },
+				quote! { { #(#bindings),* } },
+				quote! { { #(#views),* } },
+				quote! { { #(#owned),* } },
+			),
+			Fields::Unnamed(_) => (
+				quote! { ( #(#definitions),* ) },
+				quote! { ( #(#bindings),* ) },
+				quote! { ( #(#views),* ) },
+				quote! { ( #(#owned),* ) },
+			),
+			Fields::Unit => (quote! {}, quote! {}, quote! {}, quote! {}),
+		}
+	}
+}
+
+struct Converter<'a> {
+	lifetime: &'a Lifetime,
+	mapper: &'a mut LifetimeMapper,
+	type_params: &'a [&'a Ident],
+	/// Bounds required by `Foo::view`
+	view_bounds: Vec<TokenStream>,
+	/// Bounds required by `FooView::to_owned`
+	owned_bounds: Vec<TokenStream>,
+}
+
+impl Converter<'_> {
+	fn convert_fields<'f>(&mut self, fields: &'f Fields) -> ConvertedFields<'f> {
+		let conversions = fields
+			.iter()
+			.map(|field| {
+				if attributes::is_view_field_cloned(field) {
+					let ty = &field.ty;
+					let mapped = self.map_lifetimes(ty);
+					if self.is_generic(ty) {
+						self.view_bounds.push(quote! { #ty: ::core::clone::Clone });
+						self.owned_bounds
+							.push(quote! { #mapped: ::core::clone::Clone });
+					}
+					Conversion {
+						ty: mapped,
+						view: quote! { ::core::clone::Clone::clone(value) },
+						owned: quote! { ::core::clone::Clone::clone(value) },
+					}
+				} else {
+					self.convert(&field.ty)
+				}
+			})
+			.collect();
+
+		ConvertedFields {
+			fields,
+			conversions,
+		}
+	}
+
+	/// Picks the view type for a field based on its type
+	fn convert(&mut self, ty: &Type) -> Conversion {
+		let lifetime = self.lifetime;
+		match ty {
+			Type::Paren(paren) => return self.convert(&paren.elem),
+			Type::Group(group) => return self.convert(&group.elem),
+			Type::Reference(reference) => {
+				if reference.mutability.is_some() {
+					emit_error!(ty, "mutable references can't be viewed");
+				}
+				// shared references are just copied
+				return Conversion {
+					ty: self.map_lifetimes(ty),
+					view: quote! { *value },
+					owned: quote! { *value },
+				};
+			}
+			Type::Path(type_path) if type_path.qself.is_none() => {
+				let last = type_path.path.segments.last().unwrap();
+				let args = match &last.arguments {
+					PathArguments::AngleBracketed(args) => args
+						.args
+						.iter()
+						.filter_map(|arg| match arg {
+							GenericArgument::Type(ty) => Some(ty),
+							_ => None,
+						})
+						.collect(),
+					_ => Vec::new(),
+				};
+
+				match (last.ident.to_string().as_str(), args.as_slice()) {
+					("String", []) => {
+						return Conversion {
+							ty: parse_quote! { &#lifetime str },
+							view: quote! { ::std::string::String::as_str(value) },
+							owned: quote! { ::std::string::String::from(*value) },
+						}
+					}
+					("Vec", [inner]) => {
+						let inner = self.map_lifetimes(inner);
+						if self.is_generic(&inner) {
+							self.owned_bounds
+								.push(quote! { #inner: ::core::clone::Clone });
+						}
+						return Conversion {
+							ty: parse_quote! { &#lifetime [#inner] },
+							view: quote! { ::std::vec::Vec::as_slice(value) },
+							owned: quote! { <[#inner]>::to_vec(*value) },
+						};
+					}
+					("Box", [inner]) => {
+						let inner = self.map_lifetimes(inner);
+						if self.is_generic(&inner) {
+							self.owned_bounds.push(quote! {
+								::std::boxed::Box<#inner>: for<'from> ::core::convert::From<&'from #inner>
+							});
+						}
+						return Conversion {
+							ty: parse_quote! { &#lifetime #inner },
+							view: quote! { &**value },
+							owned: quote! { ::core::convert::From::from(*value) },
+						};
+					}
+					("Cow", [inner]) => {
+						let inner = self.map_lifetimes(inner);
+						return Conversion {
+							ty: parse_quote! { ::std::borrow::Cow<#lifetime, #inner> },
+							view: quote! { ::std::borrow::Cow::Borrowed(&**value) },
+							owned: quote! {
+								::std::borrow::Cow::Owned(::std::borrow::ToOwned::to_owned(&**value))
+							},
+						};
+					}
+					("Option", [inner]) => {
+						let inner = self.convert(inner);
+						let inner_ty = &inner.ty;
+						let inner_view = &inner.view;
+						let inner_owned = &inner.owned;
+						return Conversion {
+							ty: parse_quote! { ::core::option::Option<#inner_ty> },
+							view: quote! {
+								::core::option::Option::as_ref(value).map(|value| #inner_view)
+							},
+							owned: quote! {
+								::core::option::Option::as_ref(value).map(|value| #inner_owned)
+							},
+						};
+					}
+					(
+						"u8" | "i8" | "u16" | "i16" | "u32" | "i32" | "u64" | "i64" | "u128"
+						| "i128" | "usize" | "isize" | "f32" | "f64" | "bool" | "char",
+						[],
+					) if type_path.path.segments.len() == 1 => {
+						return Conversion {
+							ty: ty.clone(),
+							view: quote! { *value },
+							owned: quote! { *value },
+						}
+					}
+					_ => {}
+				}
+			}
+			_ => {}
+		}
+
+		// anything else is borrowed as it is
+		let mapped = self.map_lifetimes(ty);
+		if self.is_generic(ty) {
+			self.owned_bounds
+				.push(quote! { #mapped: ::core::clone::Clone });
+		}
+		Conversion {
+			ty: parse_quote! { &#lifetime #mapped },
+			view: quote! { value },
+			owned: quote! { ::core::clone::Clone::clone(*value) },
+		}
+	}
+
+	fn map_lifetimes(&mut self, ty: &Type) -> Type {
+		let mut ty = ty.clone();
+		visit_mut::visit_type_mut(self.mapper, &mut ty);
+		ty
+	}
+
+	/// Whether the type depends on the generics, in which case its bounds can't be checked right away
+	fn is_generic(&self, ty: &Type) -> bool {
+		let mut lifetimes = HashSet::new();
+		collect_lifetimes(ty.to_token_stream(), &mut lifetimes);
+
+		self.type_params
+			.iter()
+			.any(|param| crate::usage::mentions(ty, param))
+			|| lifetimes.contains(&self.lifetime.ident.to_string())
+			|| self
+				.mapper
+				.lifetimes
+				.iter()
+				.any(|lifetime| lifetimes.contains(&lifetime.to_string()))
+	}
+}
@@ -0,0 +1,119 @@
+use crate::{collect_lifetimes, unique_lifetime};
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use std::collections::HashSet;
+use syn::parse::{Parse, ParseStream};
+use syn::visit_mut::VisitMut;
+use syn::{Generics, Lifetime, LitStr, Path, Token, Type, WhereClause};
+
+/// Input of `assert_makeowned_subtype!`, i.e. `crate = "..."; <'a, T: Bound> Type<'a, '_, T> where ...`
+pub struct AssertInput {
+	/// `crate = "...";`, path to the `shallowclone` crate
+	krate: Option<Path>,
+	generics: Generics,
+	ty: Type,
+}
+
+impl Parse for AssertInput {
+	fn parse(input: ParseStream) -> syn::Result<Self> {
+		let krate = match input.peek(Token![crate]) {
+			true => {
+				input.parse::<Token![crate]>()?;
+				input.parse::<Token![=]>()?;
+				let path = input.parse::<LitStr>()?.parse()?;
+				input.parse::<Token![;]>()?;
+				Some(path)
+			}
+			false => None,
+		};
+		let mut generics = match input.peek(Token![<]) {
+			true => input.parse::<Generics>()?,
+			false => Generics::default(),
+		};
+		let ty = input.parse()?;
+		generics.where_clause = input.parse::<Option<WhereClause>>()?;
+
+		Ok(Self {
+			krate,
+			generics,
+			ty,
+		})
+	}
+}
+
+/// Implements `assert_makeowned_subtype!`, replacing `'_` with named lifetimes
+pub fn assert_makeowned_subtype(mut input: AssertInput) -> TokenStream {
+	let mut used = HashSet::new();
+	collect_lifetimes(input.generics.to_token_stream(), &mut used);
+	collect_lifetimes(input.ty.to_token_stream(), &mut used);
+
+	let mut namer = ElidedNamer {
+		used,
+		named: Vec::new(),
+	};
+	namer.visit_type_mut(&mut input.ty);
+	for lifetime in namer.named.into_iter().rev() {
+		input
+			.generics
+			.params
+			.insert(0, syn::parse_quote! { #lifetime });
+	}
+
+	let params = &input.generics.params;
+	let predicates = input
+		.generics
+		.where_clause
+		.iter()
+		.flat_map(|c| &c.predicates)
+		.map(|predicate| quote! { #predicate });
+	let ty = &input.ty;
+	let krate = match &input.krate {
+		Some(krate) => quote! { #krate },
+		None => quote! { ::shallowclone },
+	};
+	gen_subtype_assertion(
+		&quote! { #krate::MakeOwned },
+		&quote! { #params },
+		&quote! { #ty },
+		predicates,
+	)
+}
+
+/// Generates a function which only compiles if `<ty as Trait>::Owned` can be coerced to `ty`, i.e. it's
+/// a subtype of it. `trait_path` is `MakeOwned` or `MakeOwnedFor<'keep>`
+pub fn gen_subtype_assertion(
+	trait_path: &TokenStream,
+	params: &TokenStream,
+	ty: &TokenStream,
+	predicates: impl IntoIterator<Item = TokenStream>,
+) -> TokenStream {
+	let predicates = predicates.into_iter();
+	quote! {
+		const _: () = {
+			#[allow(dead_code, clippy::extra_unused_lifetimes, clippy::multiple_bound_locations)]
+			fn assert_makeowned_subtype<#params>(
+				owned: <#ty as #trait_path>::Owned,
+			) -> #ty
+			where
+				#(#predicates),*
+			{
+				owned
+			}
+		};
+	}
+}
+
+/// Gives names to all `'_` lifetimes
+struct ElidedNamer {
+	used: HashSet<String>,
+	named: Vec<Lifetime>,
+}
+
+impl VisitMut for ElidedNamer {
+	fn visit_lifetime_mut(&mut self, lifetime: &mut Lifetime) {
+		if lifetime.ident == "_" {
+			*lifetime = unique_lifetime(&mut self.used, "elided");
+			self.named.push(lifetime.clone());
+		}
+	}
+}
@@ -1,38 +1,251 @@
 use crate::attributes;
-use crate::DeriveType;
-use proc_macro2::TokenStream;
+use crate::usage::Usage;
+use crate::{DeriveType, Names};
+use proc_macro2::{Span, TokenStream};
+use proc_macro_error::emit_error;
 use quote::quote;
-use quote::ToTokens;
-use syn::{DeriveInput, GenericParam};
-
-/// Generates `Name<generics>` with generics changed accordingly
-pub fn get_target_type(input: &DeriveInput, derive_type: DeriveType) -> TokenStream {
-	let generics = input.generics.params.iter().map(|generic| {
-		let name: &dyn ToTokens = match generic {
-			GenericParam::Type(type_param) => &type_param.ident,
-			GenericParam::Lifetime(lifetime_param) => &lifetime_param.lifetime,
-			// we always leave const generics as they are
-			GenericParam::Const(const_param) => return quote! { #const_param },
-		};
-
-		if attributes::is_generic_skipped(derive_type, generic) {
-			quote! { #name }
-		} else {
-			match (generic, derive_type) {
-				(GenericParam::Type(_), DeriveType::ShallowClone) => {
-					quote! { <#name as ShallowClone<'shallowclone>>::Target }
+use std::collections::HashMap;
+use syn::visit_mut::{self, VisitMut};
+use syn::{
+	parse_quote, DeriveInput, GenericParam, Ident, Lifetime, Path, PredicateType,
+	TraitBoundModifier, Type, TypeParamBound, WherePredicate,
+};
+
+/// Generates `Name<generics>` with generics changed accordingly, `item_path` being the path to `Name`
+pub fn get_target_type(
+	input: &DeriveInput,
+	item_path: &TokenStream,
+	derive_type: DeriveType,
+	names: &Names,
+) -> TokenStream {
+	let mut mapper = GenericsMapper::new(input, derive_type, names);
+	let generics = input.generics.params.iter().map(|generic| match generic {
+		GenericParam::Type(type_param) => {
+			let ident = &type_param.ident;
+			let mut ty = parse_quote! { #ident };
+			mapper.visit_type_mut(&mut ty);
+			quote! { #ty }
+		}
+		GenericParam::Lifetime(lifetime_param) => {
+			let mut lifetime = lifetime_param.lifetime.clone();
+			mapper.visit_lifetime_mut(&mut lifetime);
+			quote! { #lifetime }
+		}
+		// we always leave const generics as they are
+		GenericParam::Const(const_param) => {
+			let ident = &const_param.ident;
+			quote! { #ident }
+		}
+	});
+
+	quote! { #item_path< #(#generics),* > }
+}
+
+/// Rewrites types and bounds by replacing the generics of the input with their counterparts in the
+/// target type, i.e. `T` becomes `<T as MakeOwned>::Owned` and `'a` becomes `'static`.
+///
+/// Type parameters are only replaced if they are converted by the trait in the fields, see [`Usage`].
+pub struct GenericsMapper {
+	types: HashMap<Ident, Type>,
+	lifetimes: HashMap<Ident, Lifetime>,
+	/// All trait bounds placed on each type parameter, used to resolve `T::Assoc` shorthands
+	trait_bounds: HashMap<Ident, Vec<Path>>,
+	/// Whether anything was replaced since this was last reset
+	pub changed: bool,
+}
+
+impl GenericsMapper {
+	pub fn new(input: &DeriveInput, derive_type: DeriveType, names: &Names) -> Self {
+		let trait_path = names.trait_path(derive_type);
+		let usage = Usage::analyze(input, derive_type, &names.kept);
+		let mut types = HashMap::new();
+		let mut lifetimes = HashMap::new();
+		let mut trait_bounds = HashMap::<Ident, Vec<Path>>::new();
+
+		for generic in &input.generics.params {
+			match generic {
+				GenericParam::Type(type_param) => {
+					let name = &type_param.ident;
+					trait_bounds
+						.entry(name.clone())
+						.or_default()
+						.extend(trait_paths(&type_param.bounds));
+
+					if attributes::is_generic_skipped(derive_type, generic)
+						|| !usage.param(name).is_mapped()
+					{
+						continue;
+					}
+					let mapped = match derive_type {
+						DeriveType::ShallowClone if names.gat => {
+							let lifetime = &names.lifetime;
+							parse_quote! { <#name as #trait_path>::Target<#lifetime> }
+						}
+						DeriveType::ShallowClone => parse_quote! { <#name as #trait_path>::Target },
+						DeriveType::MakeOwned => {
+							let owned = names.owned_type();
+							parse_quote! { <#name as #trait_path>::#owned }
+						}
+					};
+					types.insert(name.clone(), mapped);
 				}
-				(GenericParam::Type(_), DeriveType::MakeOwned) => {
-					quote! {<#name as MakeOwned>::Owned }
+				GenericParam::Lifetime(lifetime_param) => {
+					if attributes::is_generic_skipped(derive_type, generic)
+						|| names.kept.contains(&lifetime_param.lifetime)
+					{
+						continue;
+					}
+					let mapped = match derive_type {
+						DeriveType::ShallowClone => names.lifetime.clone(),
+						DeriveType::MakeOwned => match &names.target_lifetime {
+							Some(target_lifetime) => target_lifetime.clone(),
+							None => Lifetime::new("'static", Span::call_site()),
+						},
+					};
+					lifetimes.insert(lifetime_param.lifetime.ident.clone(), mapped);
 				}
-				(GenericParam::Lifetime(_), DeriveType::ShallowClone) => quote! { 'shallowclone },
-				(GenericParam::Lifetime(_), DeriveType::MakeOwned) => quote! { 'static },
+				GenericParam::Const(_) => {}
+			}
+		}
 
-				(GenericParam::Const(_), _) => unreachable!(),
+		for predicate in input
+			.generics
+			.where_clause
+			.iter()
+			.flat_map(|w| &w.predicates)
+		{
+			if let WherePredicate::Type(PredicateType {
+				bounded_ty: Type::Path(path),
+				bounds,
+				..
+			}) = predicate
+			{
+				if let Some(ident) = path.path.get_ident() {
+					if let Some(list) = trait_bounds.get_mut(ident) {
+						list.extend(trait_paths(bounds));
+					}
+				}
 			}
 		}
-	});
 
-	let item_name = &input.ident;
-	quote! { #item_name< #(#generics),* > }
+		Self {
+			types,
+			lifetimes,
+			trait_bounds,
+			changed: false,
+		}
+	}
+}
+
+impl VisitMut for GenericsMapper {
+	fn visit_type_mut(&mut self, ty: &mut Type) {
+		if let Type::Path(type_path) = ty {
+			let path = &type_path.path;
+			if type_path.qself.is_none()
+				&& path.leading_colon.is_none()
+				&& path.segments[0].arguments.is_none()
+			{
+				let ident = &path.segments[0].ident;
+				if let Some(mapped) = self.types.get(ident) {
+					let rest = path.segments.iter().skip(1).collect::<Vec<_>>();
+
+					*ty = if rest.is_empty() {
+						mapped.clone()
+					} else {
+						// `T::Assoc` is only allowed on type parameters, so after replacing `T` we have to
+						// specify the trait explicitly
+						let traits = &self.trait_bounds[ident];
+						if traits.len() != 1 {
+							emit_error!(
+								type_path,
+								"can't determine which trait `{}` belongs to", quote! { #(#rest)::* };
+								help = "use a fully qualified path like `<{} as Trait>::...`, or specify the bounds manually with the `bound` attribute", ident
+							);
+						}
+						match traits.first() {
+							Some(tr) => parse_quote! { <#mapped as #tr>#(::#rest)* },
+							None => parse_quote! { <#mapped>#(::#rest)* },
+						}
+					};
+					self.changed = true;
+					return;
+				}
+			}
+		}
+
+		visit_mut::visit_type_mut(self, ty);
+	}
+
+	fn visit_lifetime_mut(&mut self, lifetime: &mut Lifetime) {
+		if let Some(mapped) = self.lifetimes.get(&lifetime.ident) {
+			*lifetime = mapped.clone();
+			self.changed = true;
+		}
+	}
+}
+
+/// Takes all bounds of the input generics (both inline and in the where clause) and maps them onto
+/// the target type's generics, since the target type must satisfy them too.
+pub fn mirror_predicates(
+	input: &DeriveInput,
+	derive_type: DeriveType,
+	names: &Names,
+) -> Vec<WherePredicate> {
+	let mut predicates = Vec::new();
+	for generic in &input.generics.params {
+		match generic {
+			GenericParam::Type(type_param) if !type_param.bounds.is_empty() => {
+				let ident = &type_param.ident;
+				let bounds = &type_param.bounds;
+				predicates.push(parse_quote! { #ident: #bounds });
+			}
+			GenericParam::Lifetime(lifetime_param) if !lifetime_param.bounds.is_empty() => {
+				let lifetime = &lifetime_param.lifetime;
+				let bounds = &lifetime_param.bounds;
+				predicates.push(parse_quote! { #lifetime: #bounds });
+			}
+			_ => {}
+		}
+	}
+	if let Some(where_clause) = &input.generics.where_clause {
+		predicates.extend(where_clause.predicates.iter().cloned());
+	}
+
+	let mut mapper = GenericsMapper::new(input, derive_type, names);
+	predicates
+		.into_iter()
+		.filter_map(|mut predicate| {
+			mapper.changed = false;
+			mapper.visit_where_predicate_mut(&mut predicate);
+			if !mapper.changed {
+				// doesn't involve any of the mapped generics, already satisfied by the original bounds
+				return None;
+			}
+
+			if let WherePredicate::Type(PredicateType { bounds, .. }) = &mut predicate {
+				// `?Sized` is only allowed on type parameters
+				*bounds = std::mem::take(bounds)
+					.into_iter()
+					.filter(|bound| !matches!(bound, TypeParamBound::Trait(t) if matches!(t.modifier, TraitBoundModifier::Maybe(_))))
+					.collect();
+				if bounds.is_empty() {
+					return None;
+				}
+			}
+
+			Some(predicate)
+		})
+		.collect()
+}
+
+fn trait_paths<'a>(bounds: impl IntoIterator<Item = &'a TypeParamBound>) -> Vec<Path> {
+	bounds
+		.into_iter()
+		.filter_map(|bound| match bound {
+			TypeParamBound::Trait(t) if matches!(t.modifier, TraitBoundModifier::None) => {
+				Some(t.path.clone())
+			}
+			_ => None,
+		})
+		.collect()
 }
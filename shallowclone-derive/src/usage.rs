@@ -0,0 +1,202 @@
+use crate::attributes::{self, FieldAttrs, FieldMode};
+use crate::{collect_lifetimes, DeriveType};
+use std::collections::{HashMap, HashSet};
+use syn::{
+	Data, DeriveInput, Fields, GenericArgument, Generics, Ident, Lifetime, PathArguments, Type,
+};
+
+/// How a type parameter is used in the fields of the input
+#[derive(Default, Clone, Copy)]
+pub struct ParamUsage {
+	/// Appears somewhere where it will be converted by the trait, i.e. directly as a field or in a `Vec<T>`
+	pub converted: bool,
+	/// Appears somewhere where it's left as it is, i.e. in a `Cow<'a, [T]>` for `ShallowClone`
+	pub kept: bool,
+}
+
+impl ParamUsage {
+	/// Whether the parameter should be replaced with its converted type in the target type
+	pub fn is_mapped(self) -> bool {
+		self.converted && !self.kept
+	}
+}
+
+/// How the generics are used in the fields which are converted with the trait
+#[derive(Default)]
+pub struct Usage {
+	/// Only the type parameters which are used in some way
+	pub params: HashMap<Ident, ParamUsage>,
+	/// Types like `T::Item` or `<T as Iterator>::Item` which are converted by the trait
+	pub projections: Vec<Type>,
+}
+
+impl Usage {
+	/// `kept` are the lifetimes kept by `MakeOwned`, fields which only borrow for them are left as they are
+	pub fn analyze(input: &DeriveInput, derive_type: DeriveType, kept: &[Lifetime]) -> Self {
+		let type_params = input
+			.generics
+			.type_params()
+			.map(|param| param.ident.clone())
+			.collect::<Vec<_>>();
+
+		let mut analyzer = Analyzer {
+			derive_type,
+			type_params: &type_params,
+			usage: Usage::default(),
+		};
+
+		let fields: Vec<&Fields> = match &input.data {
+			Data::Struct(data) => vec![&data.fields],
+			Data::Enum(data) => data
+				.variants
+				.iter()
+				.map(|variant| &variant.fields)
+				.collect(),
+			// unions are never converted field by field
+			Data::Union(_) => Vec::new(),
+		};
+		// fields of packed structs can't be borrowed, so ShallowClone copies them instead of converting
+		let converted =
+			!(derive_type == DeriveType::ShallowClone && attributes::is_packed(&input.attrs));
+		for field in fields.into_iter().flatten() {
+			if matches!(FieldAttrs::parse(derive_type, field).mode, FieldMode::Trait) {
+				let moved = detach(&field.ty, &input.generics, kept) == Detach::Nothing;
+				analyzer.visit(&field.ty, converted && !moved);
+			}
+		}
+
+		analyzer.usage
+	}
+
+	pub fn param(&self, ident: &Ident) -> ParamUsage {
+		self.params.get(ident).copied().unwrap_or_default()
+	}
+}
+
+struct Analyzer<'a> {
+	derive_type: DeriveType,
+	type_params: &'a [Ident],
+	usage: Usage,
+}
+
+impl Analyzer<'_> {
+	/// `converted` is whether the trait will be applied to the values of this type
+	fn visit(&mut self, ty: &Type, converted: bool) {
+		match ty {
+			Type::Path(type_path) => {
+				let path = &type_path.path;
+				let root = &path.segments[0].ident;
+				let is_param = type_path.qself.is_none()
+					&& path.leading_colon.is_none()
+					&& self.type_params.contains(root);
+				let is_projection = match &type_path.qself {
+					Some(qself) => self.mentions_params(&qself.ty),
+					None => is_param && path.segments.len() > 1,
+				};
+
+				if is_projection {
+					if converted {
+						self.usage.projections.push(ty.clone());
+					}
+				} else if is_param {
+					let usage = self.usage.params.entry(root.clone()).or_default();
+					if converted {
+						usage.converted = true;
+					} else {
+						usage.kept = true;
+					}
+				} else {
+					let last = path.segments.last().unwrap();
+					// the contents of these are left as they are by the trait
+					let keeps_contents = match self.derive_type {
+						DeriveType::ShallowClone => {
+							last.ident == "Cow"
+								|| last.ident == "CoCow" || last.ident == "CoCowSlice"
+								|| last.ident == "PhantomData"
+						}
+						DeriveType::MakeOwned => last.ident == "PhantomData",
+					};
+
+					for segment in &path.segments {
+						if let PathArguments::AngleBracketed(args) = &segment.arguments {
+							for arg in &args.args {
+								if let GenericArgument::Type(arg) = arg {
+									self.visit(arg, converted && !keeps_contents);
+								}
+							}
+						}
+					}
+				}
+			}
+			Type::Reference(reference) => self.visit(&reference.elem, false),
+			Type::Array(array) => self.visit(&array.elem, converted),
+			Type::Slice(slice) => self.visit(&slice.elem, converted),
+			Type::Paren(paren) => self.visit(&paren.elem, converted),
+			Type::Group(group) => self.visit(&group.elem, converted),
+			Type::Tuple(tuple) => {
+				for elem in &tuple.elems {
+					self.visit(elem, converted);
+				}
+			}
+			// function pointers, trait objects and such are never converted
+			_ => self.mark_kept(ty),
+		}
+	}
+
+	fn mark_kept(&mut self, ty: &Type) {
+		for param in self.type_params {
+			if mentions(ty, param) {
+				self.usage.params.entry(param.clone()).or_default().kept = true;
+			}
+		}
+	}
+
+	fn mentions_params(&self, ty: &Type) -> bool {
+		self.type_params.iter().any(|param| mentions(ty, param))
+	}
+}
+
+/// What `MakeOwned` has to do with a field when some lifetimes are kept
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Detach {
+	/// Doesn't borrow for any kept lifetime, converted with `MakeOwned`
+	All,
+	/// Only borrows for kept lifetimes, moved as it is
+	Nothing,
+	/// Borrows for both, converted with `MakeOwnedFor`
+	Partial,
+}
+
+pub fn detach(ty: &Type, generics: &Generics, kept: &[Lifetime]) -> Detach {
+	if kept.is_empty() {
+		return Detach::All;
+	}
+
+	let mut used = HashSet::new();
+	collect_lifetimes(quote::ToTokens::to_token_stream(ty), &mut used);
+	let is_used = |lifetime: &Lifetime| used.contains(&lifetime.ident.to_string());
+
+	let keeps = kept.iter().any(is_used);
+	let detaches = generics
+		.lifetimes()
+		.map(|param| &param.lifetime)
+		.any(|lifetime| !kept.contains(lifetime) && is_used(lifetime));
+	match (keeps, detaches) {
+		(false, _) => Detach::All,
+		(true, false) => Detach::Nothing,
+		(true, true) => Detach::Partial,
+	}
+}
+
+/// Whether the type mentions the identifier anywhere
+pub fn mentions(ty: &Type, ident: &Ident) -> bool {
+	fn in_tokens(tokens: proc_macro2::TokenStream, ident: &Ident) -> bool {
+		tokens.into_iter().any(|token| match token {
+			proc_macro2::TokenTree::Ident(i) => i == *ident,
+			proc_macro2::TokenTree::Group(group) => in_tokens(group.stream(), ident),
+			_ => false,
+		})
+	}
+
+	in_tokens(quote::ToTokens::to_token_stream(ty), ident)
+}
@@ -0,0 +1,55 @@
+use crate::attributes::ContainerAttrs;
+use crate::walk::{gen_walk, gen_walk_impl, is_inspected};
+use crate::{DeriveType, Names};
+use proc_macro2::TokenStream;
+use proc_macro_error::abort;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::DeriveInput;
+
+/// Derives `VisitCows`, pushing the name of every field onto the path before visiting it
+pub fn derive_visit_cows(input: &DeriveInput) -> TokenStream {
+	let container_attrs = ContainerAttrs::parse(DeriveType::MakeOwned, &input.attrs);
+	if let Some(remote) = &container_attrs.remote {
+		abort!(
+			remote,
+			"`VisitCows` can't be derived together with `remote`"
+		);
+	}
+	let names = Names::new(input, &container_attrs);
+	let krate = &names.krate;
+	let bound = quote! { #krate::VisitCows };
+
+	let mut inferred_bounds = Vec::new();
+	let mut walk = |method: TokenStream| {
+		gen_walk(input, &bound, &mut inferred_bounds, |walked| {
+			let binding = &walked.binding;
+			let name = &walked.name;
+			is_inspected(walked.field).then(|| {
+				quote_spanned! {walked.field.ty.span()=>
+					__path.push_field(#name);
+					#krate::VisitCows::#method(#binding, __visitor, __path);
+					__path.pop();
+				}
+			})
+		})
+	};
+	let visit = walk(quote! { visit_cows_at });
+	let visit_mut = walk(quote! { visit_cows_mut_at });
+
+	gen_walk_impl(
+		input,
+		&container_attrs,
+		&bound,
+		inferred_bounds,
+		quote! {
+			fn visit_cows_at<__V: #krate::CowVisitor>(&self, __visitor: &mut __V, __path: &mut #krate::FieldPath) {
+				#visit
+			}
+
+			fn visit_cows_mut_at<__V: #krate::CowVisitorMut>(&mut self, __visitor: &mut __V, __path: &mut #krate::FieldPath) {
+				#visit_mut
+			}
+		},
+	)
+}
@@ -0,0 +1,166 @@
+use crate::attributes::{self, ContainerAttrs, FieldAttrs};
+use crate::gen_impl::tuple_field;
+use crate::{usage, DeriveType};
+use proc_macro2::TokenStream;
+use proc_macro_error::abort;
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::{Data, DeriveInput, Field, Fields};
+
+/// A field visited by [`gen_walk`]
+pub struct WalkedField<'a> {
+	pub field: &'a Field,
+	/// The name of the field, or its index in a tuple
+	pub name: String,
+	/// The variable that the field is bound to, a reference to it
+	pub binding: TokenStream,
+}
+
+/// Whether a field is inspected by the derives which look at a value without converting it
+///
+/// Every field is, whatever its `#[makeowned(...)]` conversion is, since a field converted with `with`,
+/// left as it is with `skip` or discarded with `default` can still borrow and own memory. Fields with
+/// `no_inspect` are left out, for types which don't implement the traits.
+pub fn is_inspected(field: &Field) -> bool {
+	!FieldAttrs::parse(DeriveType::MakeOwned, field).no_inspect
+}
+
+/// Generates a body which destructures `self` (either `&self` or `&mut self`) and runs the code returned
+/// by `visit` for every field, in order
+///
+/// Fields for which `visit` returns `None` are not bound. For the others `FieldType: bound` is pushed to
+/// `bounds` if the type depends on the type parameters.
+pub fn gen_walk(
+	input: &DeriveInput,
+	bound: &TokenStream,
+	bounds: &mut Vec<TokenStream>,
+	mut visit: impl FnMut(&WalkedField) -> Option<TokenStream>,
+) -> TokenStream {
+	let type_params = input
+		.generics
+		.type_params()
+		.map(|param| &param.ident)
+		.collect::<Vec<_>>();
+
+	let mut walk_fields = |fields: &Fields| {
+		let mut patterns = Vec::new();
+		let mut code = Vec::new();
+		for (i, field) in fields.iter().enumerate() {
+			let binding = match &field.ident {
+				Some(ident) => quote! { #ident },
+				None => {
+					let x = tuple_field(i);
+					quote! { #x }
+				}
+			};
+			let walked = WalkedField {
+				field,
+				name: match &field.ident {
+					Some(ident) => ident.unraw().to_string(),
+					None => i.to_string(),
+				},
+				binding: binding.clone(),
+			};
+
+			let visited = visit(&walked);
+			if visited.is_some() {
+				let ty = &field.ty;
+				if type_params.iter().any(|param| usage::mentions(ty, param)) {
+					bounds.push(quote! { #ty: #bound });
+				}
+			}
+			// unused named fields are covered by `..`
+			patterns.push(match (&field.ident, &visited) {
+				(_, Some(_)) => Some(binding),
+				(Some(_), None) => None,
+				(None, None) => Some(quote! { _ }),
+			});
+			code.extend(visited);
+		}
+
+		let pattern = match fields {
+			Fields::Named(_) => {
+				let patterns = patterns.into_iter().flatten();
+				quote! { { #(#patterns,)* .. } }
+			}
+			Fields::Unnamed(_) => {
+				let patterns = patterns.into_iter().flatten();
+				quote! { ( #(#patterns),* ) }
+			}
+			Fields::Unit => quote! {},
+		};
+		(pattern, code)
+	};
+
+	match &input.data {
+		Data::Struct(data) => {
+			// fields of packed structs can't be borrowed
+			if attributes::is_packed(&input.attrs) {
+				abort!(input.ident, "packed structs are not supported");
+			}
+			let (pattern, code) = walk_fields(&data.fields);
+			quote! {
+				let Self #pattern = self;
+				#(#code)*
+			}
+		}
+		Data::Enum(data) => {
+			let arms = data.variants.iter().map(|variant| {
+				let variant_name = &variant.ident;
+				let (pattern, code) = walk_fields(&variant.fields);
+				quote! {
+					Self::#variant_name #pattern => {
+						#(#code)*
+					}
+				}
+			});
+			// a reference to an empty enum can't be matched exhaustively
+			match data.variants.is_empty() {
+				true => quote! { match *self {} },
+				false => quote! {
+					match self {
+						#(#arms)*
+					}
+				},
+			}
+		}
+		Data::Union(data) => abort!(data.union_token, "unions are not supported"),
+	}
+}
+
+/// Generates `impl Trait for Input { #items }`, bound by `inferred_bounds` (which may contain duplicates
+/// if several methods walk the fields) unless the `bound` attribute is used
+pub fn gen_walk_impl(
+	input: &DeriveInput,
+	container_attrs: &ContainerAttrs,
+	trait_path: &TokenStream,
+	inferred_bounds: Vec<TokenStream>,
+	items: TokenStream,
+) -> TokenStream {
+	let ident = &input.ident;
+	let bounds = match &container_attrs.bound {
+		Some(bound) => bound
+			.iter()
+			.map(|predicate| quote! { #predicate })
+			.collect(),
+		None => {
+			let mut bounds: Vec<TokenStream> = Vec::new();
+			for bound in inferred_bounds {
+				if !bounds.iter().any(|b| b.to_string() == bound.to_string()) {
+					bounds.push(bound);
+				}
+			}
+			bounds
+		}
+	};
+	let generics = attributes::without_attrs(&input.generics);
+	let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+	let orig_predicates = where_clause.iter().flat_map(|c| &c.predicates);
+
+	quote! {
+		impl #impl_generics #trait_path for #ident #type_generics
+		where #(#orig_predicates,)* #(#bounds),* {
+			#items
+		}
+	}
+}
@@ -0,0 +1,228 @@
+use crate::{CoCow, CoCowSlice, MakeOwned, ShallowClone};
+use std::{
+	borrow::Cow, marker::PhantomData, mem::ManuallyDrop, ops::Deref, ptr, rc::Rc, sync::Arc,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// A type with a lifetime parameter that is used by [`Anchored`], implemented on its `'static` version.
+///
+/// The [`shorten`][Self::shorten] method must be implemented as just `view`, which only compiles if the
+/// type is covariant over the lifetime, like the types using [`CoCow`] and [`CoCowSlice`]. This is what
+/// allows [`Anchored`] to hand out the view with the lifetime of the borrow.
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # use shallowclone::BorrowFamily;
+/// struct Frame<'a> {
+///     kind: Cow<'a, str>,
+///     payload: &'a [u8],
+/// }
+///
+/// impl BorrowFamily for Frame<'static> {
+///     type View<'a> = Frame<'a>;
+///
+///     fn shorten<'a, 'b: 'a>(view: &'a Frame<'b>) -> &'a Frame<'a> {
+///         view
+///     }
+/// }
+/// ```
+///
+/// Invariant types can't be used:
+///
+/// ```compile_fail
+/// # use std::cell::Cell;
+/// # use shallowclone::BorrowFamily;
+/// struct Slot<'a>(Cell<&'a str>);
+///
+/// impl BorrowFamily for Slot<'static> {
+///     type View<'a> = Slot<'a>;
+///
+///     fn shorten<'a, 'b: 'a>(view: &'a Slot<'b>) -> &'a Slot<'a> {
+///         view
+///     }
+/// }
+/// ```
+pub trait BorrowFamily: 'static {
+	/// The type borrowing for `'a`
+	type View<'a>;
+
+	fn shorten<'a, 'b: 'a>(view: &'a Self::View<'b>) -> &'a Self::View<'a>;
+}
+
+impl<B: ToOwned + ?Sized + 'static> BorrowFamily for Cow<'static, B> {
+	type View<'a> = Cow<'a, B>;
+
+	fn shorten<'a, 'b: 'a>(view: &'a Cow<'b, B>) -> &'a Cow<'a, B> {
+		view
+	}
+}
+
+impl<T: 'static> BorrowFamily for CoCow<'static, T> {
+	type View<'a> = CoCow<'a, T>;
+
+	fn shorten<'a, 'b: 'a>(view: &'a CoCow<'b, T>) -> &'a CoCow<'a, T> {
+		view
+	}
+}
+
+impl<T: 'static> BorrowFamily for CoCowSlice<'static, T> {
+	type View<'a> = CoCowSlice<'a, T>;
+
+	fn shorten<'a, 'b: 'a>(view: &'a CoCowSlice<'b, T>) -> &'a CoCowSlice<'a, T> {
+		view
+	}
+}
+
+impl<T: ?Sized + 'static> BorrowFamily for &'static T {
+	type View<'a> = &'a T;
+
+	fn shorten<'a, 'b: 'a>(view: &'a &'b T) -> &'a &'a T {
+		view
+	}
+}
+
+/// A buffer whose contents stay at the same address when it's moved, so that [`Anchored`] can keep
+/// references to them.
+///
+/// # Safety
+///
+/// The target of [`Deref`] must not move or change when the buffer is moved, or while it's only accessed
+/// through shared references.
+pub unsafe trait StableBuffer: Deref {}
+
+// Box isn't included, since moving it asserts unique access to its contents
+unsafe impl<T> StableBuffer for Vec<T> {}
+unsafe impl StableBuffer for String {}
+unsafe impl<T: ?Sized> StableBuffer for Rc<T> {}
+unsafe impl<T: ?Sized> StableBuffer for Arc<T> {}
+unsafe impl<T: ?Sized> StableBuffer for &'static T {}
+
+/// Owns a buffer together with a view borrowing from it, like a parsed `Frame<'buf>` stored next to the
+/// `Vec<u8>` it was parsed from, so that both can be stored in a `'static` structure without
+/// [`MakeOwned`].
+///
+/// `F` is the `'static` version of the view, implementing [`BorrowFamily`].
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # use shallowclone::{Anchored, BorrowFamily, MakeOwned, ShallowClone};
+/// #[derive(ShallowClone, MakeOwned)]
+/// struct Frame<'a> {
+///     kind: Cow<'a, str>,
+///     payload: Cow<'a, [u8]>,
+/// }
+///
+/// impl BorrowFamily for Frame<'static> {
+///     type View<'a> = Frame<'a>;
+///
+///     fn shorten<'a, 'b: 'a>(view: &'a Frame<'b>) -> &'a Frame<'a> {
+///         view
+///     }
+/// }
+///
+/// fn parse(buffer: &[u8]) -> Frame<'_> {
+///     Frame {
+///         kind: String::from_utf8_lossy(&buffer[..4]),
+///         payload: Cow::Borrowed(&buffer[5..]),
+///     }
+/// }
+///
+/// // the family can't be inferred from the closure, so it has to be named
+/// let frame = Anchored::<_, Frame<'static>>::new(b"ping data".to_vec(), parse);
+/// assert_eq!(frame.get().kind, "ping");
+///
+/// let payload = frame.map_project::<Cow<'static, [u8]>>(|frame, _| frame.payload);
+/// assert_eq!(*payload.view(), &b"data"[..]);
+/// let payload: Cow<'static, [u8]> = payload.into_owned();
+/// ```
+pub struct Anchored<Buf: StableBuffer, F: BorrowFamily> {
+	// declared before the buffer, so that it's dropped first
+	/// Actually borrows from the buffer, it's never exposed with the `'static` lifetime
+	view: F::View<'static>,
+	buffer: Buf,
+}
+
+impl<Buf: StableBuffer, F: BorrowFamily> Anchored<Buf, F> {
+	/// Creates the view from the contents of the buffer
+	pub fn new(buffer: Buf, f: impl for<'b> FnOnce(&'b Buf::Target) -> F::View<'b>) -> Self {
+		let view = f(&*buffer);
+		// SAFETY: the view only borrows from the contents of the buffer, which stay in place since the
+		// buffer is `StableBuffer`, and the view is dropped before the buffer
+		let view = unsafe { erase_lifetime::<F>(view) };
+		Self { view, buffer }
+	}
+
+	/// Creates the view from the contents of the buffer, if `f` succeeds
+	pub fn try_new<E>(
+		buffer: Buf,
+		f: impl for<'b> FnOnce(&'b Buf::Target) -> Result<F::View<'b>, E>,
+	) -> Result<Self, E> {
+		let view = f(&*buffer)?;
+		// SAFETY: see `new`
+		let view = unsafe { erase_lifetime::<F>(view) };
+		Ok(Self { view, buffer })
+	}
+
+	/// The view, borrowing for as long as `self` is borrowed
+	pub fn view(&self) -> &F::View<'_> {
+		F::shorten(&self.view)
+	}
+
+	/// A shallow clone of the view, borrowing for as long as `self` is borrowed
+	pub fn get<'a>(&'a self) -> <F::View<'a> as ShallowClone<'a>>::Target
+	where
+		F::View<'a>: ShallowClone<'a>,
+	{
+		self.view().shallow_clone()
+	}
+
+	pub fn buffer(&self) -> &Buf {
+		&self.buffer
+	}
+
+	/// Drops the view and returns the buffer
+	pub fn into_buffer(self) -> Buf {
+		let Self { view, buffer } = self;
+		drop(view);
+		buffer
+	}
+
+	/// Turns the view into another one borrowing from the same buffer, e.g. one of its fields.
+	///
+	/// The [`PhantomData`] makes sure that `f` works for any lifetime, so that it can't leak the view.
+	pub fn map_project<G: BorrowFamily>(
+		self,
+		f: impl for<'a> FnOnce(F::View<'a>, PhantomData<&'a ()>) -> G::View<'a>,
+	) -> Anchored<Buf, G> {
+		let Self { view, buffer } = self;
+		Anchored {
+			view: f(view, PhantomData),
+			buffer,
+		}
+	}
+
+	/// Makes the view owned with [`MakeOwned`], dropping the buffer
+	///
+	/// `MakeOwned` must be implemented for any lifetime of the view, otherwise an implementation just
+	/// for the `'static` version could return the borrowed view as it is.
+	pub fn into_owned<O>(self) -> O
+	where
+		for<'a> F::View<'a>: MakeOwned<Owned = O>,
+	{
+		self.view.make_owned()
+	}
+}
+
+/// Changes the lifetime of the view to `'static`
+///
+/// # Safety
+///
+/// The view must not be used after whatever it borrows is gone.
+unsafe fn erase_lifetime<'b, F: BorrowFamily>(view: F::View<'b>) -> F::View<'static> {
+	let view = ManuallyDrop::new(view);
+	// SAFETY: the types only differ in the lifetime, so they have the same layout, and the original is
+	// not dropped
+	unsafe { ptr::read(ptr::from_ref::<F::View<'b>>(&view).cast::<F::View<'static>>()) }
+}
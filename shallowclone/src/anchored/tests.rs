@@ -0,0 +1,50 @@
+use crate::{points_into, tests::fixtures::Header, Anchored, BorrowFamily, CoCowSlice};
+use std::{borrow::Cow, rc::Rc};
+
+impl BorrowFamily for Header<'static> {
+	type View<'a> = Header<'a>;
+
+	fn shorten<'a, 'b: 'a>(view: &'a Header<'b>) -> &'a Header<'a> {
+		view
+	}
+}
+
+fn parse(line: &str) -> Result<Header<'_>, &'static str> {
+	let (name, rest) = line.split_once('=').ok_or("missing =")?;
+	Ok(Header {
+		name: Cow::Borrowed(name),
+		values: CoCowSlice::Owned(rest.split(',').map(Cow::Borrowed).collect()),
+	})
+}
+
+#[test]
+fn anchored() {
+	let record = Anchored::<_, Header<'static>>::try_new(String::from("key=a,b,c"), parse).unwrap();
+	// moving it around doesn't move the buffer
+	let records = vec![record];
+	let record = &records[0];
+	let view = record.get();
+	assert_eq!(view.name, "key");
+	assert!(points_into(&*view.name, record.buffer().as_bytes()));
+	assert!(view.values.is_borrowed());
+	assert_eq!(view.values.len(), 3);
+	assert!(
+		matches!(&view.values[2], Cow::Borrowed(c) if points_into(*c, record.buffer().as_bytes()))
+	);
+
+	let owned: Header<'static> = records.into_iter().next().unwrap().into_owned();
+	assert!(matches!(owned.name, Cow::Owned(_)));
+	assert_eq!(owned.values[1], "b");
+
+	let error = Anchored::<_, Header<'static>>::try_new(String::from("key"), parse);
+	assert_eq!(error.err(), Some("missing ="));
+
+	// projecting keeps the shared buffer alive
+	let buffer: Rc<str> = Rc::from("name=value");
+	let record = Anchored::<_, Header<'static>>::new(buffer.clone(), |line| parse(line).unwrap());
+	let key = record.map_project::<Cow<'static, str>>(|r, _| r.name);
+	assert_eq!(Rc::strong_count(&buffer), 2);
+	assert_eq!(key.get(), "name");
+	assert!(points_into(&**key.view(), key.buffer().as_bytes()));
+	assert!(Rc::ptr_eq(&key.into_buffer(), &buffer));
+}
@@ -0,0 +1,222 @@
+use std::{
+	borrow::Cow,
+	collections::{BTreeMap, HashMap},
+	marker::PhantomData,
+	mem,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Reports what's still borrowed inside of a value, e.g. whether it's fully detached yet, or whether
+/// anything in it still points into a buffer that's about to be reused.
+///
+/// Borrowed values are walked too, since converting them with [`MakeOwned`][crate::MakeOwned] would
+/// copy everything they borrow as well. Plain references count as borrowed copy-on-write values.
+pub trait BorrowState {
+	/// Adds the copy-on-write values inside to `stats`
+	fn add_borrow_stats(&self, stats: &mut BorrowStats);
+
+	/// Whether anything inside points into the memory of `buffer`
+	fn borrows_from(&self, buffer: &[u8]) -> bool;
+
+	/// Counts the borrowed and owned copy-on-write values inside, and the bytes that are borrowed
+	fn borrow_stats(&self) -> BorrowStats {
+		let mut stats = BorrowStats::default();
+		self.add_borrow_stats(&mut stats);
+		stats
+	}
+
+	/// Whether nothing inside is borrowed
+	fn is_fully_owned(&self) -> bool {
+		self.borrow_stats().borrowed == 0
+	}
+}
+
+/// Statistics collected by [`BorrowState::borrow_stats`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BorrowStats {
+	/// Number of borrowed copy-on-write values and references
+	pub borrowed: usize,
+	/// Number of owned copy-on-write values
+	pub owned: usize,
+	/// The total size of the borrowed values, not counting what they borrow themselves
+	pub borrowed_bytes: usize,
+}
+
+impl BorrowStats {
+	/// Records a borrowed value
+	pub fn add_borrowed<T: ?Sized>(&mut self, value: &T) {
+		self.borrowed += 1;
+		self.borrowed_bytes += mem::size_of_val(value);
+	}
+	/// Records an owned value
+	pub fn add_owned(&mut self) {
+		self.owned += 1;
+	}
+}
+
+/// Whether the memory of `value` overlaps with `buffer`, zero-sized values are checked by their address
+pub fn points_into<T: ?Sized>(value: &T, buffer: &[u8]) -> bool {
+	let start = value as *const T as *const u8 as usize;
+	let end = start + mem::size_of_val(value);
+	let buffer = buffer.as_ptr_range();
+	let (buffer_start, buffer_end) = (buffer.start as usize, buffer.end as usize);
+
+	match start == end {
+		true => start >= buffer_start && start < buffer_end,
+		false => start < buffer_end && end > buffer_start,
+	}
+}
+
+impl<'a> BorrowState for Cow<'a, str> {
+	fn add_borrow_stats(&self, stats: &mut BorrowStats) {
+		match self {
+			Cow::Borrowed(bor) => stats.add_borrowed(*bor),
+			Cow::Owned(_) => stats.add_owned(),
+		}
+	}
+	fn borrows_from(&self, buffer: &[u8]) -> bool {
+		matches!(self, Cow::Borrowed(bor) if points_into(*bor, buffer))
+	}
+}
+impl<'a, T: BorrowState + Clone> BorrowState for Cow<'a, [T]> {
+	fn add_borrow_stats(&self, stats: &mut BorrowStats) {
+		match self {
+			Cow::Borrowed(bor) => stats.add_borrowed(*bor),
+			Cow::Owned(_) => stats.add_owned(),
+		}
+		(**self).add_borrow_stats(stats);
+	}
+	fn borrows_from(&self, buffer: &[u8]) -> bool {
+		matches!(self, Cow::Borrowed(bor) if points_into(*bor, buffer))
+			|| (**self).borrows_from(buffer)
+	}
+}
+impl<'a, T: BorrowState + Clone> BorrowState for Cow<'a, T> {
+	fn add_borrow_stats(&self, stats: &mut BorrowStats) {
+		match self {
+			Cow::Borrowed(bor) => stats.add_borrowed(*bor),
+			Cow::Owned(_) => stats.add_owned(),
+		}
+		(**self).add_borrow_stats(stats);
+	}
+	fn borrows_from(&self, buffer: &[u8]) -> bool {
+		matches!(self, Cow::Borrowed(bor) if points_into(*bor, buffer))
+			|| (**self).borrows_from(buffer)
+	}
+}
+
+impl<T: BorrowState + ?Sized> BorrowState for &T {
+	fn add_borrow_stats(&self, stats: &mut BorrowStats) {
+		stats.add_borrowed(*self);
+		(**self).add_borrow_stats(stats);
+	}
+	fn borrows_from(&self, buffer: &[u8]) -> bool {
+		points_into(*self, buffer) || (**self).borrows_from(buffer)
+	}
+}
+
+impl<T: ?Sized> BorrowState for PhantomData<T> {
+	fn add_borrow_stats(&self, _stats: &mut BorrowStats) {}
+	fn borrows_from(&self, _buffer: &[u8]) -> bool {
+		false
+	}
+}
+
+// primitives and String are implemented by `impl_makeowned_basic!`
+
+// unsized types, so that references to them can be checked
+impl BorrowState for str {
+	fn add_borrow_stats(&self, _stats: &mut BorrowStats) {}
+	fn borrows_from(&self, _buffer: &[u8]) -> bool {
+		false
+	}
+}
+
+impl<T: BorrowState> BorrowState for [T] {
+	fn add_borrow_stats(&self, stats: &mut BorrowStats) {
+		self.iter().for_each(|x| x.add_borrow_stats(stats));
+	}
+	fn borrows_from(&self, buffer: &[u8]) -> bool {
+		self.iter().any(|x| x.borrows_from(buffer))
+	}
+}
+
+impl<const N: usize, T: BorrowState> BorrowState for [T; N] {
+	fn add_borrow_stats(&self, stats: &mut BorrowStats) {
+		self.as_slice().add_borrow_stats(stats);
+	}
+	fn borrows_from(&self, buffer: &[u8]) -> bool {
+		self.as_slice().borrows_from(buffer)
+	}
+}
+
+impl<T: BorrowState> BorrowState for Option<T> {
+	fn add_borrow_stats(&self, stats: &mut BorrowStats) {
+		if let Some(x) = self {
+			x.add_borrow_stats(stats);
+		}
+	}
+	fn borrows_from(&self, buffer: &[u8]) -> bool {
+		self.as_ref().is_some_and(|x| x.borrows_from(buffer))
+	}
+}
+
+impl<T: BorrowState> BorrowState for Vec<T> {
+	fn add_borrow_stats(&self, stats: &mut BorrowStats) {
+		self.as_slice().add_borrow_stats(stats);
+	}
+	fn borrows_from(&self, buffer: &[u8]) -> bool {
+		self.as_slice().borrows_from(buffer)
+	}
+}
+
+impl<T: BorrowState + ?Sized> BorrowState for Box<T> {
+	fn add_borrow_stats(&self, stats: &mut BorrowStats) {
+		(**self).add_borrow_stats(stats);
+	}
+	fn borrows_from(&self, buffer: &[u8]) -> bool {
+		(**self).borrows_from(buffer)
+	}
+}
+
+impl<K: BorrowState, V: BorrowState, S> BorrowState for HashMap<K, V, S> {
+	fn add_borrow_stats(&self, stats: &mut BorrowStats) {
+		for (k, v) in self {
+			k.add_borrow_stats(stats);
+			v.add_borrow_stats(stats);
+		}
+	}
+	fn borrows_from(&self, buffer: &[u8]) -> bool {
+		self.iter()
+			.any(|(k, v)| k.borrows_from(buffer) || v.borrows_from(buffer))
+	}
+}
+
+impl<K: BorrowState, V: BorrowState> BorrowState for BTreeMap<K, V> {
+	fn add_borrow_stats(&self, stats: &mut BorrowStats) {
+		for (k, v) in self {
+			k.add_borrow_stats(stats);
+			v.add_borrow_stats(stats);
+		}
+	}
+	fn borrows_from(&self, buffer: &[u8]) -> bool {
+		self.iter()
+			.any(|(k, v)| k.borrows_from(buffer) || v.borrows_from(buffer))
+	}
+}
+
+#[cfg(feature = "indexmap")]
+impl<K: BorrowState, V: BorrowState, S> BorrowState for indexmap::IndexMap<K, V, S> {
+	fn add_borrow_stats(&self, stats: &mut BorrowStats) {
+		for (k, v) in self {
+			k.add_borrow_stats(stats);
+			v.add_borrow_stats(stats);
+		}
+	}
+	fn borrows_from(&self, buffer: &[u8]) -> bool {
+		self.iter()
+			.any(|(k, v)| k.borrows_from(buffer) || v.borrows_from(buffer))
+	}
+}
@@ -0,0 +1,73 @@
+#![allow(dead_code)]
+
+use crate::{tests::fixtures::Header, BorrowState, CoCow, CoCowSlice};
+use std::{borrow::Cow, collections::HashMap, mem::size_of, sync::Mutex};
+
+#[derive(BorrowState)]
+struct Frame<'a, T> {
+	id: T,
+	headers: Vec<Header<'a>>,
+	parent: Option<CoCow<'a, Header<'a>>>,
+	body: CoCowSlice<'a, u8>,
+	index: HashMap<u32, Cow<'a, [u8]>>,
+	#[makeowned(skip, no_inspect)]
+	lock: Mutex<()>,
+}
+
+#[derive(BorrowState)]
+enum Message<'a> {
+	Frame(Box<Frame<'a, u32>>),
+	Text { text: Cow<'a, str> },
+	// converted with a function, but still borrowing
+	Label(#[makeowned(with = "ToOwned::to_owned")] &'a str),
+	Empty,
+}
+
+#[test]
+fn borrow_state() {
+	let buffer = b"name value body".to_vec();
+	let text = std::str::from_utf8(&buffer).unwrap();
+	let other = String::from("other");
+	let values = [Cow::Borrowed(other.as_str())];
+
+	let header = Header {
+		name: Cow::Borrowed(&text[..4]),
+		values: CoCowSlice::Borrowed(&values),
+	};
+	let frame = Frame {
+		id: 1u32,
+		headers: vec![header.clone()],
+		parent: Some(CoCow::Borrowed(&header)),
+		body: CoCowSlice::Borrowed(&buffer[11..]),
+		index: HashMap::from([(1, Cow::Owned(vec![1, 2]))]),
+		lock: Mutex::new(()),
+	};
+
+	let stats = frame.borrow_stats();
+	// two names, two slices of values with a value each, the parent and the body
+	assert_eq!(stats.borrowed, 8);
+	assert_eq!(stats.owned, 1);
+	assert_eq!(
+		stats.borrowed_bytes,
+		(4 + size_of::<[Cow<str>; 1]>() + 5) * 2 + size_of::<Header>() + 4
+	);
+	assert!(!frame.is_fully_owned());
+	assert!(frame.borrows_from(&buffer));
+	assert!(frame.borrows_from(other.as_bytes()));
+	assert!(!frame.borrows_from(b"unrelated"));
+
+	let message = Message::Frame(Box::new(frame));
+	assert!(message.borrows_from(&buffer[11..]));
+	assert!(!message.borrows_from(&buffer[5..10]));
+
+	let owned = Message::Text {
+		text: Cow::Owned(String::from("text")),
+	};
+	assert!(owned.is_fully_owned());
+	assert!(!owned.borrows_from(&buffer));
+	assert!(Message::Empty.is_fully_owned());
+
+	let label = Message::Label(&text[5..10]);
+	assert!(label.borrows_from(&buffer));
+	assert!(!label.is_fully_owned());
+}
@@ -8,241 +8,209 @@
 // are simpler, not relying on the ToOwned trait and are covariant over T, therefore not having
 // this problem.
 
-use std::{
-	borrow::{Borrow, Cow},
-	fmt::{Display, Formatter},
-	ops::Deref,
-};
-
-use crate::{MakeOwned, ShallowClone};
+use std::slice;
 
-/// Covariant copy-on-write. This is a simpler version of [`Cow`][std::borrow::Cow] that doesn't
-/// rely on [`ToOwned`] trait and is covariant over `T`.
-///
-/// You may wish to use this instead of the standard [`Cow`][std::borrow::Cow] if your
-/// inner type `T` contains references. Standard [`Cow<T>`][std::borrow::Cow] is invariant over `T`,
-/// which means you can't subtype the lifetimes of the inner `T` when making a shallow clone, which
-/// may introduce problems and force you to use multiple lifetimes.
-///
-/// This is a general version, if you wish to replicate [`Cow<'a, [T]>`][std::borrow::Cow] you
-/// should consider using [`CoCowSlice`], which allows you to have slices without an underlying
-/// allocated type like [`Vec`][std::vec::Vec].
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-#[cfg_attr(feature = "serde", serde(untagged))]
-pub enum CoCow<'a, T> {
-	Owned(T),
-	#[cfg_attr(feature = "serde", serde(skip_deserializing))]
-	Borrowed(&'a T),
-}
+use crate::{
+	borrow_state::points_into,
+	try_make_owned::{charge_clone, try_elements},
+	BorrowState, BorrowStats, Budget, CowVisitor, CowVisitorMut, FieldPath, HeapSize, HeapUsage,
+	MakeOwned, OwnError, OwnInPlace, ToOwnedStatic, TryMakeOwned, VisitCows,
+};
 
-/// Covariant copy-on-write slice. This is a specialised version of [`CoCow`] for slices
-/// and allows you to have slices without an underlying allocated type like Vec if you wish.
-///
-/// You may wish to use this instead of the standard [`Cow`][std::borrow::Cow] if your
-/// inner type `T` contains references. Standard [`Cow<T>`][std::borrow::Cow] is invariant over `T`, which means
-/// you can't subtype the lifetimes of the inner `T` when making a shallow clone, which
-/// may introduce problems and force you to use multiple lifetimes.
-///
-/// For a more general version, see [`CoCow`].
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-#[cfg_attr(feature = "serde", serde(untagged))]
-pub enum CoCowSlice<'a, T> {
-	Owned(Vec<T>),
-	#[cfg_attr(feature = "serde", serde(skip_deserializing))]
-	Borrowed(&'a [T]),
-}
+crate::__define_cocow_common! {
+	'shallowclone;
+	/// Covariant copy-on-write. This is a simpler version of [`Cow`][std::borrow::Cow] that doesn't
+	/// rely on [`ToOwned`] trait and is covariant over `T`.
+	///
+	/// You may wish to use this instead of the standard [`Cow`][std::borrow::Cow] if your
+	/// inner type `T` contains references. Standard [`Cow<T>`][std::borrow::Cow] is invariant over `T`,
+	/// which means you can't subtype the lifetimes of the inner `T` when making a shallow clone, which
+	/// may introduce problems and force you to use multiple lifetimes.
+	///
+	/// This is a general version, if you wish to replicate [`Cow<'a, [T]>`][std::borrow::Cow] you
+	/// should consider using [`CoCowSlice`], which allows you to have slices without an underlying
+	/// allocated type like [`Vec`][std::vec::Vec].
+	#[derive(Debug, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+	pub enum CoCow<'a, T> {
+		Owned(T),
+		Borrowed(&'a T),
+	}
+}
+
+crate::__define_cocow_common! {
+	'shallowclone;
+	/// Covariant copy-on-write slice. This is a specialised version of [`CoCow`] for slices
+	/// and allows you to have slices without an underlying allocated type like Vec if you wish.
+	///
+	/// You may wish to use this instead of the standard [`Cow`][std::borrow::Cow] if your
+	/// inner type `T` contains references. Standard [`Cow<T>`][std::borrow::Cow] is invariant over `T`, which means
+	/// you can't subtype the lifetimes of the inner `T` when making a shallow clone, which
+	/// may introduce problems and force you to use multiple lifetimes.
+	///
+	/// For a more general version, see [`CoCow`].
+	#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+	pub enum CoCowSlice<'a, T> {
+		Owned(Vec<T>),
+		Borrowed(&'a [T]),
+	}
+	borrow = Vec::as_slice;
+}
+
+impl<'a, T: MakeOwned + Clone> MakeOwned for CoCow<'a, T>
+where
+	<T as MakeOwned>::Owned: Clone,
+{
+	type Owned = CoCow<'static, <T as MakeOwned>::Owned>;
 
-impl<'a, T: Clone> CoCow<'a, T> {
-	/// Returns the inner owned value, cloning if it was borrowed.
-	pub fn into_owned(self) -> T {
-		match self {
-			CoCow::Owned(owned) => owned,
-			CoCow::Borrowed(borrowed) => borrowed.clone(),
-		}
-	}
-	/// Returns a mutable reference to the inner owned value, cloning if it was borrowed.
-	pub fn to_mut(&mut self) -> &mut T {
+	fn make_owned(self) -> <Self as MakeOwned>::Owned {
 		match self {
-			CoCow::Owned(owned) => owned,
-			CoCow::Borrowed(borrowed) => {
-				*self = CoCow::Owned(borrowed.clone());
-				match self {
-					CoCow::Owned(owned) => owned,
-					_ => unreachable!(),
-				}
-			}
+			CoCow::Owned(owned) => CoCow::Owned(owned.make_owned()),
+			CoCow::Borrowed(borrowed) => CoCow::Owned(borrowed.clone().make_owned()),
 		}
 	}
 }
-impl<'a, T> CoCow<'a, T> {
-	/// Returns `true` if the value is borrowed.
-	pub fn is_borrowed(&self) -> bool {
-		matches!(self, CoCow::Borrowed(_))
-	}
-	/// Returns `true` if the value is owned.
-	pub fn is_owned(&self) -> bool {
-		matches!(self, CoCow::Owned(_))
+impl<'a, T: MakeOwned + ToOwnedStatic + Clone> ToOwnedStatic for CoCow<'a, T>
+where
+	<T as MakeOwned>::Owned: Clone,
+{
+	fn to_owned_static(&self) -> <Self as MakeOwned>::Owned {
+		CoCow::Owned((**self).to_owned_static())
 	}
 }
+impl<'a, T: MakeOwned + Clone> MakeOwned for CoCowSlice<'a, T>
+where
+	<T as MakeOwned>::Owned: Clone,
+{
+	type Owned = CoCowSlice<'static, <T as MakeOwned>::Owned>;
 
-impl<'a, T: Clone> CoCowSlice<'a, T> {
-	/// Returns the inner owned [`Vec`][std::vec::Vec], cloning if it was borrowed.
-	pub fn into_owned(self) -> Vec<T> {
-		match self {
-			CoCowSlice::Owned(owned) => owned,
-			CoCowSlice::Borrowed(borrowed) => borrowed.to_owned(),
-		}
-	}
-	/// Returns a mutable reference to the inner owned [`Vec`][std::vec::Vec], cloning if it was borrowed.
-	pub fn to_mut(&mut self) -> &mut Vec<T> {
+	fn make_owned(self) -> <Self as MakeOwned>::Owned {
 		match self {
-			CoCowSlice::Owned(owned) => owned,
+			CoCowSlice::Owned(owned) => CoCowSlice::Owned(owned.make_owned()),
 			CoCowSlice::Borrowed(borrowed) => {
-				*self = CoCowSlice::Owned(borrowed.to_owned());
-				match self {
-					CoCowSlice::Owned(owned) => owned,
-					_ => unreachable!(),
-				}
+				CoCowSlice::Owned(borrowed.iter().map(|e| e.clone().make_owned()).collect())
 			}
 		}
 	}
 }
-impl<'a, T> CoCowSlice<'a, T> {
-	/// Returns `true` if the value is borrowed.
-	pub fn is_borrowed(&self) -> bool {
-		matches!(self, CoCowSlice::Borrowed(_))
-	}
-	/// Returns `true` if the value is owned.
-	pub fn is_owned(&self) -> bool {
-		matches!(self, CoCowSlice::Owned(_))
-	}
-}
-
-impl<'a, T> ShallowClone<'a> for CoCow<'a, T> {
-	type Target = CoCow<'a, T>;
-
-	fn shallow_clone(&'a self) -> Self::Target {
-		match self {
-			CoCow::Owned(owned) => CoCow::Borrowed(&owned),
-			CoCow::Borrowed(borrowed) => CoCow::Borrowed(borrowed),
-		}
-	}
-}
-impl<'a, T> ShallowClone<'a> for CoCowSlice<'a, T> {
-	type Target = CoCowSlice<'a, T>;
-
-	fn shallow_clone(&'a self) -> Self::Target {
-		match self {
-			CoCowSlice::Owned(owned) => CoCowSlice::Borrowed(&owned),
-			CoCowSlice::Borrowed(borrowed) => CoCowSlice::Borrowed(borrowed),
-		}
+impl<'a, T: MakeOwned + ToOwnedStatic + Clone> ToOwnedStatic for CoCowSlice<'a, T>
+where
+	<T as MakeOwned>::Owned: Clone,
+{
+	fn to_owned_static(&self) -> <Self as MakeOwned>::Owned {
+		CoCowSlice::Owned(self.iter().map(|e| e.to_owned_static()).collect())
 	}
 }
 
-impl<'a, T: MakeOwned> MakeOwned for CoCow<'a, T>
+impl<'a, T: TryMakeOwned + HeapSize + Clone> TryMakeOwned for CoCow<'a, T>
 where
 	<T as MakeOwned>::Owned: Clone,
 {
-	type Owned = CoCow<'static, <T as MakeOwned>::Owned>;
-
-	fn make_owned(self) -> <Self as MakeOwned>::Owned {
-		CoCow::Owned(self.into_owned().make_owned())
+	fn try_make_owned(self, budget: &mut Budget) -> Result<<Self as MakeOwned>::Owned, OwnError> {
+		let owned = match self {
+			CoCow::Owned(owned) => owned,
+			CoCow::Borrowed(borrowed) => {
+				charge_clone(slice::from_ref(borrowed), budget)?;
+				borrowed.clone()
+			}
+		};
+		Ok(CoCow::Owned(owned.try_make_owned(budget)?))
 	}
 }
-impl<'a, T: MakeOwned> MakeOwned for CoCowSlice<'a, T>
+impl<'a, T: TryMakeOwned + HeapSize + Clone> TryMakeOwned for CoCowSlice<'a, T>
 where
 	<T as MakeOwned>::Owned: Clone,
 {
-	type Owned = CoCowSlice<'static, <T as MakeOwned>::Owned>;
-
-	fn make_owned(self) -> <Self as MakeOwned>::Owned {
-		CoCowSlice::Owned(self.into_owned().make_owned())
+	fn try_make_owned(self, budget: &mut Budget) -> Result<<Self as MakeOwned>::Owned, OwnError> {
+		Ok(CoCowSlice::Owned(match self {
+			CoCowSlice::Owned(owned) => try_elements(owned, budget)?,
+			CoCowSlice::Borrowed(borrowed) => {
+				charge_clone(borrowed, budget)?;
+				try_elements(borrowed.iter().cloned(), budget)?
+			}
+		}))
 	}
 }
 
-impl<'a, T> Deref for CoCow<'a, T> {
-	type Target = T;
-
-	fn deref(&self) -> &Self::Target {
-		match self {
-			CoCow::Owned(owned) => owned,
-			CoCow::Borrowed(borrowed) => borrowed,
-		}
+impl<'a, T: OwnInPlace + Clone> OwnInPlace for CoCow<'a, T> {
+	fn own_in_place(&mut self) {
+		self.to_mut().own_in_place();
 	}
 }
-impl<'a, T> Deref for CoCowSlice<'a, T> {
-	type Target = [T];
-
-	fn deref(&self) -> &Self::Target {
-		match self {
-			CoCowSlice::Owned(owned) => owned,
-			CoCowSlice::Borrowed(borrowed) => borrowed,
-		}
+impl<'a, T: OwnInPlace + Clone> OwnInPlace for CoCowSlice<'a, T> {
+	fn own_in_place(&mut self) {
+		self.to_mut().own_in_place();
 	}
 }
 
-impl<'a, T> AsRef<T> for CoCow<'a, T> {
-	fn as_ref(&self) -> &T {
-		self
+impl<'a, T: BorrowState> BorrowState for CoCow<'a, T> {
+	fn add_borrow_stats(&self, stats: &mut BorrowStats) {
+		match self {
+			CoCow::Borrowed(borrowed) => stats.add_borrowed(*borrowed),
+			CoCow::Owned(_) => stats.add_owned(),
+		}
+		(**self).add_borrow_stats(stats);
 	}
-}
-impl<'a, T> AsRef<[T]> for CoCowSlice<'a, T> {
-	fn as_ref(&self) -> &[T] {
-		self
+	fn borrows_from(&self, buffer: &[u8]) -> bool {
+		matches!(self, CoCow::Borrowed(borrowed) if points_into(*borrowed, buffer))
+			|| (**self).borrows_from(buffer)
 	}
 }
-
-impl<'a, T> Borrow<T> for CoCow<'a, T> {
-	fn borrow(&self) -> &T {
-		self
+impl<'a, T: BorrowState> BorrowState for CoCowSlice<'a, T> {
+	fn add_borrow_stats(&self, stats: &mut BorrowStats) {
+		match self {
+			CoCowSlice::Borrowed(borrowed) => stats.add_borrowed(*borrowed),
+			CoCowSlice::Owned(_) => stats.add_owned(),
+		}
+		(**self).add_borrow_stats(stats);
 	}
-}
-impl<'a, T> Borrow<[T]> for CoCowSlice<'a, T> {
-	fn borrow(&self) -> &[T] {
-		self
+	fn borrows_from(&self, buffer: &[u8]) -> bool {
+		matches!(self, CoCowSlice::Borrowed(borrowed) if points_into(*borrowed, buffer))
+			|| (**self).borrows_from(buffer)
 	}
 }
 
-impl<'a, T: Default> Default for CoCow<'a, T> {
-	fn default() -> Self {
-		CoCow::Owned(Default::default())
-	}
-}
-impl<'a, T> Default for CoCowSlice<'a, T> {
-	fn default() -> Self {
-		CoCowSlice::Owned(Default::default())
+impl<'a, T: HeapSize> HeapSize for CoCow<'a, T> {
+	fn add_heap_size(&self, usage: &mut HeapUsage) {
+		match self {
+			CoCow::Borrowed(borrowed) => usage.add_borrowed(*borrowed),
+			CoCow::Owned(owned) => owned.add_heap_size(usage),
+		}
 	}
 }
-
-impl<'a, T: Display> Display for CoCow<'a, T> {
-	fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
-		Display::fmt(&**self, f)
+impl<'a, T: HeapSize> HeapSize for CoCowSlice<'a, T> {
+	fn add_heap_size(&self, usage: &mut HeapUsage) {
+		match self {
+			CoCowSlice::Borrowed(borrowed) => usage.add_borrowed(*borrowed),
+			CoCowSlice::Owned(owned) => owned.add_heap_size(usage),
+		}
 	}
 }
 
-impl<'a, T> From<T> for CoCow<'a, T> {
-	fn from(value: T) -> Self {
-		CoCow::Owned(value)
+impl<'a, T: VisitCows + Clone> VisitCows for CoCow<'a, T> {
+	fn visit_cows_at<V: CowVisitor>(&self, visitor: &mut V, path: &mut FieldPath) {
+		visitor.visit_cocow(path, self);
+		(**self).visit_cows_at(visitor, path);
 	}
-}
-impl<'a, T> From<Vec<T>> for CoCowSlice<'a, T> {
-	fn from(value: Vec<T>) -> Self {
-		CoCowSlice::Owned(value)
+	fn visit_cows_mut_at<V: CowVisitorMut>(&mut self, visitor: &mut V, path: &mut FieldPath) {
+		visitor.visit_cocow(path, self);
+		if let CoCow::Owned(owned) = self {
+			owned.visit_cows_mut_at(visitor, path);
+		}
 	}
 }
-
-impl<'a, T> From<&'a T> for CoCow<'a, T> {
-	fn from(value: &'a T) -> Self {
-		CoCow::Borrowed(value)
+impl<'a, T: VisitCows + Clone> VisitCows for CoCowSlice<'a, T> {
+	fn visit_cows_at<V: CowVisitor>(&self, visitor: &mut V, path: &mut FieldPath) {
+		visitor.visit_cocow_slice(path, self);
+		(**self).visit_cows_at(visitor, path);
 	}
-}
-impl<'a, T> From<&'a [T]> for CoCowSlice<'a, T> {
-	fn from(value: &'a [T]) -> Self {
-		CoCowSlice::Borrowed(value)
+	fn visit_cows_mut_at<V: CowVisitorMut>(&mut self, visitor: &mut V, path: &mut FieldPath) {
+		visitor.visit_cocow_slice(path, self);
+		if let CoCowSlice::Owned(owned) = self {
+			owned.visit_cows_mut_at(visitor, path);
+		}
 	}
 }
+
 impl<'a, T> From<&'a Vec<T>> for CoCowSlice<'a, T> {
 	fn from(value: &'a Vec<T>) -> Self {
 		CoCowSlice::Borrowed(value)
@@ -254,51 +222,8 @@ impl<'a, const N: usize, T> From<&'a [T; N]> for CoCowSlice<'a, T> {
 	}
 }
 
-impl<'a, T: Clone> From<Cow<'a, T>> for CoCow<'a, T> {
-	fn from(value: Cow<'a, T>) -> Self {
-		match value {
-			Cow::Borrowed(borrowed) => Self::Borrowed(borrowed),
-			Cow::Owned(owned) => Self::Owned(owned),
-		}
-	}
-}
-impl<'a, T: Clone> From<Cow<'a, [T]>> for CoCowSlice<'a, T> {
-	fn from(value: Cow<'a, [T]>) -> Self {
-		match value {
-			Cow::Borrowed(borrowed) => Self::Borrowed(borrowed),
-			Cow::Owned(owned) => Self::Owned(owned),
-		}
-	}
-}
-
-impl<'a, T> IntoIterator for &'a CoCow<'a, T>
-where
-	&'a T: IntoIterator,
-{
-	type Item = <&'a T as IntoIterator>::Item;
-	type IntoIter = <&'a T as IntoIterator>::IntoIter;
-
-	fn into_iter(self) -> Self::IntoIter {
-		match self {
-			CoCow::Owned(owned) => owned.into_iter(),
-			CoCow::Borrowed(borrowed) => borrowed.into_iter(),
-		}
-	}
-}
-impl<'a, T> IntoIterator for &'a CoCowSlice<'a, T>
-where
-	&'a [T]: IntoIterator,
-{
-	type Item = <&'a [T] as IntoIterator>::Item;
-	type IntoIter = <&'a [T] as IntoIterator>::IntoIter;
-
-	fn into_iter(self) -> Self::IntoIter {
-		match self {
-			CoCowSlice::Owned(owned) => (&owned[..]).into_iter(),
-			CoCowSlice::Borrowed(borrowed) => borrowed.into_iter(),
-		}
-	}
-}
+crate::assert_makeowned_subtype!(<T: MakeOwned<Owned = T> + Clone> CoCow<'_, T>);
+crate::assert_makeowned_subtype!(<T: MakeOwned<Owned = T> + Clone> CoCowSlice<'_, T>);
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,477 @@
+//! [`define_cocow!`][crate::define_cocow] for making custom covariant copy-on-write types.
+
+/// Defines a covariant copy-on-write enum like [`CoCow`][crate::CoCow] and [`CoCowSlice`][crate::CoCowSlice],
+/// with the same set of trait implementations.
+///
+/// The enum must have exactly one lifetime parameter (followed by any number of type parameters without
+/// bounds), an `Owned(O)` variant and a `Borrowed(&'a B)` variant, in that order. It's converted from owned
+/// to borrowed with [`Borrow<B>`][std::borrow::Borrow], or a custom `fn(&O) -> &B` specified after the
+/// enum with `borrow = path::to::function;`. It's converted from borrowed to owned with [`ToOwned`],
+/// so `B: ToOwned<Owned = O>` is required for some of the methods and implementations.
+///
+/// The following is generated:
+///  - `into_owned`, `to_mut`, `is_borrowed` and `is_owned` methods,
+///  - [`Clone`] (so don't derive it), [`ShallowClone`][crate::ShallowClone] and
+///    [`ShallowCloneGat`][crate::ShallowCloneGat],
+///  - [`MakeOwned`][crate::MakeOwned], [`ToOwnedStatic`][crate::ToOwnedStatic],
+///    [`TryMakeOwned`][crate::TryMakeOwned], [`OwnInPlace`][crate::OwnInPlace],
+///    [`Rebase`][crate::Rebase], [`BorrowState`][crate::BorrowState], [`HeapSize`][crate::HeapSize],
+///    [`VisitCows`][crate::VisitCows] and, if the `bumpalo` feature of this crate is enabled,
+///    [`MakeOwnedIn`][crate::MakeOwnedIn]. These leave the type parameters as they are and don't look
+///    into the borrowed value, which is copied as a whole. [`HeapSize`][crate::HeapSize] is only
+///    implemented if the owned and the borrowed types implement it, and
+///    [`TryMakeOwned`][crate::TryMakeOwned] if the borrowed type does,
+///  - [`Deref`][std::ops::Deref], [`AsRef`], [`Borrow`][std::borrow::Borrow], [`Default`] and
+///    [`Display`][std::fmt::Display],
+///  - [`From`] the owned value, the borrowed reference and [`Cow`][std::borrow::Cow],
+///  - [`IntoIterator`] for references,
+///  - serde `Serialize` and `Deserialize` (like `#[serde(untagged)]`), if the `serde` feature of this
+///    crate is enabled.
+///
+/// Other attributes, like derives and docs, are left on the enum. [`CoCow`][crate::CoCow] and
+/// [`CoCowSlice`][crate::CoCowSlice] are defined the same way, except that their traits convert the
+/// elements too.
+///
+/// ```
+/// use std::path::{Path, PathBuf};
+///
+/// shallowclone::define_cocow! {
+///     /// A covariant `Cow<'a, Path>`
+///     #[derive(Debug, PartialEq)]
+///     pub enum CoCowPath<'a> {
+///         Owned(PathBuf),
+///         Borrowed(&'a Path),
+///     }
+/// }
+///
+/// shallowclone::define_cocow! {
+///     pub enum CoCowVec<'a, T> {
+///         Owned(Vec<T>),
+///         Borrowed(&'a [T]),
+///     }
+///     borrow = Vec::as_slice;
+/// }
+///
+/// let path = CoCowPath::from(Path::new("/tmp"));
+/// assert_eq!(path.into_owned(), PathBuf::from("/tmp"));
+/// ```
+#[macro_export]
+macro_rules! define_cocow {
+    (
+        $(#[$attr:meta])*
+        $vis:vis enum $name:ident<$lt:lifetime $(, $param:ident)* $(,)?> {
+            $(#[$owned_attr:meta])*
+            Owned($owned:ty),
+            $(#[$borrowed_attr:meta])*
+            Borrowed(&$borrowed_lt:lifetime $borrowed:ty) $(,)?
+        }
+        $(borrow = $borrow:expr;)?
+    ) => {
+        // the lifetimes of `macro_rules!` aren't hygienic, so the ones of the impls are chosen to not
+        // collide with the one of the enum
+        $crate::__private::fresh_lifetimes! {
+            'shallowclone 'new 'arena => $crate::__define_cocow! {
+                $(#[$attr])*
+                $vis enum $name<$lt $(, $param)*> {
+                    $(#[$owned_attr])*
+                    Owned($owned),
+                    $(#[$borrowed_attr])*
+                    Borrowed(&$lt $borrowed),
+                }
+                $(borrow = $borrow;)?
+            }
+        }
+    };
+}
+
+/// [`define_cocow!`] with the lifetimes of the impls chosen, implementing the traits which would look
+/// into the elements of [`CoCow`][crate::CoCow] and [`CoCowSlice`][crate::CoCowSlice]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __define_cocow {
+    (
+        $shallowclone:lifetime $new:lifetime $arena:lifetime
+        $(#[$attr:meta])*
+        $vis:vis enum $name:ident<$lt:lifetime $(, $param:ident)*> {
+            $(#[$owned_attr:meta])*
+            Owned($owned:ty),
+            $(#[$borrowed_attr:meta])*
+            Borrowed(&$borrowed_lt:lifetime $borrowed:ty),
+        }
+        $(borrow = $borrow:expr;)?
+    ) => {
+        $crate::__define_cocow_common! {
+            $shallowclone;
+            $(#[$attr])*
+            $vis enum $name<$lt $(, $param)*> {
+                $(#[$owned_attr])*
+                Owned($owned),
+                $(#[$borrowed_attr])*
+                Borrowed(&$lt $borrowed),
+            }
+            $(borrow = $borrow;)?
+        }
+
+        impl<$lt $(, $param: 'static)*> $crate::MakeOwned for $name<$lt $(, $param)*>
+        where
+            <Self as $crate::__private::CoCowTypes>::Owned: 'static,
+            <Self as $crate::__private::CoCowTypes>::Borrowed: ::std::borrow::ToOwned<Owned = <Self as $crate::__private::CoCowTypes>::Owned> + 'static,
+        {
+            type Owned = $name<'static $(, $param)*>;
+
+            fn make_owned(self) -> <Self as $crate::MakeOwned>::Owned {
+                $name::Owned(self.into_owned())
+            }
+        }
+
+        impl<$lt $(, $param: 'static)*> $crate::ToOwnedStatic for $name<$lt $(, $param)*>
+        where
+            <Self as $crate::__private::CoCowTypes>::Owned: 'static,
+            <Self as $crate::__private::CoCowTypes>::Borrowed: ::std::borrow::ToOwned<Owned = <Self as $crate::__private::CoCowTypes>::Owned> + 'static,
+        {
+            fn to_owned_static(&self) -> <Self as $crate::MakeOwned>::Owned {
+                $name::Owned(::std::borrow::ToOwned::to_owned(&**self))
+            }
+        }
+
+        // copying the borrowed value also allocates the heap memory it owns
+        impl<$lt $(, $param: 'static)*> $crate::TryMakeOwned for $name<$lt $(, $param)*>
+        where
+            <Self as $crate::__private::CoCowTypes>::Owned: 'static,
+            <Self as $crate::__private::CoCowTypes>::Borrowed: ::std::borrow::ToOwned<Owned = <Self as $crate::__private::CoCowTypes>::Owned> + $crate::HeapSize + 'static,
+        {
+            fn try_make_owned(self, budget: &mut $crate::Budget) -> ::core::result::Result<<Self as $crate::MakeOwned>::Owned, $crate::OwnError> {
+                if let Self::Borrowed(borrowed) = &self {
+                    let heap = $crate::HeapSize::heap_size(*borrowed).owned;
+                    budget.charge(::core::mem::size_of_val(*borrowed).saturating_add(heap), 1)?;
+                }
+                ::core::result::Result::Ok($crate::MakeOwned::make_owned(self))
+            }
+        }
+
+        impl<$lt $(, $param)*> $crate::OwnInPlace for $name<$lt $(, $param)*>
+        where
+            <Self as $crate::__private::CoCowTypes>::Borrowed: ::std::borrow::ToOwned<Owned = <Self as $crate::__private::CoCowTypes>::Owned>,
+        {
+            fn own_in_place(&mut self) {
+                self.to_mut();
+            }
+        }
+
+        // the borrowed value isn't known to be bytes of the buffer, so it's copied
+        impl<$new, $lt $(, $param: $new)*> $crate::Rebase<$new> for $name<$lt $(, $param)*>
+        where
+            <Self as $crate::__private::CoCowTypes>::Owned: $new,
+            <Self as $crate::__private::CoCowTypes>::Borrowed: ::std::borrow::ToOwned<Owned = <Self as $crate::__private::CoCowTypes>::Owned> + $new,
+        {
+            type Rebased = $name<$new $(, $param)*>;
+
+            fn rebase(self, _old: &[u8], _new: &$new [u8]) -> <Self as $crate::Rebase<$new>>::Rebased {
+                $name::Owned(self.into_owned())
+            }
+        }
+
+        // the borrowed value is opaque, so only the reference itself is checked
+        impl<$lt $(, $param)*> $crate::BorrowState for $name<$lt $(, $param)*> {
+            fn add_borrow_stats(&self, stats: &mut $crate::BorrowStats) {
+                match self {
+                    Self::Owned(_) => stats.add_owned(),
+                    Self::Borrowed(borrowed) => stats.add_borrowed(*borrowed),
+                }
+            }
+
+            fn borrows_from(&self, buffer: &[u8]) -> bool {
+                matches!(self, Self::Borrowed(borrowed) if $crate::points_into(*borrowed, buffer))
+            }
+        }
+
+        impl<$lt $(, $param)*> $crate::HeapSize for $name<$lt $(, $param)*>
+        where
+            <Self as $crate::__private::CoCowTypes>::Owned: $crate::HeapSize,
+            <Self as $crate::__private::CoCowTypes>::Borrowed: $crate::HeapSize,
+        {
+            fn add_heap_size(&self, usage: &mut $crate::HeapUsage) {
+                match self {
+                    Self::Owned(owned) => $crate::HeapSize::add_heap_size(owned, usage),
+                    Self::Borrowed(borrowed) => usage.add_borrowed(*borrowed),
+                }
+            }
+        }
+
+        // the visitors don't know the type, so only `visit_any` is called
+        impl<$lt $(, $param)*> $crate::VisitCows for $name<$lt $(, $param)*> {
+            fn visit_cows_at<V: $crate::CowVisitor>(&self, visitor: &mut V, path: &mut $crate::FieldPath) {
+                visitor.visit_any(path, self.is_borrowed());
+            }
+
+            fn visit_cows_mut_at<V: $crate::CowVisitorMut>(&mut self, visitor: &mut V, path: &mut $crate::FieldPath) {
+                visitor.visit_any(path, self.is_borrowed());
+            }
+        }
+
+        $crate::__define_cocow_bumpalo! { $arena; $name<$lt $(, $param)*> $(, $borrow)? }
+    };
+}
+
+/// The enum and the implementations of [`define_cocow!`] which don't depend on what's inside, and are
+/// the same for [`CoCow`][crate::CoCow] and [`CoCowSlice`][crate::CoCowSlice]. The lifetime for
+/// `ShallowClone` is given first.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __define_cocow_common {
+    (@borrow $owned:ident) => {
+        ::core::borrow::Borrow::borrow($owned)
+    };
+    (@borrow $owned:ident $borrow:expr) => {
+        ($borrow)($owned)
+    };
+    (
+        $shallowclone:lifetime;
+        $(#[$attr:meta])*
+        $vis:vis enum $name:ident<$lt:lifetime $(, $param:ident)* $(,)?> {
+            $(#[$owned_attr:meta])*
+            Owned($owned:ty),
+            $(#[$borrowed_attr:meta])*
+            Borrowed(&$borrowed_lt:lifetime $borrowed:ty) $(,)?
+        }
+        $(borrow = $borrow:expr;)?
+    ) => {
+        $(#[$attr])*
+        $vis enum $name<$lt $(, $param)*> {
+            $(#[$owned_attr])*
+            Owned($owned),
+            $(#[$borrowed_attr])*
+            Borrowed(&$lt $borrowed),
+        }
+
+        // bounds go through `CoCowTypes` so that they're not rejected as trivially false for concrete types
+        impl<$lt $(, $param)*> $crate::__private::CoCowTypes for $name<$lt $(, $param)*> {
+            type Owned = $owned;
+            type Borrowed = $borrowed;
+        }
+
+        impl<$lt $(, $param)*> $name<$lt $(, $param)*>
+        where
+            <Self as $crate::__private::CoCowTypes>::Borrowed: ::std::borrow::ToOwned<Owned = <Self as $crate::__private::CoCowTypes>::Owned>,
+        {
+            /// Returns the inner owned value, cloning if it was borrowed.
+            pub fn into_owned(self) -> $owned {
+                match self {
+                    Self::Owned(owned) => owned,
+                    Self::Borrowed(borrowed) => ::std::borrow::ToOwned::to_owned(borrowed),
+                }
+            }
+            /// Returns a mutable reference to the inner owned value, cloning if it was borrowed.
+            pub fn to_mut(&mut self) -> &mut $owned {
+                if let Self::Borrowed(borrowed) = *self {
+                    *self = Self::Owned(::std::borrow::ToOwned::to_owned(borrowed));
+                }
+                match self {
+                    Self::Owned(owned) => owned,
+                    Self::Borrowed(_) => unreachable!(),
+                }
+            }
+        }
+        impl<$lt $(, $param)*> $name<$lt $(, $param)*> {
+            /// Returns `true` if the value is borrowed.
+            pub fn is_borrowed(&self) -> bool {
+                matches!(self, Self::Borrowed(_))
+            }
+            /// Returns `true` if the value is owned.
+            pub fn is_owned(&self) -> bool {
+                matches!(self, Self::Owned(_))
+            }
+        }
+
+        impl<$lt $(, $param)*> ::core::clone::Clone for $name<$lt $(, $param)*>
+        where
+            <Self as $crate::__private::CoCowTypes>::Owned: ::core::clone::Clone,
+        {
+            fn clone(&self) -> Self {
+                match self {
+                    Self::Owned(owned) => Self::Owned(::core::clone::Clone::clone(owned)),
+                    Self::Borrowed(borrowed) => Self::Borrowed(borrowed),
+                }
+            }
+        }
+
+        impl<$shallowclone, $lt $(, $param)*> $crate::ShallowClone<$shallowclone> for $name<$lt $(, $param)*>
+        where
+            $lt: $shallowclone,
+        {
+            type Target = $name<$shallowclone $(, $param)*>;
+
+            fn shallow_clone(&$shallowclone self) -> Self::Target {
+                $crate::ShallowCloneGat::shallow_clone_gat(self)
+            }
+        }
+
+        impl<$lt $(, $param)*> $crate::ShallowCloneGat for $name<$lt $(, $param)*> {
+            type Target<$shallowclone> = $name<$shallowclone $(, $param)*> where Self: $shallowclone;
+
+            fn shallow_clone_gat(&self) -> Self::Target<'_> {
+                match self {
+                    Self::Owned(owned) => $name::Borrowed($crate::__define_cocow_common!(@borrow owned $($borrow)?)),
+                    Self::Borrowed(borrowed) => $name::Borrowed(borrowed),
+                }
+            }
+        }
+
+        impl<$lt $(, $param)*> ::core::ops::Deref for $name<$lt $(, $param)*> {
+            type Target = $borrowed;
+
+            fn deref(&self) -> &Self::Target {
+                match self {
+                    Self::Owned(owned) => $crate::__define_cocow_common!(@borrow owned $($borrow)?),
+                    Self::Borrowed(borrowed) => borrowed,
+                }
+            }
+        }
+
+        impl<$lt $(, $param)*> ::core::convert::AsRef<$borrowed> for $name<$lt $(, $param)*> {
+            fn as_ref(&self) -> &$borrowed {
+                self
+            }
+        }
+
+        impl<$lt $(, $param)*> ::core::borrow::Borrow<$borrowed> for $name<$lt $(, $param)*> {
+            fn borrow(&self) -> &$borrowed {
+                self
+            }
+        }
+
+        impl<$lt $(, $param)*> ::core::default::Default for $name<$lt $(, $param)*>
+        where
+            <Self as $crate::__private::CoCowTypes>::Owned: ::core::default::Default,
+        {
+            fn default() -> Self {
+                Self::Owned(::core::default::Default::default())
+            }
+        }
+
+        impl<$lt $(, $param)*> ::core::fmt::Display for $name<$lt $(, $param)*>
+        where
+            <Self as $crate::__private::CoCowTypes>::Borrowed: ::core::fmt::Display,
+        {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                ::core::fmt::Display::fmt(&**self, f)
+            }
+        }
+
+        impl<$lt $(, $param)*> ::core::convert::From<$owned> for $name<$lt $(, $param)*> {
+            fn from(value: $owned) -> Self {
+                Self::Owned(value)
+            }
+        }
+
+        impl<$lt $(, $param)*> ::core::convert::From<&$lt $borrowed> for $name<$lt $(, $param)*> {
+            fn from(value: &$lt $borrowed) -> Self {
+                Self::Borrowed(value)
+            }
+        }
+
+        impl<$lt $(, $param)*> ::core::convert::From<::std::borrow::Cow<$lt, $borrowed>> for $name<$lt $(, $param)*>
+        where
+            <Self as $crate::__private::CoCowTypes>::Borrowed: ::std::borrow::ToOwned<Owned = <Self as $crate::__private::CoCowTypes>::Owned>,
+        {
+            fn from(value: ::std::borrow::Cow<$lt, $borrowed>) -> Self {
+                match value {
+                    ::std::borrow::Cow::Borrowed(borrowed) => Self::Borrowed(borrowed),
+                    ::std::borrow::Cow::Owned(owned) => Self::Owned(owned),
+                }
+            }
+        }
+
+        impl<$lt $(, $param)*> ::core::iter::IntoIterator for &$lt $name<$lt $(, $param)*>
+        where
+            &$lt $borrowed: ::core::iter::IntoIterator,
+        {
+            type Item = <&$lt $borrowed as ::core::iter::IntoIterator>::Item;
+            type IntoIter = <&$lt $borrowed as ::core::iter::IntoIterator>::IntoIter;
+
+            fn into_iter(self) -> Self::IntoIter {
+                let borrowed: &$lt $borrowed = self;
+                ::core::iter::IntoIterator::into_iter(borrowed)
+            }
+        }
+
+        $crate::__define_cocow_serde! { $name<$lt $(, $param)*>, $owned, $borrowed }
+    };
+}
+
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __define_cocow_serde {
+    ($name:ident<$lt:lifetime $(, $param:ident)*>, $owned:ty, $borrowed:ty) => {
+        impl<$lt $(, $param)*> $crate::__private::serde::Serialize for $name<$lt $(, $param)*>
+        where
+            <Self as $crate::__private::CoCowTypes>::Borrowed: $crate::__private::serde::Serialize,
+        {
+            fn serialize<S: $crate::__private::serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> ::core::result::Result<S::Ok, S::Error> {
+                $crate::__private::serde::Serialize::serialize(&**self, serializer)
+            }
+        }
+
+        impl<'de, $lt $(, $param)*> $crate::__private::serde::Deserialize<'de> for $name<$lt $(, $param)*>
+        where
+            $owned: $crate::__private::serde::Deserialize<'de>,
+        {
+            fn deserialize<D: $crate::__private::serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> ::core::result::Result<Self, D::Error> {
+                <$owned as $crate::__private::serde::Deserialize<'de>>::deserialize(deserializer).map(Self::Owned)
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "serde"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __define_cocow_serde {
+	($($tt:tt)*) => {};
+}
+
+#[cfg(feature = "bumpalo")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __define_cocow_bumpalo {
+    ($arena:lifetime; $name:ident<$lt:lifetime $(, $param:ident)*> $(, $borrow:expr)?) => {
+        // the borrowed value is copied into the arena if it doesn't need to be dropped when owned
+        impl<$arena, $lt $(, $param: $arena)*> $crate::MakeOwnedIn<$arena> for $name<$lt $(, $param)*>
+        where
+            <Self as $crate::__private::CoCowTypes>::Owned: $arena,
+            <Self as $crate::__private::CoCowTypes>::Borrowed: ::std::borrow::ToOwned<Owned = <Self as $crate::__private::CoCowTypes>::Owned> + $arena,
+        {
+            type Owned = $name<$arena $(, $param)*>;
+
+            fn make_owned_in(self, arena: &$arena $crate::__private::Bump) -> <Self as $crate::MakeOwnedIn<$arena>>::Owned {
+                match self {
+                    Self::Borrowed(borrowed) if !::core::mem::needs_drop::<<Self as $crate::__private::CoCowTypes>::Owned>() => {
+                        let owned: &$arena _ = arena.alloc(::std::borrow::ToOwned::to_owned(borrowed));
+                        $name::Borrowed($crate::__define_cocow_common!(@borrow owned $($borrow)?))
+                    }
+                    value => $name::Owned(value.into_owned()),
+                }
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "bumpalo"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __define_cocow_bumpalo {
+	($($tt:tt)*) => {};
+}
+
+/// Gives the owned and borrowed types of a type defined with [`define_cocow!`][crate::define_cocow]
+#[doc(hidden)]
+pub trait CoCowTypes {
+	type Owned;
+	type Borrowed: ?Sized;
+}
@@ -0,0 +1,63 @@
+use std::fmt::{self, Display, Formatter};
+
+/// A step in a [`FieldPath`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+	/// A named field, or the index of a tuple field
+	Field(&'static str),
+	/// An element of a collection, maps use the position of the entry
+	Index(usize),
+}
+
+/// The path to a value from the root of a structure, like `items[2].name`
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct FieldPath {
+	segments: Vec<PathSegment>,
+}
+
+impl FieldPath {
+	pub fn new() -> Self {
+		Self::default()
+	}
+	/// The segments from the outermost field inwards
+	pub fn segments(&self) -> &[PathSegment] {
+		&self.segments
+	}
+	/// Whether this is the path of the root itself
+	pub fn is_empty(&self) -> bool {
+		self.segments.is_empty()
+	}
+	/// Enters a field, call [`pop`][Self::pop] when leaving it
+	pub fn push_field(&mut self, name: &'static str) {
+		self.segments.push(PathSegment::Field(name));
+	}
+	/// Enters an element of a collection, call [`pop`][Self::pop] when leaving it
+	pub fn push_index(&mut self, index: usize) {
+		self.segments.push(PathSegment::Index(index));
+	}
+	/// Leaves the innermost segment
+	pub fn pop(&mut self) -> Option<PathSegment> {
+		self.segments.pop()
+	}
+}
+
+impl Display for FieldPath {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		write_segments(f, &self.segments)
+	}
+}
+
+/// Writes the segments, ordered from the outermost one, like `items[2].name`
+pub(crate) fn write_segments<'a>(
+	f: &mut impl fmt::Write,
+	segments: impl IntoIterator<Item = &'a PathSegment>,
+) -> fmt::Result {
+	for (i, segment) in segments.into_iter().enumerate() {
+		match segment {
+			PathSegment::Field(name) if i == 0 => f.write_str(name)?,
+			PathSegment::Field(name) => write!(f, ".{name}")?,
+			PathSegment::Index(index) => write!(f, "[{index}]")?,
+		}
+	}
+	Ok(())
+}
@@ -0,0 +1,183 @@
+use std::{
+	borrow::Cow,
+	collections::{BTreeMap, HashMap},
+	marker::PhantomData,
+	mem,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Reports how much heap memory a value owns, and separately how much memory it only borrows, e.g. for
+/// sizing caches.
+///
+/// The owned bytes are what [`ShallowClone`][crate::ShallowClone] saves compared to [`Clone`], and the
+/// borrowed bytes are what [`MakeOwned`][crate::MakeOwned] has to copy. Everything reachable through a
+/// borrowed value counts as borrowed, including the memory that it owns itself. The inline size of the
+/// value is not included.
+pub trait HeapSize {
+	/// Adds the memory used by the value to `usage`
+	fn add_heap_size(&self, usage: &mut HeapUsage);
+
+	/// The memory used by the value
+	fn heap_size(&self) -> HeapUsage {
+		let mut usage = HeapUsage::default();
+		self.add_heap_size(&mut usage);
+		usage
+	}
+}
+
+/// Memory collected by [`HeapSize::heap_size`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HeapUsage {
+	/// Bytes of heap memory owned by the value
+	pub owned: usize,
+	/// Bytes referenced by borrowed values
+	pub borrowed: usize,
+}
+
+impl HeapUsage {
+	/// The owned and borrowed bytes together
+	pub fn total(&self) -> usize {
+		self.owned + self.borrowed
+	}
+	/// Records a borrowed value, together with all of the memory it uses
+	pub fn add_borrowed<T: HeapSize + ?Sized>(&mut self, value: &T) {
+		self.borrowed += mem::size_of_val(value) + value.heap_size().total();
+	}
+}
+
+impl<'a> HeapSize for Cow<'a, str> {
+	fn add_heap_size(&self, usage: &mut HeapUsage) {
+		match self {
+			Cow::Borrowed(bor) => usage.add_borrowed(*bor),
+			Cow::Owned(owned) => owned.add_heap_size(usage),
+		}
+	}
+}
+impl<'a, T: HeapSize + Clone> HeapSize for Cow<'a, [T]> {
+	fn add_heap_size(&self, usage: &mut HeapUsage) {
+		match self {
+			Cow::Borrowed(bor) => usage.add_borrowed(*bor),
+			Cow::Owned(owned) => owned.add_heap_size(usage),
+		}
+	}
+}
+impl<'a, T: HeapSize + Clone> HeapSize for Cow<'a, T> {
+	fn add_heap_size(&self, usage: &mut HeapUsage) {
+		match self {
+			Cow::Borrowed(bor) => usage.add_borrowed(*bor),
+			Cow::Owned(owned) => owned.add_heap_size(usage),
+		}
+	}
+}
+
+impl<T: HeapSize + ?Sized> HeapSize for &T {
+	fn add_heap_size(&self, usage: &mut HeapUsage) {
+		usage.add_borrowed(*self);
+	}
+}
+
+impl<T: ?Sized> HeapSize for PhantomData<T> {
+	fn add_heap_size(&self, _usage: &mut HeapUsage) {}
+}
+
+/// Implements [`HeapSize`] for types that don't own any heap memory, reporting nothing.
+///
+/// Useful for foreign types registered with [`impl_makeowned_basic!`][crate::impl_makeowned_basic].
+///
+/// ```
+/// # mod other_crate { pub struct Id(pub u64); }
+/// shallowclone::impl_no_heap_size! { other_crate::Id }
+/// ```
+#[macro_export]
+macro_rules! impl_no_heap_size {
+    ($( $x:ty ),* $(,)? ) => {
+        $(
+            impl $crate::HeapSize for $x {
+                fn add_heap_size(&self, _usage: &mut $crate::HeapUsage) {}
+            }
+        )*
+    };
+}
+
+// primitives, and `str` so that references to it can be measured
+impl_no_heap_size! { u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, f32, f64, bool, char, str }
+
+impl HeapSize for String {
+	fn add_heap_size(&self, usage: &mut HeapUsage) {
+		usage.owned += self.capacity();
+	}
+}
+
+impl<T: HeapSize> HeapSize for [T] {
+	fn add_heap_size(&self, usage: &mut HeapUsage) {
+		self.iter().for_each(|x| x.add_heap_size(usage));
+	}
+}
+
+impl<const N: usize, T: HeapSize> HeapSize for [T; N] {
+	fn add_heap_size(&self, usage: &mut HeapUsage) {
+		self.as_slice().add_heap_size(usage);
+	}
+}
+
+impl<T: HeapSize> HeapSize for Option<T> {
+	fn add_heap_size(&self, usage: &mut HeapUsage) {
+		if let Some(x) = self {
+			x.add_heap_size(usage);
+		}
+	}
+}
+
+impl<T: HeapSize> HeapSize for Vec<T> {
+	fn add_heap_size(&self, usage: &mut HeapUsage) {
+		usage.owned += self.capacity() * mem::size_of::<T>();
+		self.as_slice().add_heap_size(usage);
+	}
+}
+
+impl<T: HeapSize + ?Sized> HeapSize for Box<T> {
+	fn add_heap_size(&self, usage: &mut HeapUsage) {
+		usage.owned += mem::size_of_val(&**self);
+		(**self).add_heap_size(usage);
+	}
+}
+
+// the layout of the maps isn't public, so their own allocations are estimated from the entries and,
+// for the hash maps, one control byte per bucket
+
+impl<K: HeapSize, V: HeapSize, S> HeapSize for HashMap<K, V, S> {
+	fn add_heap_size(&self, usage: &mut HeapUsage) {
+		usage.owned += self.capacity() * (mem::size_of::<(K, V)>() + 1);
+		add_entries(self, usage);
+	}
+}
+
+impl<K: HeapSize, V: HeapSize> HeapSize for BTreeMap<K, V> {
+	fn add_heap_size(&self, usage: &mut HeapUsage) {
+		usage.owned += self.len() * mem::size_of::<(K, V)>();
+		add_entries(self, usage);
+	}
+}
+
+#[cfg(feature = "indexmap")]
+impl<K: HeapSize, V: HeapSize, S> HeapSize for indexmap::IndexMap<K, V, S> {
+	fn add_heap_size(&self, usage: &mut HeapUsage) {
+		// the entries are stored with their hashes, and the table of indices points to them
+		let entry = mem::size_of::<(usize, K, V)>();
+		let index = mem::size_of::<usize>() + 1;
+		usage.owned += self.capacity() * (entry + index);
+		add_entries(self, usage);
+	}
+}
+
+fn add_entries<'a, K: HeapSize + 'a, V: HeapSize + 'a>(
+	entries: impl IntoIterator<Item = (&'a K, &'a V)>,
+	usage: &mut HeapUsage,
+) {
+	for (k, v) in entries {
+		k.add_heap_size(usage);
+		v.add_heap_size(usage);
+	}
+}
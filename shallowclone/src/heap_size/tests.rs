@@ -0,0 +1,105 @@
+use crate::{
+	tests::fixtures::{Handle, Header},
+	CoCow, CoCowSlice, HeapSize, HeapUsage,
+};
+use std::{borrow::Cow, mem::size_of};
+
+#[derive(HeapSize)]
+enum Body<'a, T> {
+	Parts(CoCowSlice<'a, T>),
+	Header(Box<CoCow<'a, Header<'a>>>),
+	Skipped(#[makeowned(skip)] Vec<u8>),
+	Cached(#[makeowned(default)] Option<String>),
+	Custom(#[makeowned(with = "ToOwned::to_owned")] &'a str),
+}
+
+#[test]
+fn heap_size() {
+	let mut values = Vec::with_capacity(4);
+	values.push(Cow::Owned(String::with_capacity(6)));
+	let header = Header {
+		name: Cow::Owned(String::with_capacity(10)),
+		values: CoCowSlice::Owned(values),
+	};
+	let header_heap = 10 + 4 * size_of::<Cow<str>>() + 6;
+	assert_eq!(
+		header.heap_size(),
+		HeapUsage {
+			owned: header_heap,
+			borrowed: 0
+		}
+	);
+
+	// everything reachable through a borrow is borrowed
+	let body: Body<u8> = Body::Header(Box::new(CoCow::Borrowed(&header)));
+	let boxed = size_of::<CoCow<Header>>();
+	assert_eq!(
+		body.heap_size(),
+		HeapUsage {
+			owned: boxed,
+			borrowed: size_of::<Header>() + header_heap,
+		}
+	);
+
+	// cloning doesn't keep the spare capacity
+	let cloned = header.clone();
+	let cloned_heap = cloned.heap_size().owned;
+	assert_eq!(cloned_heap, size_of::<Cow<str>>());
+	let owned: Body<u8> = Body::Header(Box::new(CoCow::Owned(cloned)));
+	assert_eq!(
+		owned.heap_size(),
+		HeapUsage {
+			owned: boxed + cloned_heap,
+			borrowed: 0,
+		}
+	);
+
+	let parts = [Cow::Borrowed("abc"), Cow::Owned(String::from("de"))];
+	let body = Body::Parts(CoCowSlice::Borrowed(&parts));
+	let usage = body.heap_size();
+	assert_eq!(usage.owned, 0);
+	assert_eq!(usage.borrowed, size_of::<[Cow<str>; 2]>() + 3 + 2);
+	assert_eq!(usage.total(), usage.borrowed);
+
+	// fields are measured however they're converted
+	let skipped: Body<u8> = Body::Skipped(vec![0; 100]);
+	assert_eq!(
+		skipped.heap_size(),
+		HeapUsage {
+			owned: 100,
+			borrowed: 0
+		}
+	);
+	let cached: Body<u8> = Body::Cached(Some(String::with_capacity(8)));
+	assert_eq!(cached.heap_size().owned, 8);
+	let custom: Body<u8> = Body::Custom("custom");
+	assert_eq!(
+		custom.heap_size(),
+		HeapUsage {
+			owned: 0,
+			borrowed: 6
+		}
+	);
+}
+
+// types registered with `impl_no_heap_size!` can be measured too
+#[derive(HeapSize)]
+struct WithHandle<'a> {
+	name: Cow<'a, str>,
+	handle: Handle,
+}
+
+#[test]
+fn registered_leaf() {
+	let value = WithHandle {
+		name: Cow::Borrowed("name"),
+		handle: Handle::open(),
+	};
+	assert_eq!(
+		value.heap_size(),
+		HeapUsage {
+			owned: 0,
+			borrowed: 4
+		}
+	);
+}
@@ -18,47 +18,844 @@
 //! things like [`Cow<'a, str>`][std::borrow::Cow] or [`Cow<'a, [u8]>`][std::borrow::Cow], but if your
 //! `T` contains references, the standard [`Cow`][std::borrow::Cow] will not let you subtype them
 //! after shallow cloning, and you will end up with 2 different lifetimes.
-//! [`CoCow`] and [`CoCowSlice`] solve this problem.
+//! [`CoCow`] and [`CoCowSlice`] solve this problem. You can define your own specialised covariant
+//! copy-on-write types with [`define_cocow!`].
 
+// the derive macros refer to `::shallowclone`, this makes them work inside of this crate too
+extern crate self as shallowclone;
+
+mod anchored;
+mod borrow_state;
 mod cows;
+mod define_cocow;
+mod field_path;
+mod heap_size;
 mod makeowned;
-mod shallowclone;
+mod makeowned_for;
+#[cfg(feature = "bumpalo")]
+mod makeowned_in;
+mod own_in_place;
+mod rebase;
+mod shallow_clone;
+#[cfg(test)]
+mod tests;
+mod try_make_owned;
+mod visit_cows;
 
+pub use anchored::{Anchored, BorrowFamily, StableBuffer};
+pub use borrow_state::{points_into, BorrowState, BorrowStats};
 pub use cows::{CoCow, CoCowSlice};
-pub use makeowned::MakeOwned;
-pub use shallowclone::ShallowClone;
+pub use field_path::{FieldPath, PathSegment};
+pub use heap_size::{HeapSize, HeapUsage};
+pub use makeowned::{MakeOwned, ToOwnedStatic};
+pub use makeowned_for::MakeOwnedFor;
+#[cfg(feature = "bumpalo")]
+pub use makeowned_in::MakeOwnedIn;
+pub use own_in_place::OwnInPlace;
+pub use rebase::Rebase;
+pub use shallow_clone::{ShallowClone, ShallowCloneGat};
+pub use try_make_owned::{Budget, Limit, OwnError, TryMakeOwned};
+pub use visit_cows::{CowVisitor, CowVisitorMut, VisitCows};
+
+// used by the macros, not public API
+#[doc(hidden)]
+pub mod __private {
+	pub use crate::define_cocow::CoCowTypes;
+	#[cfg(feature = "bumpalo")]
+	pub use bumpalo::Bump;
+	#[cfg(feature = "serde")]
+	pub use serde;
+	pub use shallowclone_derive::__fresh_lifetimes as fresh_lifetimes;
+}
 
+/// Implements both [`ShallowClone`] and [`ToOwnedStatic`] (by cloning) and [`MakeOwned`] (by returning the
+/// value as it is) for types that are always owned.
+///
+/// This is the same as calling [`impl_shallowclone_by_clone!`], [`impl_makeowned_basic!`] and
+/// [`impl_to_owned_static_by_clone!`].
+///
+/// ```
+/// # mod other_crate { #[derive(Clone)] pub struct Id(pub u64); }
+/// shallowclone::impl_by_clone! { other_crate::Id }
+/// ```
+#[macro_export]
+macro_rules! impl_by_clone {
+    ($( $x:ty ),* $(,)? ) => {
+        $crate::impl_shallowclone_by_clone! { $($x),* }
+        $crate::impl_makeowned_basic! { $($x),* }
+        $crate::impl_to_owned_static_by_clone! { $($x),* }
+    };
+}
+
+/// Asserts at compile time that [`MakeOwned::Owned`] is a subtype of the type, as required by the trait.
+///
+/// It fails to compile if the [`Owned`][MakeOwned::Owned] type can't be obtained from the type just by
+/// making its lifetimes longer. Elided lifetimes (`'_`) are allowed, and generics can be declared in front
+/// of the type, with an optional where clause after it. Type parameters must be bound so that their own
+/// [`Owned`][MakeOwned::Owned] type is the same, since the subtype relation can't be expressed for them.
+///
+/// [`MakeOwned`] derives emit this assertion automatically, unless `#[makeowned(owned = "...")]` is used.
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # use shallowclone::{assert_makeowned_subtype, CoCow, MakeOwned};
+/// assert_makeowned_subtype!(Cow<'_, str>);
+/// assert_makeowned_subtype!(<T: MakeOwned<Owned = T> + Clone> CoCow<'_, T>);
+/// assert_makeowned_subtype!(<'a, T> Vec<Cow<'a, [T]>> where T: MakeOwned<Owned = T> + Clone);
+/// ```
+///
+/// The generated code refers to this crate as `::shallowclone`. If you re-export it from another crate,
+/// the path to the re-export can be given first with `crate = "...";`.
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # mod facade { pub mod shallowclone { pub use ::shallowclone::*; } }
+/// facade::shallowclone::assert_makeowned_subtype!(crate = "facade::shallowclone"; Cow<'_, str>);
+/// ```
+///
+/// ```compile_fail
+/// # use shallowclone::{assert_makeowned_subtype, MakeOwned};
+/// #[derive(Clone)]
+/// struct Name<'a>(&'a str);
+///
+/// impl MakeOwned for Name<'_> {
+///     type Owned = String;
+///
+///     fn make_owned(self) -> String {
+///         self.0.to_owned()
+///     }
+/// }
+///
+/// assert_makeowned_subtype!(Name<'_>);
+/// ```
+pub use shallowclone_derive::assert_makeowned_subtype;
+/// Rewrites the references in the fields of a struct or enum into copy-on-write types, and derives
+/// [`ShallowClone`] and [`MakeOwned`] for it. This makes the borrowed output of zero-copy parsers ownable.
+///
+///  - `&'a str` (and other unsized types like `Path`, `OsStr` and `CStr`) becomes [`Cow<'a, str>`][std::borrow::Cow],
+///  - `&'a [T]` becomes [`CoCowSlice<'a, T>`][CoCowSlice],
+///  - `&'a T` becomes [`CoCow<'a, T>`][CoCow].
+///
+/// References nested in other types, like `Option<&'a str>`, are rewritten too, except in a
+/// [`PhantomData`][std::marker::PhantomData]. Fields with `#[cowify(skip)]` are left as they are.
+///
+/// The path to this crate can be specified with `#[cowify(crate = "...")]`.
+///
+/// ```
+/// # use shallowclone::{cowify, MakeOwned};
+/// #[cowify]
+/// #[derive(Clone)]
+/// struct Token<'a> {
+///     text: &'a str,
+///     bytes: Option<&'a [u8]>,
+///     // already 'static, so it doesn't need to be converted
+///     #[cowify(skip)]
+///     #[shallowclone(clone)]
+///     #[makeowned(skip)]
+///     kind: &'static str,
+/// }
+///
+/// let text = String::from("text");
+/// let token = Token { text: text.as_str().into(), bytes: None, kind: "word" };
+/// let owned: Token<'static> = token.make_owned();
+/// ```
+pub use shallowclone_derive::cowify;
+/// Converts the arguments of a function with [`MakeOwned::make_owned`] at entry, so that they can be moved
+/// into `'static` closures or futures.
+///
+/// For an `async fn` the arguments are converted when it's called, and it returns a `'static` future,
+/// so it can be spawned even if the arguments borrow from the caller. Arguments with `#[borrowed]` are
+/// left as they are, and so is `self`. Note that an `async fn` with `self` or `impl Trait` arguments
+/// follows the capture rules of your edition, since the returned future can't specify what it captures.
+///
+/// The path to this crate can be specified with `#[owned_args(crate = "...")]`.
+///
+/// ```
+/// # use std::{borrow::Cow, future::Future, sync::mpsc::Sender};
+/// # use shallowclone::{owned_args, MakeOwned};
+/// #[derive(MakeOwned, Clone)]
+/// struct Request<'a> {
+///     path: Cow<'a, str>,
+/// }
+///
+/// #[owned_args]
+/// async fn handle(request: Request<'_>, #[borrowed] responses: Sender<String>) {
+///     // `request` is a `Request<'static>` here
+///     responses.send(request.path.into_owned()).unwrap();
+/// }
+///
+/// #[owned_args]
+/// fn handle_in_thread(request: Request<'_>) -> std::thread::JoinHandle<()> {
+///     std::thread::spawn(move || println!("{}", request.path))
+/// }
+///
+/// fn spawn(_future: impl Future<Output = ()> + 'static) {}
+///
+/// let path = String::from("/index.html");
+/// let (sender, _receiver) = std::sync::mpsc::channel();
+/// spawn(handle(Request { path: Cow::Borrowed(&path) }, sender));
+/// handle_in_thread(Request { path: Cow::Borrowed(&path) }).join().unwrap();
+/// ```
+pub use shallowclone_derive::owned_args;
+/// Automatically derives the [`BorrowState`] trait
+///
+/// It reads the same `#[makeowned(...)]` attributes as `#[derive(MakeOwned)]`. Fields are inspected
+/// however they're converted, since a field converted `with` a function can still borrow from a buffer,
+/// unless they have the `no_inspect` attribute. Fields whose types depend on the type parameters are
+/// required to implement [`BorrowState`]. The `remote` attribute is not supported.
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # use shallowclone::{BorrowState, CoCowSlice};
+/// #[derive(BorrowState)]
+/// struct Frame<'a> {
+///     header: Cow<'a, str>,
+///     payload: CoCowSlice<'a, u8>,
+/// }
+///
+/// let buffer = vec![0u8; 64];
+/// let frame = Frame {
+///     header: Cow::Owned(String::from("header")),
+///     payload: CoCowSlice::Borrowed(&buffer[16..32]),
+/// };
+/// let stats = frame.borrow_stats();
+/// assert_eq!((stats.borrowed, stats.owned, stats.borrowed_bytes), (1, 1, 16));
+/// assert!(frame.borrows_from(&buffer));
+/// assert!(!frame.borrows_from(&buffer[32..]));
+/// assert!(!frame.is_fully_owned());
+/// ```
+pub use shallowclone_derive::BorrowState;
+/// Derives [`ShallowClone`], [`MakeOwned`] and [`Clone`] together, plus some common helpers:
+///
+///  - `type FooOwned = Foo<'static>;`, the [`Owned`][MakeOwned::Owned] type of `Foo`,
+///  - `fn into_static(self) -> FooOwned`, calling [`MakeOwned::make_owned`],
+///  - `fn reborrow(&self) -> Foo<'_>`, calling [`ShallowClone::shallow_clone`],
+///  - `impl<'a> From<&'a Foo<'_>> for Foo<'a>`, also calling [`ShallowClone::shallow_clone`]. This one
+///    is only generated if none of the type parameters are replaced in the target type.
+///
+/// The trait impls are exactly the same as the ones generated by the separate derives,
+/// and both `#[shallowclone(...)]` and `#[makeowned(...)]` attributes can be used.
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # use shallowclone::CowFamily;
+/// #[derive(CowFamily)]
+/// struct Foo<'a> {
+///     name: Cow<'a, str>,
+/// }
+///
+/// fn store(foo: FooOwned) {}
+///
+/// let name = String::from("name");
+/// let foo = Foo { name: Cow::Borrowed(&name) };
+/// let reborrowed: Foo = Foo::from(&foo);
+/// store(reborrowed.reborrow().into_static());
+/// ```
+pub use shallowclone_derive::CowFamily;
+/// Automatically derives the [`HeapSize`] trait
+///
+/// It reads the same `#[makeowned(...)]` attributes as `#[derive(MakeOwned)]`. Every field is measured,
+/// including the ones with `skip`, `with` or `default`, since they own their heap memory until the value
+/// is converted, except for fields with `no_inspect`. Fields whose types depend on the type parameters
+/// are required to implement [`HeapSize`]. The `remote` attribute is not supported.
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # use shallowclone::{CoCowSlice, HeapSize, HeapUsage};
+/// #[derive(HeapSize)]
+/// struct Entry<'a> {
+///     key: Cow<'a, str>,
+///     value: CoCowSlice<'a, u32>,
+/// }
+///
+/// let values = [1, 2, 3, 4];
+/// let entry = Entry {
+///     key: Cow::Owned(String::with_capacity(16)),
+///     value: CoCowSlice::Borrowed(&values),
+/// };
+/// assert_eq!(entry.heap_size(), HeapUsage { owned: 16, borrowed: 16 });
+/// ```
+pub use shallowclone_derive::HeapSize;
 /// Automatically derives the [`MakeOwned`] trait
 ///
-/// ## `#[makeowned(skip)]` attribute
+/// ## Generics
 ///
-/// You can use this attribute on generics (type or lifetime) to not place [`MakeOwned`] bounds on them,
-/// if your type requires so.
+/// Bounds are inferred from the types of the fields. A type parameter is only required to implement
+/// [`MakeOwned`] (and replaced with `<T as MakeOwned>::Owned` in the [`Owned`][MakeOwned::Owned] type)
+/// if some field converts it, i.e. `T` or `Vec<T>`. Parameters which are only used in a
+/// [`PhantomData`][std::marker::PhantomData], behind a reference or in fields with attributes are left as
+/// they are. Associated types like `T::Item` or `<T as Iterator>::Item` are bound directly.
 ///
 /// ```
 /// # use std::marker::PhantomData;
 /// # use shallowclone::MakeOwned;
 /// #[derive(MakeOwned, Clone)]
-/// struct MyStruct<#[makeowned(skip)] T> {
-///     // No need to place the bounds on T, since it's inside the PhantomData
-///     phantom: PhantomData<T>,
+/// struct MyStruct<T: Iterator, U>
+/// where
+///     T::Item: Clone,
+/// {
+///     // requires `<T as Iterator>::Item: MakeOwned<Owned = <T as Iterator>::Item>`
+///     item: <T as Iterator>::Item,
+///     // U doesn't need to implement MakeOwned
+///     phantom: PhantomData<U>,
+/// }
+/// ```
+///
+/// ## `#[makeowned(skip)]` attribute
+///
+/// You can use this attribute on type parameters to leave them as they are in the
+/// [`Owned`][MakeOwned::Owned] type, even if a field converts them. Lifetimes can't be skipped, since the
+/// owned type must be `'static`, but see the `keep` attribute below.
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # use shallowclone::MakeOwned;
+/// #[derive(MakeOwned, Clone)]
+/// struct MyStruct<'a, #[makeowned(skip)] T> {
+///     // T is not replaced with <T as MakeOwned>::Owned, so the field must be left as it is
+///     #[makeowned(skip)]
+///     id: T,
+///     name: Cow<'a, str>,
+/// }
+/// ```
+///
+/// ## `#[makeowned(keep = 'a)]` attribute
+///
+/// Use this attribute on the type to keep some of its lifetimes, for example if they point into
+/// long-lived data that doesn't need to be copied. Instead of [`MakeOwned`] this implements
+/// [`MakeOwnedFor<'keep>`][MakeOwnedFor] for any `'keep` that the kept lifetimes outlive. Fields that
+/// only borrow for the kept lifetimes are moved as they are, fields that borrow for both are converted
+/// with [`MakeOwnedFor`], and the rest with [`MakeOwned`].
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # use shallowclone::{MakeOwned, MakeOwnedFor};
+/// # #[derive(Clone)] struct Config;
+/// #[derive(MakeOwned, Clone)]
+/// #[makeowned(keep = 'cfg)]
+/// struct Packet<'cfg, 'buf> {
+///     config: &'cfg Config,
+///     payload: Cow<'buf, [u8]>,
+/// }
+///
+/// fn detach<'cfg>(packet: Packet<'cfg, '_>) -> Packet<'cfg, 'static> {
+///     packet.make_owned_for()
+/// }
+/// ```
+///
+/// ## Where clauses
+///
+/// All bounds on the generics, both inline and in the where clause, are duplicated for the
+/// [`Owned`][MakeOwned::Owned] type, replacing `T` with `<T as MakeOwned>::Owned` and lifetimes with `'static`.
+///
+/// ## `#[makeowned(bound = "...")]` attribute
+///
+/// If the generated bounds are not right for your type, you can use this attribute on the type to specify them
+/// manually. It replaces the bounds that would be generated for the generics.
+///
+/// ```
+/// # use shallowclone::MakeOwned;
+/// #[derive(MakeOwned, Clone)]
+/// #[makeowned(bound = "T: MakeOwned<Owned = T> + 'static")]
+/// struct MyStruct<T> {
+///     field: T,
+/// }
+/// ```
+///
+/// ## Field attributes
+///
+/// By default every field is converted using [`MakeOwned`], but you can change that with these attributes:
+///
+///  - `#[makeowned(skip)]` moves the field as it is. The type of the field must not depend on any of the
+///    changed generics.
+///  - `#[makeowned(with = "path::to::function")]` uses a custom function `fn(Field) -> OwnedField`.
+///  - `#[makeowned(default)]` discards the value and uses [`Default`] instead. Useful for caches.
+///
+/// `#[makeowned(no_inspect)]` doesn't change the conversion, but leaves the field out of the derives that
+/// inspect a value, [`BorrowState`], [`HeapSize`] and [`VisitCows`], for types that don't implement them.
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # use shallowclone::MakeOwned;
+/// #[derive(MakeOwned, Clone)]
+/// struct MyStruct<'a> {
+///     #[makeowned(skip)]
+///     id: std::time::Duration,
+///     #[makeowned(with = "own_str")]
+///     name: Cow<'a, str>,
+///     #[makeowned(default)]
+///     cache: Option<Cow<'a, str>>,
+/// }
+///
+/// fn own_str(s: Cow<str>) -> Cow<'static, str> {
+///     Cow::Owned(s.into_owned())
+/// }
+/// ```
+///
+/// ## `#[makeowned(to_owned_static)]` attribute
+///
+/// Implements [`ToOwnedStatic`] as well, converting every field from a reference, and cloning the fields
+/// with `skip` or `with` before they are converted. This requires the fields to implement
+/// [`ToOwnedStatic`] or [`Clone`] respectively. Not supported for packed structs, or together with `keep`
+/// or `remote`.
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # use shallowclone::{MakeOwned, ToOwnedStatic};
+/// #[derive(MakeOwned, Clone)]
+/// #[makeowned(to_owned_static)]
+/// struct MyStruct<'a> {
+///     name: Cow<'a, str>,
+///     items: Cow<'a, [String]>,
+/// }
+///
+/// fn detach(value: &MyStruct) -> MyStruct<'static> {
+///     value.to_owned_static()
+/// }
+/// ```
+///
+/// ## `#[makeowned(owned = "...")]` attribute
+///
+/// By default the [`Owned`][MakeOwned::Owned] type is the same type with changed generics, but you can
+/// use this attribute to convert to a different struct or enum instead. Fields (and variants) are matched
+/// by name, and each field is converted using its own [`MakeOwned`] implementation (or the field attributes).
+///
+/// Note that this breaks the contract of [`MakeOwned::Owned`] being a subtype of `Self`, so it's not checked
+/// with [`assert_makeowned_subtype!`] like it is otherwise.
+///
+/// ```
+/// # use shallowclone::MakeOwned;
+/// #[derive(MakeOwned, Clone)]
+/// #[makeowned(owned = "MessageOwned")]
+/// struct MessageRef<'a> {
+///     #[makeowned(with = "ToOwned::to_owned")]
+///     text: &'a str,
+///     id: u32,
+/// }
+///
+/// #[derive(Clone)]
+/// struct MessageOwned {
+///     text: String,
+///     id: u32,
+/// }
+/// ```
+///
+/// ## Unions
+///
+/// Only `Copy` unions without lifetimes are supported, and they are returned as they are.
+///
+/// ## `#[makeowned(remote = "...")]` attribute
+///
+/// Like serde's remote derive, this lets you derive [`MakeOwned`] for a type from another crate by copying
+/// its definition. Instead of implementing the trait, an associated function
+/// `fn make_owned(value: Remote) -> RemoteOwned` is generated on the copy, which can be used with the
+/// `with` field attribute.
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # use shallowclone::MakeOwned;
+/// # mod other_crate { #[derive(Clone)] pub struct Name<'a> { pub first: std::borrow::Cow<'a, str> } }
+/// #[derive(MakeOwned)]
+/// #[makeowned(remote = "other_crate::Name")]
+/// struct NameDef<'a> {
+///     first: Cow<'a, str>,
+/// }
+///
+/// #[derive(MakeOwned, Clone)]
+/// struct User<'a> {
+///     #[makeowned(with = "NameDef::make_owned")]
+///     name: other_crate::Name<'a>,
+/// }
+/// ```
+///
+/// ## `#[makeowned(crate = "...")]` attribute
+///
+/// The generated code refers to this crate as `::shallowclone`. If you re-export it from another crate,
+/// you can use this attribute to specify the path to the re-export.
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # mod facade { pub mod shallowclone { pub use ::shallowclone::*; } }
+/// #[derive(facade::shallowclone::MakeOwned, Clone)]
+/// #[makeowned(crate = "facade::shallowclone")]
+/// struct MyStruct<'a> {
+///     field: Cow<'a, str>,
 /// }
 /// ```
 pub use shallowclone_derive::MakeOwned;
+/// Automatically derives the [`MakeOwnedIn`] trait
+///
+/// It reads the same `#[makeowned(...)]` attributes as `#[derive(MakeOwned)]`, except for `keep`, `remote`
+/// and `owned`, which are not supported. The lifetimes are changed to `'arena` and type parameters are
+/// converted like with [`MakeOwned`].
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # use shallowclone::MakeOwnedIn;
+/// # use bumpalo::Bump;
+/// #[derive(MakeOwnedIn)]
+/// struct Row<'a> {
+///     key: Cow<'a, str>,
+///     values: Vec<Cow<'a, str>>,
+/// }
+///
+/// fn detach<'arena>(row: Row<'_>, arena: &'arena Bump) -> Row<'arena> {
+///     row.make_owned_in(arena)
+/// }
+///
+/// let arena = Bump::new();
+/// let buffer = String::from("key");
+/// let row = detach(Row { key: Cow::Borrowed(&buffer), values: Vec::new() }, &arena);
+/// drop(buffer);
+/// assert_eq!(row.key, "key");
+/// ```
+#[cfg(feature = "bumpalo")]
+pub use shallowclone_derive::MakeOwnedIn;
+/// Automatically derives the [`OwnInPlace`] trait
+///
+/// It reads the same `#[makeowned(...)]` attributes as `#[derive(MakeOwned)]`. Fields with the `skip`,
+/// `with` or `default` attribute, and fields which only borrow for lifetimes kept with `keep`, are left as
+/// they are. Fields whose types depend on the type parameters are required to implement [`OwnInPlace`].
+/// The `remote` attribute is not supported.
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # use shallowclone::{CoCowSlice, OwnInPlace};
+/// #[derive(OwnInPlace)]
+/// struct Frame<'a> {
+///     header: Cow<'a, str>,
+///     chunks: CoCowSlice<'a, Cow<'a, [u8]>>,
+/// }
+///
+/// let buffer = vec![1, 2, 3];
+/// let chunks = [Cow::Borrowed(&buffer[..])];
+/// let mut frame = Frame {
+///     header: Cow::Borrowed("header"),
+///     chunks: CoCowSlice::Borrowed(&chunks),
+/// };
+/// frame.own_in_place();
+/// assert!(matches!(frame.header, Cow::Owned(_)));
+/// assert!(frame.chunks.is_owned());
+/// assert!(matches!(&frame.chunks[0], Cow::Owned(x) if x == &[1, 2, 3]));
+/// ```
+pub use shallowclone_derive::OwnInPlace;
+/// Automatically derives the [`Rebase`] trait
+///
+/// It reads the same `#[makeowned(...)]` attributes as `#[derive(MakeOwned)]`, except for `keep`, `remote`
+/// and `owned`, which are not supported. The lifetimes are changed to `'new` and type parameters are
+/// converted like with [`MakeOwned`].
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # use shallowclone::{CoCowSlice, Rebase};
+/// #[derive(Rebase)]
+/// struct Frame<'a> {
+///     kind: Cow<'a, str>,
+///     payload: CoCowSlice<'a, u8>,
+/// }
+///
+/// let buffer = b"ping payload".to_vec();
+/// let frame = Frame {
+///     kind: Cow::Borrowed(std::str::from_utf8(&buffer[..4]).unwrap()),
+///     payload: CoCowSlice::Borrowed(&buffer[5..]),
+/// };
+///
+/// let page = buffer.clone();
+/// let frame = frame.rebase(&buffer, &page);
+/// drop(buffer);
+/// assert_eq!(frame.kind, "ping");
+/// assert!(matches!(frame.payload, CoCowSlice::Borrowed(b"payload")));
+/// ```
+pub use shallowclone_derive::Rebase;
 /// Automatically derives the [`ShallowClone`] trait
 ///
+/// ## Generics
+///
+/// Bounds are inferred from the types of the fields. A type parameter is only required to implement
+/// [`ShallowClone`] (and replaced with `<T as ShallowClone<'shallowclone>>::Target` in the
+/// [`Target`][ShallowClone::Target] type) if some field converts it, i.e. `T` or `Vec<T>`. Parameters
+/// which are only used in a [`Cow`][std::borrow::Cow], [`CoCow`], [`CoCowSlice`],
+/// [`PhantomData`][std::marker::PhantomData], behind a reference or in fields with attributes are left as
+/// they are. Associated types like `T::Item` or `<T as Iterator>::Item` are bound directly.
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # use shallowclone::ShallowClone;
+/// #[derive(ShallowClone)]
+/// struct MyStruct<'a, T: Clone> {
+///     // T doesn't need to implement ShallowClone, the slice is just borrowed
+///     items: Cow<'a, [T]>,
+/// }
+/// ```
+///
 /// ## `#[shallowclone(skip)]` attribute
 ///
-/// You can use this attribute on generics (type or lifetime) to not place [`ShallowClone`] bounds on them,
-/// if your type requires so.
+/// You can use this attribute on generics (type or lifetime) to leave them as they are in the
+/// [`Target`][ShallowClone::Target] type, even if a field converts them.
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # use shallowclone::ShallowClone;
+/// #[derive(ShallowClone)]
+/// struct MyStruct<#[shallowclone(skip)] 'a, 'b> {
+///     // 'a is not replaced with 'shallowclone, so the field must be left as it is
+///     #[shallowclone(clone)]
+///     id: &'a str,
+///     name: Cow<'b, str>,
+/// }
+/// ```
+///
+/// ## Where clauses
+///
+/// All bounds on the generics, both inline and in the where clause, are duplicated for the
+/// [`Target`][ShallowClone::Target] type, replacing `T` with `<T as ShallowClone<'shallowclone>>::Target` and
+/// lifetimes with `'shallowclone`.
+///
+/// ## `#[shallowclone(bound = "...")]` attribute
+///
+/// If the generated bounds are not right for your type, you can use this attribute on the type to specify them
+/// manually. It replaces the bounds that would be generated for the generics. The lifetime of the trait
+/// can be referred to as `'shallowclone`.
 ///
 /// ```
-/// # use std::marker::PhantomData;
 /// # use shallowclone::ShallowClone;
 /// #[derive(ShallowClone)]
-/// struct MyStruct<#[shallowclone(skip)] T> {
-///     // No need to place the bounds on T, since it's inside the PhantomData
-///     phantom: PhantomData<T>,
+/// #[shallowclone(bound = "T: ShallowClone<'shallowclone, Target = T>")]
+/// struct MyStruct<T> {
+///     field: T,
+/// }
+/// ```
+///
+/// ## Field attributes
+///
+/// By default every field is converted using [`ShallowClone`], but you can change that with these attributes:
+///
+///  - `#[shallowclone(clone)]` uses [`Clone`] instead. The type of the field must not depend on any of the
+///    changed generics.
+///  - `#[shallowclone(with = "path::to::function")]` uses a custom function `fn(&'shallowclone Field) -> TargetField`.
+///  - `#[shallowclone(default)]` discards the value and uses [`Default`] instead.
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # use shallowclone::ShallowClone;
+/// #[derive(ShallowClone)]
+/// struct MyStruct<'a> {
+///     #[shallowclone(clone)]
+///     id: std::time::Duration,
+///     #[shallowclone(with = "reborrow")]
+///     name: &'a str,
+///     #[shallowclone(default)]
+///     cache: Option<Cow<'a, str>>,
+/// }
+///
+/// fn reborrow<'a>(s: &'a &str) -> &'a str {
+///     s
+/// }
+/// ```
+///
+/// ## `#[shallowclone(target = "...")]` attribute
+///
+/// By default the [`Target`][ShallowClone::Target] type is the same type with changed generics, but you can
+/// use this attribute to convert to a different struct or enum instead. Fields (and variants) are matched
+/// by name, and each field is converted using its own [`ShallowClone`] implementation (or the field attributes).
+///
+/// ```
+/// # use shallowclone::ShallowClone;
+/// #[derive(ShallowClone)]
+/// #[shallowclone(target = "MessageRef<'shallowclone>")]
+/// struct MessageOwned {
+///     #[shallowclone(with = "String::as_str")]
+///     text: String,
+///     id: u32,
+/// }
+///
+/// struct MessageRef<'a> {
+///     text: &'a str,
+///     id: u32,
+/// }
+/// ```
+///
+/// ## Unions and packed structs
+///
+/// Only `Copy` unions are supported, and they are simply copied. Fields of `#[repr(packed)]` structs
+/// can't be borrowed, so they are copied instead of being shallow cloned, and must be `Copy`.
+///
+/// ```
+/// # use shallowclone::ShallowClone;
+/// #[derive(ShallowClone)]
+/// #[repr(packed)]
+/// struct Header<'a> {
+///     id: u8,
+///     name: &'a str,
+/// }
+/// ```
+///
+/// ## `#[shallowclone(remote = "...")]` attribute
+///
+/// Like serde's remote derive, this lets you derive [`ShallowClone`] for a type from another crate by
+/// copying its definition. Instead of implementing the trait, an associated function
+/// `fn shallow_clone(value: &Remote) -> RemoteTarget` is generated on the copy, which can be used with the
+/// `with` field attribute.
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # use shallowclone::ShallowClone;
+/// # mod other_crate { pub struct Name<'a> { pub first: std::borrow::Cow<'a, str> } }
+/// #[derive(ShallowClone)]
+/// #[shallowclone(remote = "other_crate::Name")]
+/// struct NameDef<'a> {
+///     first: Cow<'a, str>,
+/// }
+///
+/// #[derive(ShallowClone)]
+/// struct User<'a> {
+///     #[shallowclone(with = "NameDef::shallow_clone")]
+///     name: other_crate::Name<'a>,
+/// }
+/// ```
+///
+/// ## `#[shallowclone(crate = "...")]` attribute
+///
+/// The generated code refers to this crate as `::shallowclone`. If you re-export it from another crate,
+/// you can use this attribute to specify the path to the re-export.
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # mod facade { pub mod shallowclone { pub use ::shallowclone::*; } }
+/// #[derive(facade::shallowclone::ShallowClone)]
+/// #[shallowclone(crate = "facade::shallowclone")]
+/// struct MyStruct<'a> {
+///     field: Cow<'a, str>,
+/// }
+/// ```
+///
+/// ## `#[shallowclone(gat)]` attribute
+///
+/// Implements [`ShallowCloneGat`] as well, and [`ShallowClone`] for every lifetime through it. Fields and
+/// converted type parameters must implement [`ShallowCloneGat`]. This doesn't work if the bounds would
+/// depend on the lifetime, like trait bounds on converted type parameters, or parameters which are both
+/// converted and left as they are.
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # use shallowclone::{ShallowClone, ShallowCloneGat};
+/// #[derive(ShallowClone)]
+/// #[shallowclone(gat)]
+/// struct MyStruct<'a, T> {
+///     name: Cow<'a, str>,
+///     value: Option<T>,
+/// }
+///
+/// fn first<T: ShallowCloneGat>(items: &[T]) -> Option<T::Target<'_>> {
+///     items.first().map(ShallowCloneGat::shallow_clone_gat)
 /// }
 /// ```
 pub use shallowclone_derive::ShallowClone;
+/// Generates a borrowed view of a struct or enum, for cheap read-only snapshots of types that store their
+/// data in `String`s and `Vec`s instead of [`Cow`][std::borrow::Cow]s.
+///
+/// For `Foo`, this generates a `FooView<'view>` type with the same fields, but borrowed:
+///
+///  - `String` becomes `&'view str`,
+///  - `Vec<T>` becomes `&'view [T]`,
+///  - `Box<T>` becomes `&'view T`,
+///  - [`Cow<'a, T>`][std::borrow::Cow] becomes `Cow<'view, T>`, always `Borrowed`,
+///  - `Option<T>` becomes an `Option` of the view of `T`,
+///  - references and primitives are copied,
+///  - everything else `T` becomes `&'view T`, unless the field has `#[shallowview(clone)]`, in which case
+///    it's cloned into the view.
+///
+/// All lifetimes of `Foo` are replaced with `'view`. If none of the fields borrow anything, the view type
+/// has no lifetime parameter.
+///
+/// It also generates `Foo::view(&self) -> FooView<'_>` and `FooView::to_owned(&self) -> Foo`, which
+/// clones the view back. Traits can be derived for the view type with `#[shallowview(derive(...))]`.
+///
+/// ```
+/// # use shallowclone::ShallowView;
+/// #[derive(ShallowView)]
+/// #[shallowview(derive(Debug, Clone, Copy))]
+/// struct Request {
+///     path: String,
+///     headers: Vec<(String, String)>,
+///     body: Option<Box<[u8]>>,
+///     #[shallowview(clone)]
+///     id: std::time::Duration,
+/// }
+///
+/// fn handle(request: RequestView) {}
+///
+/// let request = Request {
+///     path: "/".to_owned(),
+///     headers: Vec::new(),
+///     body: None,
+///     id: std::time::Duration::ZERO,
+/// };
+/// handle(request.view());
+/// let copy: Request = request.view().to_owned();
+/// ```
+pub use shallowclone_derive::ShallowView;
+/// Automatically derives the [`TryMakeOwned`] trait
+///
+/// It must be used together with `#[derive(MakeOwned)]` and reads the same `#[makeowned(...)]`
+/// attributes. Fields are converted with [`TryMakeOwned`], and an error is returned with the name of the
+/// field prepended to its path. Fields with attributes are converted as they are by [`MakeOwned`]. The
+/// `keep` and `remote` attributes are not supported.
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # use shallowclone::{Budget, MakeOwned, TryMakeOwned};
+/// #[derive(MakeOwned, TryMakeOwned, Clone, Debug)]
+/// struct Message<'a> {
+///     from: Cow<'a, str>,
+///     attachments: Vec<Cow<'a, [u8]>>,
+/// }
+///
+/// let data = [0; 1024];
+/// let message = Message {
+///     from: Cow::Borrowed("me"),
+///     attachments: vec![Cow::Borrowed(&data[..10]), Cow::Borrowed(&data)],
+/// };
+/// let error = message.try_make_owned(&mut Budget::new(512, 100)).unwrap_err();
+/// assert_eq!(error.path(), "attachments[1]");
+/// ```
+pub use shallowclone_derive::TryMakeOwned;
+/// Automatically derives the [`VisitCows`] trait
+///
+/// It reads the same `#[makeowned(...)]` attributes as `#[derive(MakeOwned)]`. All fields are visited
+/// however they're converted, except for the ones with `no_inspect`. Fields whose types depend on the
+/// type parameters are required to implement [`VisitCows`]. The `remote` attribute is not supported.
+///
+/// The path of a field is its name, or its index in a tuple struct or variant, enum variants are not
+/// part of the path.
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # use shallowclone::{CoCowSlice, CowVisitor, FieldPath, VisitCows};
+/// #[derive(VisitCows)]
+/// struct Request<'a> {
+///     path: Cow<'a, str>,
+///     headers: Vec<Cow<'a, str>>,
+///     body: Option<CoCowSlice<'a, u8>>,
+/// }
+///
+/// struct Borrowed(Vec<String>);
+///
+/// impl CowVisitor for Borrowed {
+///     fn visit_any(&mut self, path: &FieldPath, borrowed: bool) {
+///         if borrowed {
+///             self.0.push(path.to_string());
+///         }
+///     }
+/// }
+///
+/// let request = Request {
+///     path: Cow::Owned(String::from("/")),
+///     headers: vec![Cow::Owned(String::from("a: b")), Cow::Borrowed("c: d")],
+///     body: Some(CoCowSlice::Borrowed(b"body")),
+/// };
+/// let mut borrowed = Borrowed(Vec::new());
+/// request.visit_cows(&mut borrowed);
+/// assert_eq!(borrowed.0, ["headers[1]", "body"]);
+/// ```
+pub use shallowclone_derive::VisitCows;
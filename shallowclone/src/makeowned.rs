@@ -5,18 +5,42 @@ use std::{
 	marker::PhantomData,
 };
 
+#[cfg(test)]
 mod tests;
 
 /// Takes a value and transforms it to be `'static`, cloning parts if necessary
-pub trait MakeOwned: Clone {
+///
+/// This doesn't require [`Clone`], since converting an owned value only moves its parts, so types holding
+/// resources like files or locks can implement it too. Generic code that clones the values needs to
+/// bound `T: MakeOwned + Clone`, or `T: MakeOwned + ToOwnedStatic` to convert them from references.
+pub trait MakeOwned {
 	/// This must be a `'static` SUBTYPE of `Self`.
 	///
-	/// For more information see <https://doc.rust-lang.org/reference/subtyping.html>
-	type Owned: Clone + 'static;
+	/// For more information see <https://doc.rust-lang.org/reference/subtyping.html>. Manual
+	/// implementations can check this with [`assert_makeowned_subtype!`][crate::assert_makeowned_subtype].
+	type Owned: 'static;
 
 	fn make_owned(self) -> Self::Owned;
 }
 
+/// Converts a reference to the `'static` [`Owned`][MakeOwned::Owned] type in one pass.
+///
+/// The same as `value.clone().make_owned()`, but without cloning the parts that would be converted
+/// anyway. This is a separate trait, so that containers can require it from their elements without
+/// [`MakeOwned`] requiring [`Clone`].
+///
+/// Implemented for the types from std whose parts implement it, and by `#[derive(MakeOwned)]` with
+/// `#[makeowned(to_owned_static)]`. Types
+/// that don't convert anything can implement it by cloning themselves.
+///
+/// [`MakeOwned`] is required by the method rather than being a supertrait, because bounds like
+/// `Vec<T>: ToOwnedStatic` would otherwise hide the [`Owned`][MakeOwned::Owned] type of the impl.
+pub trait ToOwnedStatic {
+	fn to_owned_static(&self) -> <Self as MakeOwned>::Owned
+	where
+		Self: MakeOwned;
+}
+
 impl<'a> MakeOwned for Cow<'a, str> {
 	type Owned = Cow<'static, str>;
 
@@ -27,6 +51,11 @@ impl<'a> MakeOwned for Cow<'a, str> {
 		})
 	}
 }
+impl<'a> ToOwnedStatic for Cow<'a, str> {
+	fn to_owned_static(&self) -> <Self as MakeOwned>::Owned {
+		Cow::Owned(self.to_string())
+	}
+}
 impl<'a, T: MakeOwned + Clone> MakeOwned for Cow<'a, [T]>
 where
 	<T as MakeOwned>::Owned: Clone,
@@ -35,12 +64,20 @@ where
 
 	fn make_owned(self) -> <Self as MakeOwned>::Owned {
 		Cow::Owned(match self {
-			Cow::Borrowed(bor) => bor.into_iter().map(|e| e.clone().make_owned()).collect(),
+			Cow::Borrowed(bor) => bor.iter().map(|e| e.clone().make_owned()).collect(),
 			Cow::Owned(owned) => owned.into_iter().map(|e| e.make_owned()).collect(),
 		})
 	}
 }
-impl<'a, A: MakeOwned + 'static, T: Clone + ?Sized> MakeOwned for Cow<'a, T>
+impl<'a, T: MakeOwned + ToOwnedStatic + Clone> ToOwnedStatic for Cow<'a, [T]>
+where
+	<T as MakeOwned>::Owned: Clone,
+{
+	fn to_owned_static(&self) -> <Self as MakeOwned>::Owned {
+		Cow::Owned(self.iter().map(|e| e.to_owned_static()).collect())
+	}
+}
+impl<'a, A: MakeOwned + Clone + 'static, T: Clone> MakeOwned for Cow<'a, T>
 where
 	T: MakeOwned<Owned = A>,
 {
@@ -53,6 +90,14 @@ where
 		}
 	}
 }
+impl<'a, A: MakeOwned + Clone + 'static, T: Clone> ToOwnedStatic for Cow<'a, T>
+where
+	T: MakeOwned + ToOwnedStatic + MakeOwned<Owned = A>,
+{
+	fn to_owned_static(&self) -> <Self as MakeOwned>::Owned {
+		Cow::Owned((**self).to_owned_static())
+	}
+}
 
 impl<T: 'static> MakeOwned for PhantomData<T> {
 	type Owned = Self;
@@ -61,23 +106,92 @@ impl<T: 'static> MakeOwned for PhantomData<T> {
 		self
 	}
 }
+impl<T: 'static> ToOwnedStatic for PhantomData<T> {
+	fn to_owned_static(&self) -> <Self as MakeOwned>::Owned {
+		*self
+	}
+}
 
+/// Implements [`MakeOwned`], [`TryMakeOwned`][crate::TryMakeOwned], [`OwnInPlace`][crate::OwnInPlace],
+/// [`BorrowState`][crate::BorrowState] and [`VisitCows`][crate::VisitCows] for types that are always owned,
+/// by leaving them as they are.
+///
+/// Useful for foreign types that don't borrow anything, which can't be derived due to the orphan rule.
+/// The types don't have to be [`Clone`], so [`ToOwnedStatic`] is not implemented, see
+/// [`impl_by_clone!`][crate::impl_by_clone] for that. [`HeapSize`][crate::HeapSize],
+/// [`Rebase`][crate::Rebase] and `MakeOwnedIn` can be implemented with
+/// [`impl_no_heap_size!`][crate::impl_no_heap_size], [`impl_rebase_by_move!`][crate::impl_rebase_by_move]
+/// and `impl_makeowned_in_by_move!`.
+///
+/// ```
+/// # mod other_crate { pub struct Handle(pub std::fs::File); }
+/// shallowclone::impl_makeowned_basic! { other_crate::Handle }
+/// ```
+#[macro_export]
 macro_rules! impl_makeowned_basic {
     ($( $x:ty ),* $(,)? ) => {
         $(
-            impl MakeOwned for $x {
+            impl $crate::MakeOwned for $x {
                 type Owned = Self;
 
                 fn make_owned(self) -> Self::Owned {
                     self
                 }
             }
+
+            impl $crate::TryMakeOwned for $x {
+                fn try_make_owned(self, _budget: &mut $crate::Budget) -> ::core::result::Result<Self::Owned, $crate::OwnError> {
+                    ::core::result::Result::Ok(self)
+                }
+            }
+
+            impl $crate::OwnInPlace for $x {
+                fn own_in_place(&mut self) {}
+            }
+
+            impl $crate::BorrowState for $x {
+                fn add_borrow_stats(&self, _stats: &mut $crate::BorrowStats) {}
+
+                fn borrows_from(&self, _buffer: &[u8]) -> bool {
+                    false
+                }
+            }
+
+            impl $crate::VisitCows for $x {
+                fn visit_cows_at<V: $crate::CowVisitor>(&self, _visitor: &mut V, _path: &mut $crate::FieldPath) {}
+
+                fn visit_cows_mut_at<V: $crate::CowVisitorMut>(&mut self, _visitor: &mut V, _path: &mut $crate::FieldPath) {}
+            }
+        )*
+    };
+}
+
+/// Implements [`ToOwnedStatic`] for types that are always owned, by cloning them.
+///
+/// [`impl_by_clone!`][crate::impl_by_clone] calls this together with
+/// [`impl_makeowned_basic!`][crate::impl_makeowned_basic].
+///
+/// ```
+/// # mod other_crate { #[derive(Clone)] pub struct Id(pub u64); }
+/// shallowclone::impl_makeowned_basic! { other_crate::Id }
+/// shallowclone::impl_to_owned_static_by_clone! { other_crate::Id }
+/// ```
+#[macro_export]
+macro_rules! impl_to_owned_static_by_clone {
+    ($( $x:ty ),* $(,)? ) => {
+        $(
+            impl $crate::ToOwnedStatic for $x {
+                fn to_owned_static(&self) -> <Self as $crate::MakeOwned>::Owned {
+                    ::core::clone::Clone::clone(self)
+                }
+            }
         )*
     };
 }
 
 // primitives
 impl_makeowned_basic! { u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, f32, f64, bool, char}
+impl_to_owned_static_by_clone! { u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, f32, f64, bool, char}
 
 impl<const N: usize, T: MakeOwned> MakeOwned for [T; N] {
 	type Owned = [T::Owned; N];
@@ -86,9 +200,15 @@ impl<const N: usize, T: MakeOwned> MakeOwned for [T; N] {
 		self.map(|i| i.make_owned())
 	}
 }
+impl<const N: usize, T: MakeOwned + ToOwnedStatic> ToOwnedStatic for [T; N] {
+	fn to_owned_static(&self) -> <Self as MakeOwned>::Owned {
+		std::array::from_fn(|i| self[i].to_owned_static())
+	}
+}
 
 // common std types
 impl_makeowned_basic! { String }
+impl_to_owned_static_by_clone! { String }
 
 impl<T: MakeOwned> MakeOwned for Option<T> {
 	type Owned = Option<T::Owned>;
@@ -97,6 +217,11 @@ impl<T: MakeOwned> MakeOwned for Option<T> {
 		self.map(|x| x.make_owned())
 	}
 }
+impl<T: MakeOwned + ToOwnedStatic> ToOwnedStatic for Option<T> {
+	fn to_owned_static(&self) -> <Self as MakeOwned>::Owned {
+		self.as_ref().map(|x| x.to_owned_static())
+	}
+}
 
 impl<T: MakeOwned> MakeOwned for Vec<T> {
 	type Owned = Vec<T::Owned>;
@@ -105,6 +230,11 @@ impl<T: MakeOwned> MakeOwned for Vec<T> {
 		self.into_iter().map(|x| x.make_owned()).collect()
 	}
 }
+impl<T: MakeOwned + ToOwnedStatic> ToOwnedStatic for Vec<T> {
+	fn to_owned_static(&self) -> <Self as MakeOwned>::Owned {
+		self.iter().map(|x| x.to_owned_static()).collect()
+	}
+}
 
 impl<T: MakeOwned> MakeOwned for Box<T> {
 	type Owned = Box<T::Owned>;
@@ -113,6 +243,11 @@ impl<T: MakeOwned> MakeOwned for Box<T> {
 		Box::new((*self).make_owned())
 	}
 }
+impl<T: MakeOwned + ToOwnedStatic> ToOwnedStatic for Box<T> {
+	fn to_owned_static(&self) -> <Self as MakeOwned>::Owned {
+		Box::new((**self).to_owned_static())
+	}
+}
 
 impl<K: MakeOwned, V: MakeOwned> MakeOwned for HashMap<K, V>
 where
@@ -126,6 +261,16 @@ where
 			.collect()
 	}
 }
+impl<K: MakeOwned + ToOwnedStatic, V: MakeOwned + ToOwnedStatic> ToOwnedStatic for HashMap<K, V>
+where
+	K::Owned: Eq + Hash,
+{
+	fn to_owned_static(&self) -> <Self as MakeOwned>::Owned {
+		self.iter()
+			.map(|(k, v)| (k.to_owned_static(), v.to_owned_static()))
+			.collect()
+	}
+}
 
 impl<K: MakeOwned, V: MakeOwned> MakeOwned for BTreeMap<K, V>
 where
@@ -139,6 +284,16 @@ where
 			.collect()
 	}
 }
+impl<K: MakeOwned + ToOwnedStatic, V: MakeOwned + ToOwnedStatic> ToOwnedStatic for BTreeMap<K, V>
+where
+	K::Owned: Eq + Ord,
+{
+	fn to_owned_static(&self) -> <Self as MakeOwned>::Owned {
+		self.iter()
+			.map(|(k, v)| (k.to_owned_static(), v.to_owned_static()))
+			.collect()
+	}
+}
 
 #[cfg(feature = "indexmap")]
 impl<K: MakeOwned, V: MakeOwned> MakeOwned for indexmap::IndexMap<K, V>
@@ -153,3 +308,21 @@ where
 			.collect()
 	}
 }
+#[cfg(feature = "indexmap")]
+impl<K: MakeOwned + ToOwnedStatic, V: MakeOwned + ToOwnedStatic> ToOwnedStatic
+	for indexmap::IndexMap<K, V>
+where
+	K::Owned: Hash + Eq,
+{
+	fn to_owned_static(&self) -> <Self as MakeOwned>::Owned {
+		self.iter()
+			.map(|(k, v)| (k.to_owned_static(), v.to_owned_static()))
+			.collect()
+	}
+}
+
+// the impls above must uphold the subtype contract too
+crate::assert_makeowned_subtype!(Cow<'_, str>);
+crate::assert_makeowned_subtype!(<T: MakeOwned<Owned = T> + Clone> Cow<'_, [T]>);
+crate::assert_makeowned_subtype!(<T: MakeOwned<Owned = T>> Option<Vec<Box<[T; 4]>>>);
+crate::assert_makeowned_subtype!(<K: MakeOwned<Owned = K> + Ord, V: MakeOwned<Owned = V>> BTreeMap<K, V>);
@@ -1,4 +1,6 @@
-use crate::MakeOwned;
+#![allow(dead_code, clippy::enum_variant_names)]
+
+use crate::{tests::fixtures::Handle, MakeOwned, ToOwnedStatic};
 use std::{borrow::Cow, marker::PhantomData};
 
 #[derive(MakeOwned, Clone)]
@@ -43,6 +45,25 @@ struct WithPhantom<#[makeowned(skip)] T> {
 	inner: PhantomData<T>,
 }
 
+#[derive(MakeOwned, Clone)]
+struct ConstGeneric<'a, T, const N: usize> {
+	items: [T; N],
+	name: Cow<'a, str>,
+}
+
+#[derive(MakeOwned, Clone, Copy)]
+union CopyUnion<T: Copy> {
+	int: u32,
+	value: T,
+}
+
+#[derive(MakeOwned, Clone, Copy)]
+#[repr(C, packed(2))]
+struct Packed<T: Copy> {
+	id: u8,
+	value: T,
+}
+
 #[derive(MakeOwned, Clone)]
 pub struct HoverActionShowEntity<'a> {
 	/// The textual identifier of the entity's type. If unrecognized, defaults to minecraft:pig.
@@ -52,3 +73,366 @@ pub struct HoverActionShowEntity<'a> {
 	/// The entity's custom name.
 	pub name: Option<Cow<'a, str>>,
 }
+
+#[derive(MakeOwned, Clone)]
+struct WithWhereClause<'a, T>
+where
+	T: Clone + PartialEq<T>,
+{
+	data: Cow<'a, [T]>,
+}
+
+#[derive(MakeOwned, Clone)]
+struct WithAssocTypeBound<T: Iterator>
+where
+	T::Item: std::fmt::Display,
+{
+	iter: T,
+}
+
+#[derive(MakeOwned, Clone)]
+#[makeowned(bound = "T: MakeOwned<Owned = T> + 'static")]
+struct WithCustomBound<T> {
+	field: T,
+}
+
+#[derive(Clone, Default, PartialEq, Debug)]
+struct NotMakeOwned(u32);
+
+fn own_str(s: Cow<str>) -> Cow<'static, str> {
+	Cow::Owned(s.into_owned())
+}
+
+#[derive(MakeOwned, Clone)]
+struct WithFieldAttrs<'a> {
+	#[makeowned(skip)]
+	skipped: NotMakeOwned,
+	#[makeowned(with = "own_str")]
+	with: Cow<'a, str>,
+	#[makeowned(default)]
+	cache: Option<Cow<'a, str>>,
+}
+
+#[derive(MakeOwned, Clone)]
+enum EnumWithFieldAttrs<'a> {
+	Tuple(#[makeowned(default)] Vec<Cow<'a, str>>, Cow<'a, str>),
+	Struct {
+		#[makeowned(skip)]
+		skipped: NotMakeOwned,
+		#[makeowned(with = "own_str")]
+		with: Cow<'a, str>,
+	},
+}
+
+#[derive(MakeOwned, Clone)]
+struct WithPhantomInferred<T: Clone> {
+	phantom: std::marker::PhantomData<T>,
+}
+
+#[derive(MakeOwned, Clone)]
+struct WithProjection<T: Iterator>
+where
+	T::Item: Clone,
+{
+	item: <T as Iterator>::Item,
+	shorthand: Option<T::Item>,
+}
+
+#[derive(MakeOwned, Clone)]
+struct GenericDefault<T> {
+	#[makeowned(default)]
+	cache: T,
+}
+
+#[test]
+fn field_attributes() {
+	let s = String::from("borrowed");
+	let value = WithFieldAttrs {
+		skipped: NotMakeOwned(5),
+		with: Cow::Borrowed(&s),
+		cache: Some(Cow::Borrowed(&s)),
+	};
+	let owned = value.make_owned();
+
+	assert_eq!(owned.skipped, NotMakeOwned(5));
+	assert!(matches!(owned.with, Cow::Owned(ref x) if x == "borrowed"));
+	assert_eq!(owned.cache, None);
+}
+
+#[derive(MakeOwned)]
+#[makeowned(to_owned_static)]
+struct Document<'a, T> {
+	title: Cow<'a, str>,
+	sections: Cow<'a, [String]>,
+	#[makeowned(skip)]
+	id: u32,
+	#[makeowned(with = "own_str")]
+	author: Cow<'a, str>,
+	meta: Option<T>,
+}
+
+thread_local! {
+	static DOCUMENT_CLONES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+impl<T: Clone> Clone for Document<'_, T> {
+	fn clone(&self) -> Self {
+		DOCUMENT_CLONES.with(|clones| clones.set(clones.get() + 1));
+		Self {
+			title: self.title.clone(),
+			sections: self.sections.clone(),
+			id: self.id,
+			author: self.author.clone(),
+			meta: self.meta.clone(),
+		}
+	}
+}
+
+#[test]
+fn to_owned_static() {
+	let text = String::from("text");
+	let sections = vec![text.clone(), text.clone()];
+	let document = Document {
+		title: Cow::Borrowed(text.as_str()),
+		sections: Cow::Borrowed(&sections),
+		id: 7,
+		author: Cow::Owned("author".to_owned()),
+		meta: Some(Cow::Borrowed(text.as_str())),
+	};
+
+	let owned: Document<'static, Cow<'static, str>> = document.to_owned_static();
+	// containers convert their elements from references too
+	let documents = vec![Some(Box::new(document))];
+	let owned_documents = documents.to_owned_static();
+	assert_eq!(DOCUMENT_CLONES.with(|clones| clones.get()), 0);
+	drop(documents);
+	drop(sections);
+	drop(text);
+
+	assert!(
+		matches!(owned_documents[0], Some(ref d) if matches!(d.title, Cow::Owned(ref x) if x == "text"))
+	);
+
+	assert!(matches!(owned.title, Cow::Owned(ref x) if x == "text"));
+	assert!(matches!(owned.sections, Cow::Owned(ref x) if x == &["text", "text"]));
+	assert_eq!(owned.id, 7);
+	assert!(matches!(owned.author, Cow::Owned(ref x) if x == "author"));
+	assert!(matches!(owned.meta, Some(Cow::Owned(ref x)) if x == "text"));
+}
+
+// not `Clone`, since the lock and the receiver can't be cloned
+#[derive(MakeOwned)]
+struct Worker<'a, T> {
+	name: Cow<'a, str>,
+	#[makeowned(skip)]
+	state: std::sync::Mutex<u32>,
+	#[makeowned(skip)]
+	jobs: std::sync::mpsc::Receiver<T>,
+	pending: Vec<Resource<'a>>,
+}
+
+#[derive(MakeOwned)]
+struct Resource<'a> {
+	path: Cow<'a, str>,
+	#[makeowned(skip)]
+	handle: std::sync::Arc<std::sync::Mutex<()>>,
+}
+
+#[test]
+fn without_clone() {
+	let name = String::from("worker");
+	let (sender, receiver) = std::sync::mpsc::channel();
+	let worker = Worker {
+		name: Cow::Borrowed(&name),
+		state: std::sync::Mutex::new(1),
+		jobs: receiver,
+		pending: vec![Resource {
+			path: Cow::Borrowed(&name),
+			handle: Default::default(),
+		}],
+	};
+
+	let owned: Worker<'static, u8> = worker.make_owned();
+	drop(name);
+	sender.send(5).unwrap();
+
+	assert!(matches!(owned.name, Cow::Owned(ref x) if x == "worker"));
+	assert_eq!(*owned.state.lock().unwrap(), 1);
+	assert_eq!(owned.jobs.recv().unwrap(), 5);
+	assert!(matches!(owned.pending[0].path, Cow::Owned(ref x) if x == "worker"));
+}
+
+// `Handle` isn't `Clone`, so registering it can't implement `ToOwnedStatic`
+#[derive(MakeOwned)]
+struct WithHandle<'a> {
+	name: Cow<'a, str>,
+	handle: Handle,
+}
+
+mod hygiene {
+	// nothing is imported here on purpose
+
+	#[derive(crate::MakeOwned, Clone)]
+	struct CollidingLifetimes<'any, 'shallowclone, T> {
+		field1: Option<T>,
+		field2: std::borrow::Cow<'any, str>,
+		field3: std::borrow::Cow<'shallowclone, str>,
+	}
+
+	#[derive(crate::MakeOwned, Clone)]
+	#[makeowned(crate = "crate")]
+	struct CustomCratePath<'a> {
+		field: std::borrow::Cow<'a, str>,
+	}
+}
+
+#[derive(MakeOwned, Clone)]
+#[makeowned(owned = "MessageOwned")]
+struct MessageRef<'a> {
+	id: u32,
+	#[makeowned(with = "ToOwned::to_owned")]
+	text: &'a str,
+	#[makeowned(with = "<[u8]>::to_vec")]
+	items: &'a [u8],
+}
+
+#[derive(Clone)]
+struct MessageOwned {
+	text: String,
+	items: Vec<u8>,
+	id: u32,
+}
+
+#[test]
+fn custom_owned() {
+	let text = String::from("text");
+	let items = vec![1, 2, 3];
+	let message = MessageRef {
+		id: 1,
+		text: &text,
+		items: &items,
+	};
+	let owned = message.make_owned();
+	assert_eq!(owned.id, 1);
+	assert_eq!(owned.text, "text");
+	assert_eq!(owned.items, [1, 2, 3]);
+}
+
+mod remote {
+	use crate::MakeOwned;
+	use std::borrow::Cow;
+
+	mod foreign {
+		use std::borrow::Cow;
+
+		#[derive(Clone)]
+		pub struct Name<'a> {
+			pub first: Cow<'a, str>,
+			pub last: Option<Cow<'a, str>>,
+		}
+	}
+
+	#[derive(MakeOwned)]
+	#[makeowned(remote = "foreign::Name")]
+	struct NameDef<'a> {
+		first: Cow<'a, str>,
+		last: Option<Cow<'a, str>>,
+	}
+
+	#[derive(MakeOwned, Clone)]
+	struct User<'a> {
+		#[makeowned(with = "NameDef::make_owned")]
+		name: foreign::Name<'a>,
+	}
+
+	#[test]
+	fn remote() {
+		let first = String::from("first");
+		let user = User {
+			name: foreign::Name {
+				first: Cow::Borrowed(&first),
+				last: None,
+			},
+		};
+		let owned: User<'static> = user.make_owned();
+
+		assert!(matches!(owned.name.first, Cow::Owned(ref x) if x == "first"));
+	}
+}
+
+mod keep {
+	use crate::{MakeOwned, MakeOwnedFor};
+	use std::borrow::Cow;
+
+	#[derive(Clone)]
+	struct Config {
+		name: String,
+	}
+
+	#[derive(MakeOwned, Clone)]
+	#[makeowned(keep = 'cfg)]
+	struct Header<'cfg, 'buf> {
+		config: &'cfg Config,
+		name: Cow<'buf, str>,
+	}
+
+	#[derive(MakeOwned, Clone)]
+	#[makeowned(keep = 'cfg)]
+	struct Packet<'cfg, 'buf, T> {
+		config: &'cfg Config,
+		fallback: Option<Cow<'cfg, str>>,
+		header: Header<'cfg, 'buf>,
+		extra: Vec<Header<'cfg, 'buf>>,
+		payload: Cow<'buf, [u8]>,
+		id: T,
+	}
+
+	#[derive(MakeOwned, Clone)]
+	#[makeowned(keep = 'cfg)]
+	enum Message<'cfg, 'buf> {
+		Packet(Packet<'cfg, 'buf, u32>),
+		Named(&'cfg str, (Cow<'buf, str>, &'cfg Config)),
+	}
+
+	fn detach<'cfg>(packet: Packet<'cfg, '_, u32>) -> Packet<'cfg, 'static, u32> {
+		packet.make_owned_for()
+	}
+
+	#[test]
+	fn keep() {
+		let config = Config {
+			name: String::from("config"),
+		};
+		let buffer = vec![1, 2, 3];
+		let name = String::from("name");
+
+		let header = Header {
+			config: &config,
+			name: Cow::Borrowed(&name),
+		};
+		let packet = Packet {
+			config: &config,
+			fallback: None,
+			header: header.clone(),
+			extra: vec![header],
+			payload: Cow::Borrowed(&buffer),
+			id: 5,
+		};
+		let owned = detach(packet);
+		drop(buffer);
+		drop(name);
+
+		assert!(std::ptr::eq(owned.config, &config));
+		assert!(std::ptr::eq(owned.header.config, &config));
+		assert_eq!(owned.config.name, "config");
+		assert!(matches!(owned.header.name, Cow::Owned(ref x) if x == "name"));
+		assert!(matches!(owned.payload, Cow::Owned(ref x) if x == &[1, 2, 3]));
+
+		let message: Message<'_, 'static> =
+			Message::Named("named", (Cow::Borrowed("buf"), &config)).make_owned_for();
+		assert!(matches!(
+			message,
+			Message::Named("named", (Cow::Owned(_), _))
+		));
+	}
+}
@@ -0,0 +1,93 @@
+use crate::MakeOwned;
+use std::borrow::Cow;
+
+/// Like [`MakeOwned`], but only detaches some of the lifetimes, keeping the ones that outlive `'keep`
+///
+/// This is useful for types like `Packet<'cfg, 'buf>`, where `'cfg` points into long-lived data which
+/// doesn't need to be copied, and `'buf` into a transient buffer. Deriving [`MakeOwned`] with
+/// `#[makeowned(keep = 'cfg)]` implements this trait, converting it to `Packet<'cfg, 'static>`.
+pub trait MakeOwnedFor<'keep> {
+	/// This must be a SUBTYPE of `Self` that outlives `'keep`.
+	///
+	/// For more information see <https://doc.rust-lang.org/reference/subtyping.html>.
+	type Owned: 'keep;
+
+	fn make_owned_for(self) -> Self::Owned;
+}
+
+impl<'keep, 'a: 'keep, T: ?Sized + 'keep> MakeOwnedFor<'keep> for &'a T {
+	type Owned = &'a T;
+
+	fn make_owned_for(self) -> Self::Owned {
+		self
+	}
+}
+
+impl<'keep, const N: usize, T: MakeOwnedFor<'keep>> MakeOwnedFor<'keep> for [T; N] {
+	type Owned = [T::Owned; N];
+
+	fn make_owned_for(self) -> Self::Owned {
+		self.map(|i| i.make_owned_for())
+	}
+}
+
+impl<'keep, T: MakeOwnedFor<'keep>> MakeOwnedFor<'keep> for Option<T> {
+	type Owned = Option<T::Owned>;
+
+	fn make_owned_for(self) -> Self::Owned {
+		self.map(|x| x.make_owned_for())
+	}
+}
+
+impl<'keep, T: MakeOwnedFor<'keep>> MakeOwnedFor<'keep> for Vec<T> {
+	type Owned = Vec<T::Owned>;
+
+	fn make_owned_for(self) -> Self::Owned {
+		self.into_iter().map(|x| x.make_owned_for()).collect()
+	}
+}
+
+impl<'keep, T: MakeOwnedFor<'keep>> MakeOwnedFor<'keep> for Box<T> {
+	type Owned = Box<T::Owned>;
+
+	fn make_owned_for(self) -> Self::Owned {
+		Box::new((*self).make_owned_for())
+	}
+}
+
+// tuples are a common way to combine kept and detached parts
+impl<'keep, A: MakeOwnedFor<'keep>, B: MakeOwnedFor<'keep>> MakeOwnedFor<'keep> for (A, B) {
+	type Owned = (A::Owned, B::Owned);
+
+	fn make_owned_for(self) -> Self::Owned {
+		(self.0.make_owned_for(), self.1.make_owned_for())
+	}
+}
+
+/// Implements [`MakeOwnedFor`] by detaching everything with [`MakeOwned`].
+///
+/// A blanket implementation would conflict with the implementations for containers, so this is only
+/// done for common leaf types. The derive uses [`MakeOwned`] directly for fields without kept lifetimes.
+macro_rules! impl_by_makeowned {
+    ($( $x:ty ),* $(,)? ) => {
+        $(
+            impl<'keep> MakeOwnedFor<'keep> for $x {
+                type Owned = <Self as MakeOwned>::Owned;
+
+                fn make_owned_for(self) -> <Self as MakeOwnedFor<'keep>>::Owned {
+                    self.make_owned()
+                }
+            }
+        )*
+    };
+}
+
+impl_by_makeowned! { u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, f32, f64, bool, char, String }
+
+impl<'keep, 'a> MakeOwnedFor<'keep> for Cow<'a, str> {
+	type Owned = Cow<'static, str>;
+
+	fn make_owned_for(self) -> <Self as MakeOwnedFor<'keep>>::Owned {
+		self.make_owned()
+	}
+}
@@ -0,0 +1,221 @@
+use crate::{CoCow, CoCowSlice};
+use bumpalo::Bump;
+use std::{
+	borrow::Cow,
+	collections::{BTreeMap, HashMap},
+	hash::Hash,
+	marker::PhantomData,
+	mem,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Like [`MakeOwned`][crate::MakeOwned], but copies the borrowed data into a [`Bump`] arena instead of
+/// separate heap allocations, so that the result lives as long as the arena.
+///
+/// This is much cheaper for short-lived bulk data, like copying all borrowed strings of a parsed batch
+/// into an arena that lives for a request. Owned values are moved as they are.
+///
+/// The arena doesn't run destructors, so values that need to be dropped (like a `Cow<str>` inside of a
+/// `CoCow`) are made owned on the heap instead. This is also how the owned form of the borrowed value of
+/// types defined with [`define_cocow!`][crate::define_cocow] is treated.
+pub trait MakeOwnedIn<'arena> {
+	/// This must be `Self` with the lifetimes changed to `'arena`.
+	type Owned: 'arena;
+
+	fn make_owned_in(self, arena: &'arena Bump) -> Self::Owned;
+}
+
+/// Moves the value into the arena if it doesn't need to be dropped, or makes it owned otherwise
+fn alloc<'arena, T>(arena: &'arena Bump, value: T) -> CoCow<'arena, T> {
+	match mem::needs_drop::<T>() {
+		true => CoCow::Owned(value),
+		false => CoCow::Borrowed(arena.alloc(value)),
+	}
+}
+
+/// Copies the elements into the arena if they don't need to be dropped, or collects them otherwise
+fn alloc_slice<'arena, T>(
+	arena: &'arena Bump,
+	elements: impl ExactSizeIterator<Item = T>,
+) -> CoCowSlice<'arena, T> {
+	match mem::needs_drop::<T>() {
+		true => CoCowSlice::Owned(elements.collect()),
+		false => CoCowSlice::Borrowed(arena.alloc_slice_fill_iter(elements)),
+	}
+}
+
+impl<'arena, 'a> MakeOwnedIn<'arena> for Cow<'a, str> {
+	type Owned = Cow<'arena, str>;
+
+	fn make_owned_in(self, arena: &'arena Bump) -> <Self as MakeOwnedIn<'arena>>::Owned {
+		match self {
+			Cow::Borrowed(bor) => Cow::Borrowed(arena.alloc_str(bor)),
+			Cow::Owned(owned) => Cow::Owned(owned),
+		}
+	}
+}
+impl<'arena, 'a, T: MakeOwnedIn<'arena> + Clone> MakeOwnedIn<'arena> for Cow<'a, [T]>
+where
+	T::Owned: Clone,
+{
+	type Owned = Cow<'arena, [T::Owned]>;
+
+	fn make_owned_in(self, arena: &'arena Bump) -> <Self as MakeOwnedIn<'arena>>::Owned {
+		match self {
+			Cow::Borrowed(bor) => {
+				match alloc_slice(arena, bor.iter().map(|e| e.clone().make_owned_in(arena))) {
+					CoCowSlice::Owned(owned) => Cow::Owned(owned),
+					CoCowSlice::Borrowed(bor) => Cow::Borrowed(bor),
+				}
+			}
+			Cow::Owned(owned) => Cow::Owned(owned.make_owned_in(arena)),
+		}
+	}
+}
+impl<'arena, 'a, A: Clone + 'arena, T: Clone> MakeOwnedIn<'arena> for Cow<'a, T>
+where
+	T: MakeOwnedIn<'arena, Owned = A>,
+{
+	type Owned = Cow<'arena, A>;
+
+	fn make_owned_in(self, arena: &'arena Bump) -> <Self as MakeOwnedIn<'arena>>::Owned {
+		match self {
+			Cow::Borrowed(bor) => match alloc(arena, bor.clone().make_owned_in(arena)) {
+				CoCow::Owned(owned) => Cow::Owned(owned),
+				CoCow::Borrowed(bor) => Cow::Borrowed(bor),
+			},
+			Cow::Owned(owned) => Cow::Owned(owned.make_owned_in(arena)),
+		}
+	}
+}
+
+impl<'arena, 'a, T: MakeOwnedIn<'arena> + Clone> MakeOwnedIn<'arena> for CoCow<'a, T> {
+	type Owned = CoCow<'arena, T::Owned>;
+
+	fn make_owned_in(self, arena: &'arena Bump) -> <Self as MakeOwnedIn<'arena>>::Owned {
+		match self {
+			CoCow::Borrowed(bor) => alloc(arena, bor.clone().make_owned_in(arena)),
+			CoCow::Owned(owned) => CoCow::Owned(owned.make_owned_in(arena)),
+		}
+	}
+}
+impl<'arena, 'a, T: MakeOwnedIn<'arena> + Clone> MakeOwnedIn<'arena> for CoCowSlice<'a, T> {
+	type Owned = CoCowSlice<'arena, T::Owned>;
+
+	fn make_owned_in(self, arena: &'arena Bump) -> <Self as MakeOwnedIn<'arena>>::Owned {
+		match self {
+			CoCowSlice::Borrowed(bor) => {
+				alloc_slice(arena, bor.iter().map(|e| e.clone().make_owned_in(arena)))
+			}
+			CoCowSlice::Owned(owned) => CoCowSlice::Owned(owned.make_owned_in(arena)),
+		}
+	}
+}
+
+impl<'arena, T: 'arena> MakeOwnedIn<'arena> for PhantomData<T> {
+	type Owned = Self;
+
+	fn make_owned_in(self, _arena: &'arena Bump) -> Self::Owned {
+		self
+	}
+}
+
+/// Implements [`MakeOwnedIn`] for types that are always owned, by returning them as they are.
+///
+/// Useful for foreign types registered with [`impl_makeowned_basic!`][crate::impl_makeowned_basic].
+///
+/// ```
+/// # mod other_crate { pub struct Id(pub u64); }
+/// shallowclone::impl_makeowned_in_by_move! { other_crate::Id }
+/// ```
+#[macro_export]
+macro_rules! impl_makeowned_in_by_move {
+    ($( $x:ty ),* $(,)? ) => {
+        $(
+            impl<'arena> $crate::MakeOwnedIn<'arena> for $x {
+                type Owned = Self;
+
+                fn make_owned_in(self, _arena: &'arena $crate::__private::Bump) -> Self::Owned {
+                    self
+                }
+            }
+        )*
+    };
+}
+
+impl_makeowned_in_by_move! { u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, f32, f64, bool, char, String }
+
+impl<'arena, const N: usize, T: MakeOwnedIn<'arena>> MakeOwnedIn<'arena> for [T; N] {
+	type Owned = [T::Owned; N];
+
+	fn make_owned_in(self, arena: &'arena Bump) -> Self::Owned {
+		self.map(|i| i.make_owned_in(arena))
+	}
+}
+
+impl<'arena, T: MakeOwnedIn<'arena>> MakeOwnedIn<'arena> for Option<T> {
+	type Owned = Option<T::Owned>;
+
+	fn make_owned_in(self, arena: &'arena Bump) -> Self::Owned {
+		self.map(|x| x.make_owned_in(arena))
+	}
+}
+
+impl<'arena, T: MakeOwnedIn<'arena>> MakeOwnedIn<'arena> for Vec<T> {
+	type Owned = Vec<T::Owned>;
+
+	fn make_owned_in(self, arena: &'arena Bump) -> Self::Owned {
+		self.into_iter().map(|x| x.make_owned_in(arena)).collect()
+	}
+}
+
+impl<'arena, T: MakeOwnedIn<'arena>> MakeOwnedIn<'arena> for Box<T> {
+	type Owned = Box<T::Owned>;
+
+	fn make_owned_in(self, arena: &'arena Bump) -> Self::Owned {
+		Box::new((*self).make_owned_in(arena))
+	}
+}
+
+impl<'arena, K: MakeOwnedIn<'arena>, V: MakeOwnedIn<'arena>> MakeOwnedIn<'arena> for HashMap<K, V>
+where
+	K::Owned: Eq + Hash,
+{
+	type Owned = HashMap<K::Owned, V::Owned>;
+
+	fn make_owned_in(self, arena: &'arena Bump) -> Self::Owned {
+		self.into_iter()
+			.map(|(k, v)| (k.make_owned_in(arena), v.make_owned_in(arena)))
+			.collect()
+	}
+}
+
+impl<'arena, K: MakeOwnedIn<'arena>, V: MakeOwnedIn<'arena>> MakeOwnedIn<'arena> for BTreeMap<K, V>
+where
+	K::Owned: Eq + Ord,
+{
+	type Owned = BTreeMap<K::Owned, V::Owned>;
+
+	fn make_owned_in(self, arena: &'arena Bump) -> Self::Owned {
+		self.into_iter()
+			.map(|(k, v)| (k.make_owned_in(arena), v.make_owned_in(arena)))
+			.collect()
+	}
+}
+
+#[cfg(feature = "indexmap")]
+impl<'arena, K: MakeOwnedIn<'arena>, V: MakeOwnedIn<'arena>> MakeOwnedIn<'arena>
+	for indexmap::IndexMap<K, V>
+where
+	K::Owned: Hash + Eq,
+{
+	type Owned = indexmap::IndexMap<K::Owned, V::Owned>;
+
+	fn make_owned_in(self, arena: &'arena Bump) -> Self::Owned {
+		self.into_iter()
+			.map(|(k, v)| (k.make_owned_in(arena), v.make_owned_in(arena)))
+			.collect()
+	}
+}
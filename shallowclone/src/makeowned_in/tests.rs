@@ -0,0 +1,68 @@
+use crate::{tests::fixtures::Handle, CoCow, CoCowSlice, MakeOwnedIn};
+use bumpalo::Bump;
+use std::borrow::Cow;
+
+#[derive(MakeOwnedIn, Debug)]
+struct Point<'a> {
+	label: Cow<'a, str>,
+	coords: Cow<'a, [u32]>,
+}
+
+#[derive(MakeOwnedIn, Debug)]
+struct Batch<'a, T> {
+	id: T,
+	points: Vec<Point<'a>>,
+	origin: CoCow<'a, u64>,
+	names: CoCowSlice<'a, Cow<'a, str>>,
+	#[makeowned(default)]
+	cache: Option<String>,
+}
+
+fn detach<'arena, T: MakeOwnedIn<'arena, Owned = T>>(
+	batch: Batch<'_, T>,
+	arena: &'arena Bump,
+) -> Batch<'arena, T> {
+	batch.make_owned_in(arena)
+}
+
+#[test]
+fn make_owned_in() {
+	let arena = Bump::new();
+	let input = String::from("label");
+	let coords = vec![1, 2, 3];
+	let names = vec![Cow::Borrowed("name")];
+	let origin = 7;
+
+	let batch = Batch {
+		id: 1u8,
+		points: vec![Point {
+			label: Cow::Borrowed(&input),
+			coords: Cow::Borrowed(&coords),
+		}],
+		origin: CoCow::Borrowed(&origin),
+		names: CoCowSlice::Borrowed(&names),
+		cache: Some(String::from("cache")),
+	};
+	let batch = detach(batch, &arena);
+	drop((input, coords, names));
+
+	// copied into the arena
+	assert!(matches!(batch.points[0].label, Cow::Borrowed("label")));
+	assert!(matches!(batch.points[0].coords, Cow::Borrowed(&[1, 2, 3])));
+	assert!(matches!(batch.origin, CoCow::Borrowed(&7)));
+	// `Cow` needs to be dropped, so it's not moved into the arena
+	assert!(matches!(&batch.names, CoCowSlice::Owned(names) if names[0] == "name"));
+	assert_eq!(batch.id, 1);
+	assert_eq!(batch.cache, None);
+
+	// types registered with `impl_makeowned_in_by_move!` are moved as they are
+	let batch = Batch {
+		id: Handle::open(),
+		points: Vec::new(),
+		origin: CoCow::Borrowed(&origin),
+		names: CoCowSlice::Owned(Vec::new()),
+		cache: None,
+	};
+	let batch = detach(batch, &arena);
+	assert!(matches!(batch.origin, CoCow::Borrowed(&7)));
+}
@@ -0,0 +1,116 @@
+use std::{
+	borrow::Cow,
+	collections::{BTreeMap, HashMap},
+	hash::Hash,
+	marker::PhantomData,
+	mem,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Converts every borrowed copy-on-write value inside to its owned variant in place, like a deep
+/// [`Cow::to_mut`], keeping the same type and lifetime.
+///
+/// Unlike [`MakeOwned`][crate::MakeOwned] this doesn't consume the value, which is useful before mutating
+/// a structure that borrows from a buffer which is about to be reused.
+pub trait OwnInPlace {
+	fn own_in_place(&mut self);
+}
+
+impl<'a> OwnInPlace for Cow<'a, str> {
+	fn own_in_place(&mut self) {
+		self.to_mut();
+	}
+}
+impl<'a, T: OwnInPlace + Clone> OwnInPlace for Cow<'a, [T]> {
+	fn own_in_place(&mut self) {
+		self.to_mut().own_in_place();
+	}
+}
+impl<'a, T: OwnInPlace + Clone> OwnInPlace for Cow<'a, T> {
+	fn own_in_place(&mut self) {
+		self.to_mut().own_in_place();
+	}
+}
+
+impl<T: ?Sized> OwnInPlace for PhantomData<T> {
+	fn own_in_place(&mut self) {}
+}
+
+// primitives and String are implemented by `impl_makeowned_basic!`
+
+impl<const N: usize, T: OwnInPlace> OwnInPlace for [T; N] {
+	fn own_in_place(&mut self) {
+		self.iter_mut().for_each(T::own_in_place);
+	}
+}
+
+impl<T: OwnInPlace> OwnInPlace for Option<T> {
+	fn own_in_place(&mut self) {
+		if let Some(x) = self {
+			x.own_in_place();
+		}
+	}
+}
+
+impl<T: OwnInPlace> OwnInPlace for Vec<T> {
+	fn own_in_place(&mut self) {
+		self.iter_mut().for_each(T::own_in_place);
+	}
+}
+
+impl<T: OwnInPlace + ?Sized> OwnInPlace for Box<T> {
+	fn own_in_place(&mut self) {
+		(**self).own_in_place();
+	}
+}
+
+// the keys of maps can't be mutated in place, so the maps are rebuilt, which is fine since owning a
+// value doesn't change its hash or ordering
+
+impl<K: OwnInPlace + Eq + Hash, V: OwnInPlace> OwnInPlace for HashMap<K, V> {
+	fn own_in_place(&mut self) {
+		own_entries(self);
+	}
+}
+
+impl<K: OwnInPlace + Ord, V: OwnInPlace> OwnInPlace for BTreeMap<K, V> {
+	fn own_in_place(&mut self) {
+		own_entries(self);
+	}
+}
+
+#[cfg(feature = "indexmap")]
+impl<K: OwnInPlace + Hash + Eq, V: OwnInPlace> OwnInPlace for indexmap::IndexMap<K, V> {
+	fn own_in_place(&mut self) {
+		own_entries(self);
+	}
+}
+
+/// Moves the entries of a map into a [`Vec`], owns them there and puts them back
+///
+/// The entries are put back when [`RebuildGuard`] is dropped, so even if one of them panics the map keeps
+/// all of its entries, some of them still borrowed.
+fn own_entries<K: OwnInPlace, V: OwnInPlace, M>(map: &mut M)
+where
+	M: Default + IntoIterator<Item = (K, V)> + FromIterator<(K, V)>,
+{
+	let entries = mem::take(map).into_iter().collect();
+	let mut guard = RebuildGuard { map, entries };
+	for (k, v) in &mut guard.entries {
+		k.own_in_place();
+		v.own_in_place();
+	}
+}
+
+struct RebuildGuard<'m, M: FromIterator<(K, V)>, K, V> {
+	map: &'m mut M,
+	entries: Vec<(K, V)>,
+}
+
+impl<'m, M: FromIterator<(K, V)>, K, V> Drop for RebuildGuard<'m, M, K, V> {
+	fn drop(&mut self) {
+		*self.map = mem::take(&mut self.entries).into_iter().collect();
+	}
+}
@@ -0,0 +1,80 @@
+use crate::{CoCow, OwnInPlace};
+use std::{borrow::Cow, collections::BTreeMap};
+
+#[derive(Clone)]
+struct Config;
+
+#[derive(OwnInPlace)]
+#[makeowned(keep = 'cfg)]
+struct Record<'cfg, 'a, T> {
+	config: &'cfg Config,
+	key: Cow<'a, str>,
+	values: Vec<CoCow<'a, T>>,
+	index: BTreeMap<Cow<'a, str>, Option<Cow<'a, [u8]>>>,
+	#[makeowned(skip)]
+	source: &'a str,
+}
+
+#[derive(OwnInPlace)]
+enum Change<'a> {
+	Insert(Cow<'a, str>, u32),
+	Remove { key: Cow<'a, str> },
+	Clear,
+}
+
+#[test]
+fn own_in_place() {
+	let config = Config;
+	let value = 5u64;
+	let mut record = Record {
+		config: &config,
+		key: Cow::Borrowed("key"),
+		values: vec![CoCow::Borrowed(&value), CoCow::Owned(6)],
+		index: BTreeMap::from([(Cow::Borrowed("a"), Some(Cow::Borrowed(&[1u8][..])))]),
+		source: "source",
+	};
+	record.own_in_place();
+
+	assert!(std::ptr::eq(record.config, &config));
+	assert!(matches!(record.key, Cow::Owned(ref x) if x == "key"));
+	assert!(record.values.iter().all(|v| v.is_owned()));
+	let (key, value) = record.index.iter().next().unwrap();
+	assert!(matches!(key, Cow::Owned(_)));
+	assert!(matches!(value, Some(Cow::Owned(x)) if x == &[1]));
+	assert_eq!(record.source, "source");
+
+	let mut changes = [
+		Change::Insert(Cow::Borrowed("a"), 1),
+		Change::Remove {
+			key: Cow::Borrowed("b"),
+		},
+		Change::Clear,
+	];
+	changes.own_in_place();
+	assert!(matches!(changes[0], Change::Insert(Cow::Owned(_), 1)));
+	assert!(matches!(changes[1], Change::Remove { key: Cow::Owned(_) }));
+}
+
+struct Panics(bool);
+
+impl OwnInPlace for Panics {
+	fn own_in_place(&mut self) {
+		assert!(!self.0, "owning failed");
+	}
+}
+
+#[test]
+fn map_keeps_entries_on_panic() {
+	let mut map = BTreeMap::from([
+		(Cow::Borrowed("a"), Panics(false)),
+		(Cow::Borrowed("b"), Panics(true)),
+		(Cow::Borrowed("c"), Panics(false)),
+	]);
+	let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| map.own_in_place()));
+	assert!(result.is_err());
+
+	let keys = map.keys().collect::<Vec<_>>();
+	assert_eq!(keys, ["a", "b", "c"]);
+	assert!(matches!(keys[0], Cow::Owned(_)));
+	assert!(matches!(keys[2], Cow::Borrowed(_)));
+}
@@ -0,0 +1,235 @@
+use crate::{CoCow, CoCowSlice};
+use std::{
+	borrow::Cow,
+	collections::{BTreeMap, HashMap},
+	hash::Hash,
+	marker::PhantomData,
+	str,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Moves a value that borrows from one buffer over to a copy of that buffer, e.g. after copying a
+/// receive buffer into a larger ring buffer, without making every field owned.
+///
+/// Borrowed strings and byte slices which lie inside of `old` are re-pointed to the same offset in `new`.
+/// Everything else that is borrowed is copied like [`MakeOwned`][crate::MakeOwned] does, since it isn't
+/// known to live as long as `new`, and owned values are moved as they are. Parts which don't fit into
+/// `new`, or strings which aren't valid UTF-8 there anymore, are copied as well, so `new` should contain
+/// the same bytes as `old`.
+///
+/// Types defined with [`define_cocow!`][crate::define_cocow] are always copied when borrowed, since
+/// what they borrow isn't known.
+pub trait Rebase<'new>: Sized {
+	/// This must be `Self` with the lifetimes changed to `'new`.
+	type Rebased: 'new;
+
+	fn rebase(self, old: &[u8], new: &'new [u8]) -> Self::Rebased;
+
+	/// Rebases a borrowed slice, which is copied unless it's a byte slice inside of `old`
+	fn rebase_slice(slice: &[Self], old: &[u8], new: &'new [u8]) -> CoCowSlice<'new, Self::Rebased>
+	where
+		Self: Clone,
+	{
+		CoCowSlice::Owned(slice.iter().map(|x| x.clone().rebase(old, new)).collect())
+	}
+}
+
+/// The bytes at the same offset in `new`, if `bytes` lie inside of `old` and the offset fits into `new`
+fn rebase_bytes<'new>(bytes: &[u8], old: &[u8], new: &'new [u8]) -> Option<&'new [u8]> {
+	let offset = (bytes.as_ptr() as usize).checked_sub(old.as_ptr() as usize)?;
+	let end = offset.checked_add(bytes.len())?;
+	if end > old.len() {
+		return None;
+	}
+	new.get(offset..end)
+}
+
+impl<'new, 'a> Rebase<'new> for Cow<'a, str> {
+	type Rebased = Cow<'new, str>;
+
+	fn rebase(self, old: &[u8], new: &'new [u8]) -> <Self as Rebase<'new>>::Rebased {
+		match self {
+			Cow::Borrowed(bor) => match rebase_bytes(bor.as_bytes(), old, new) {
+				Some(bytes) => match str::from_utf8(bytes) {
+					Ok(rebased) => Cow::Borrowed(rebased),
+					Err(_) => Cow::Owned(bor.to_owned()),
+				},
+				None => Cow::Owned(bor.to_owned()),
+			},
+			Cow::Owned(owned) => Cow::Owned(owned),
+		}
+	}
+}
+impl<'new, 'a, T: Rebase<'new> + Clone> Rebase<'new> for Cow<'a, [T]>
+where
+	T::Rebased: Clone,
+{
+	type Rebased = Cow<'new, [T::Rebased]>;
+
+	fn rebase(self, old: &[u8], new: &'new [u8]) -> <Self as Rebase<'new>>::Rebased {
+		match self {
+			Cow::Borrowed(bor) => match T::rebase_slice(bor, old, new) {
+				CoCowSlice::Owned(owned) => Cow::Owned(owned),
+				CoCowSlice::Borrowed(bor) => Cow::Borrowed(bor),
+			},
+			Cow::Owned(owned) => Cow::Owned(owned.rebase(old, new)),
+		}
+	}
+}
+impl<'new, 'a, A: Clone + 'new, T: Clone> Rebase<'new> for Cow<'a, T>
+where
+	T: Rebase<'new, Rebased = A>,
+{
+	type Rebased = Cow<'new, A>;
+
+	fn rebase(self, old: &[u8], new: &'new [u8]) -> <Self as Rebase<'new>>::Rebased {
+		match self {
+			Cow::Borrowed(bor) => Cow::Owned(bor.clone().rebase(old, new)),
+			Cow::Owned(owned) => Cow::Owned(owned.rebase(old, new)),
+		}
+	}
+}
+
+impl<'new, 'a, T: Rebase<'new> + Clone> Rebase<'new> for CoCow<'a, T> {
+	type Rebased = CoCow<'new, T::Rebased>;
+
+	fn rebase(self, old: &[u8], new: &'new [u8]) -> <Self as Rebase<'new>>::Rebased {
+		match self {
+			CoCow::Borrowed(bor) => CoCow::Owned(bor.clone().rebase(old, new)),
+			CoCow::Owned(owned) => CoCow::Owned(owned.rebase(old, new)),
+		}
+	}
+}
+impl<'new, 'a, T: Rebase<'new> + Clone> Rebase<'new> for CoCowSlice<'a, T> {
+	type Rebased = CoCowSlice<'new, T::Rebased>;
+
+	fn rebase(self, old: &[u8], new: &'new [u8]) -> <Self as Rebase<'new>>::Rebased {
+		match self {
+			CoCowSlice::Borrowed(bor) => T::rebase_slice(bor, old, new),
+			CoCowSlice::Owned(owned) => CoCowSlice::Owned(owned.rebase(old, new)),
+		}
+	}
+}
+
+impl<'new, T: 'new> Rebase<'new> for PhantomData<T> {
+	type Rebased = Self;
+
+	fn rebase(self, _old: &[u8], _new: &'new [u8]) -> Self::Rebased {
+		self
+	}
+}
+
+// byte slices are the only ones which can be re-pointed, since any bytes are valid for them
+impl<'new> Rebase<'new> for u8 {
+	type Rebased = Self;
+
+	fn rebase(self, _old: &[u8], _new: &'new [u8]) -> Self::Rebased {
+		self
+	}
+
+	fn rebase_slice(slice: &[Self], old: &[u8], new: &'new [u8]) -> CoCowSlice<'new, Self> {
+		match rebase_bytes(slice, old, new) {
+			Some(rebased) => CoCowSlice::Borrowed(rebased),
+			None => CoCowSlice::Owned(slice.to_vec()),
+		}
+	}
+}
+
+/// Implements [`Rebase`] for types that are always owned, by returning them as they are.
+///
+/// Useful for foreign types registered with [`impl_makeowned_basic!`][crate::impl_makeowned_basic].
+///
+/// ```
+/// # mod other_crate { pub struct Id(pub u64); }
+/// shallowclone::impl_rebase_by_move! { other_crate::Id }
+/// ```
+#[macro_export]
+macro_rules! impl_rebase_by_move {
+    ($( $x:ty ),* $(,)? ) => {
+        $(
+            impl<'new> $crate::Rebase<'new> for $x {
+                type Rebased = Self;
+
+                fn rebase(self, _old: &[u8], _new: &'new [u8]) -> Self::Rebased {
+                    self
+                }
+            }
+        )*
+    };
+}
+
+impl_rebase_by_move! { i8, u16, i16, u32, i32, u64, i64, u128, i128, f32, f64, bool, char, String }
+
+impl<'new, const N: usize, T: Rebase<'new>> Rebase<'new> for [T; N] {
+	type Rebased = [T::Rebased; N];
+
+	fn rebase(self, old: &[u8], new: &'new [u8]) -> Self::Rebased {
+		self.map(|i| i.rebase(old, new))
+	}
+}
+
+impl<'new, T: Rebase<'new>> Rebase<'new> for Option<T> {
+	type Rebased = Option<T::Rebased>;
+
+	fn rebase(self, old: &[u8], new: &'new [u8]) -> Self::Rebased {
+		self.map(|x| x.rebase(old, new))
+	}
+}
+
+impl<'new, T: Rebase<'new>> Rebase<'new> for Vec<T> {
+	type Rebased = Vec<T::Rebased>;
+
+	fn rebase(self, old: &[u8], new: &'new [u8]) -> Self::Rebased {
+		self.into_iter().map(|x| x.rebase(old, new)).collect()
+	}
+}
+
+impl<'new, T: Rebase<'new>> Rebase<'new> for Box<T> {
+	type Rebased = Box<T::Rebased>;
+
+	fn rebase(self, old: &[u8], new: &'new [u8]) -> Self::Rebased {
+		Box::new((*self).rebase(old, new))
+	}
+}
+
+impl<'new, K: Rebase<'new>, V: Rebase<'new>> Rebase<'new> for HashMap<K, V>
+where
+	K::Rebased: Eq + Hash,
+{
+	type Rebased = HashMap<K::Rebased, V::Rebased>;
+
+	fn rebase(self, old: &[u8], new: &'new [u8]) -> Self::Rebased {
+		self.into_iter()
+			.map(|(k, v)| (k.rebase(old, new), v.rebase(old, new)))
+			.collect()
+	}
+}
+
+impl<'new, K: Rebase<'new>, V: Rebase<'new>> Rebase<'new> for BTreeMap<K, V>
+where
+	K::Rebased: Eq + Ord,
+{
+	type Rebased = BTreeMap<K::Rebased, V::Rebased>;
+
+	fn rebase(self, old: &[u8], new: &'new [u8]) -> Self::Rebased {
+		self.into_iter()
+			.map(|(k, v)| (k.rebase(old, new), v.rebase(old, new)))
+			.collect()
+	}
+}
+
+#[cfg(feature = "indexmap")]
+impl<'new, K: Rebase<'new>, V: Rebase<'new>> Rebase<'new> for indexmap::IndexMap<K, V>
+where
+	K::Rebased: Hash + Eq,
+{
+	type Rebased = indexmap::IndexMap<K::Rebased, V::Rebased>;
+
+	fn rebase(self, old: &[u8], new: &'new [u8]) -> Self::Rebased {
+		self.into_iter()
+			.map(|(k, v)| (k.rebase(old, new), v.rebase(old, new)))
+			.collect()
+	}
+}
@@ -0,0 +1,83 @@
+use crate::{
+	points_into,
+	tests::fixtures::{Handle, Header},
+	CoCow, CoCowSlice, Rebase,
+};
+use std::{borrow::Cow, str};
+
+#[derive(Rebase)]
+enum Frame<'a, T> {
+	Data {
+		id: T,
+		payload: Cow<'a, [u8]>,
+		header: Option<CoCow<'a, Header<'a>>>,
+	},
+	Text(Cow<'a, str>),
+}
+
+#[test]
+fn rebase() {
+	let old = b"name value payload".to_vec();
+	let text = str::from_utf8(&old).unwrap();
+	let values = [Cow::Borrowed(&text[5..10]), Cow::Borrowed("static")];
+	let header = Header {
+		name: Cow::Borrowed(&text[..4]),
+		values: CoCowSlice::Borrowed(&values),
+	};
+	let frame = Frame::Data {
+		id: 7u32,
+		payload: Cow::Borrowed(&old[11..]),
+		header: Some(CoCow::Borrowed(&header)),
+	};
+
+	let mut new = vec![0; 64];
+	new[..old.len()].copy_from_slice(&old);
+	let Frame::Data {
+		id,
+		payload,
+		header,
+	} = frame.rebase(&old, &new)
+	else {
+		unreachable!()
+	};
+	assert_eq!(id, 7);
+	assert_eq!(payload, &b"payload"[..]);
+	assert!(matches!(&payload, Cow::Borrowed(bytes) if points_into(*bytes, &new)));
+
+	// the header and its values aren't bytes, so they're copied, but the strings in them are rebased
+	let header = header.unwrap();
+	assert!(header.is_owned() && header.values.is_owned());
+	assert!(matches!(&header.name, Cow::Borrowed(name) if points_into(*name, &new)));
+	assert!(matches!(&header.values[0], Cow::Borrowed(value) if points_into(*value, &new)));
+	assert_eq!(header.values[0], "value");
+	// borrowed from outside of the old buffer
+	assert!(matches!(header.values[1], Cow::Owned(_)));
+
+	// the payload doesn't fit into the new buffer, and the text isn't borrowed
+	let short = &old[..11];
+	let frame: Frame<u8> = Frame::Data {
+		id: 1,
+		payload: Cow::Borrowed(&old[11..]),
+		header: None,
+	};
+	let Frame::Data { payload, .. } = frame.rebase(&old, short) else {
+		unreachable!()
+	};
+	assert!(matches!(payload, Cow::Owned(_)));
+
+	// types registered with `impl_rebase_by_move!` are moved over
+	let frame = Frame::Data {
+		id: Handle::open(),
+		payload: Cow::Borrowed(&old[11..]),
+		header: None,
+	};
+	let Frame::Data { payload, .. } = frame.rebase(&old, &new) else {
+		unreachable!()
+	};
+	assert!(matches!(&payload, Cow::Borrowed(bytes) if points_into(*bytes, &new)));
+	let frame: Frame<u8> = Frame::Text(Cow::Owned(String::from("text")));
+	assert!(matches!(
+		frame.rebase(&old, &new),
+		Frame::Text(Cow::Owned(_))
+	));
+}
@@ -0,0 +1,294 @@
+use std::{
+	array,
+	borrow::Cow,
+	collections::{BTreeMap, HashMap},
+	hash::Hash,
+	marker::PhantomData,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// The same as [`Clone`], but doesnt clone [`Cow`][std::borrow::Cow] values, instead it just borrows them.
+pub trait ShallowClone<'a> {
+	type Target;
+
+	fn shallow_clone(&'a self) -> Self::Target;
+}
+
+/// [`ShallowClone`] for any lifetime, using a generic associated type.
+///
+/// Generic code can bound `T: ShallowCloneGat` instead of the higher-ranked `for<'a> T: ShallowClone<'a>`,
+/// which requires `T: 'static` for any type that borrows something. It can be derived together with
+/// [`ShallowClone`] with `#[derive(ShallowClone)]` and `#[shallowclone(gat)]`.
+///
+/// The two traits are independent, so that containers can implement [`ShallowClone`] for elements that
+/// only implement [`ShallowClone`]. Types implementing this trait manually should implement [`ShallowClone`]
+/// too, by calling [`ShallowCloneGat::shallow_clone_gat`]. Maps only implement this trait for keys that
+/// don't borrow anything, like `String`, since the bounds on their keys have to be higher-ranked.
+pub trait ShallowCloneGat {
+	type Target<'a>
+	where
+		Self: 'a;
+
+	fn shallow_clone_gat(&self) -> Self::Target<'_>;
+}
+
+impl<'a, 'b, T: ToOwned + ?Sized> ShallowClone<'a> for Cow<'b, T>
+where
+	'b: 'a,
+{
+	type Target = Cow<'a, T>;
+
+	fn shallow_clone(&'a self) -> Self::Target {
+		Cow::Borrowed(&**self)
+	}
+}
+impl<'b, T: ToOwned + ?Sized> ShallowCloneGat for Cow<'b, T> {
+	type Target<'a>
+		= Cow<'a, T>
+	where
+		Self: 'a;
+
+	fn shallow_clone_gat(&self) -> Self::Target<'_> {
+		Cow::Borrowed(&**self)
+	}
+}
+
+impl<'a, 'b, T: ?Sized> ShallowClone<'a> for &'b T
+where
+	'b: 'a,
+{
+	type Target = &'a T;
+
+	fn shallow_clone(&'a self) -> Self::Target {
+		self
+	}
+}
+impl<T: ?Sized> ShallowCloneGat for &T {
+	type Target<'a>
+		= &'a T
+	where
+		Self: 'a;
+
+	fn shallow_clone_gat(&self) -> Self::Target<'_> {
+		self
+	}
+}
+
+impl<'a, T: ?Sized> ShallowClone<'a> for PhantomData<T> {
+	type Target = Self;
+
+	fn shallow_clone(&'a self) -> Self::Target {
+		*self
+	}
+}
+impl<T: ?Sized> ShallowCloneGat for PhantomData<T> {
+	type Target<'a>
+		= Self
+	where
+		Self: 'a;
+
+	fn shallow_clone_gat(&self) -> Self::Target<'_> {
+		*self
+	}
+}
+
+/// Implements [`ShallowClone`] and [`ShallowCloneGat`] for types that are always owned, by cloning them.
+///
+/// Useful for foreign types that don't contain any copy-on-write values, which can't be derived due to
+/// the orphan rule. See also [`impl_by_clone!`][crate::impl_by_clone].
+///
+/// ```
+/// # mod other_crate { #[derive(Clone)] pub struct Id(pub u64); }
+/// shallowclone::impl_shallowclone_by_clone! { other_crate::Id }
+/// ```
+#[macro_export]
+macro_rules! impl_shallowclone_by_clone {
+    ($( $x:ty ),* $(,)? ) => {
+        $(
+            impl<'a> $crate::ShallowClone<'a> for $x {
+                type Target = Self;
+
+                fn shallow_clone(&'a self) -> Self::Target {
+                    ::core::clone::Clone::clone(self)
+                }
+            }
+
+            impl $crate::ShallowCloneGat for $x {
+                type Target<'a> = Self where Self: 'a;
+
+                fn shallow_clone_gat(&self) -> Self::Target<'_> {
+                    ::core::clone::Clone::clone(self)
+                }
+            }
+        )*
+    };
+}
+
+// primitives
+impl_shallowclone_by_clone! { u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, f32, f64, bool, char}
+
+impl<'a, const N: usize, T: ShallowClone<'a>> ShallowClone<'a> for [T; N] {
+	type Target = [T::Target; N];
+
+	fn shallow_clone(&'a self) -> Self::Target {
+		array::from_fn(|i| self[i].shallow_clone())
+	}
+}
+impl<const N: usize, T: ShallowCloneGat> ShallowCloneGat for [T; N] {
+	type Target<'a>
+		= [T::Target<'a>; N]
+	where
+		Self: 'a;
+
+	fn shallow_clone_gat(&self) -> Self::Target<'_> {
+		array::from_fn(|i| self[i].shallow_clone_gat())
+	}
+}
+
+// common std types
+impl_shallowclone_by_clone! { String }
+
+impl<'a, T: ShallowClone<'a>> ShallowClone<'a> for Option<T> {
+	type Target = Option<T::Target>;
+
+	fn shallow_clone(&'a self) -> Self::Target {
+		self.as_ref().map(|x| x.shallow_clone())
+	}
+}
+impl<T: ShallowCloneGat> ShallowCloneGat for Option<T> {
+	type Target<'a>
+		= Option<T::Target<'a>>
+	where
+		Self: 'a;
+
+	fn shallow_clone_gat(&self) -> Self::Target<'_> {
+		self.as_ref().map(|x| x.shallow_clone_gat())
+	}
+}
+
+impl<'a, T: ShallowClone<'a>> ShallowClone<'a> for Vec<T> {
+	type Target = Vec<T::Target>;
+
+	fn shallow_clone(&'a self) -> Self::Target {
+		self.iter().map(|x| x.shallow_clone()).collect()
+	}
+}
+impl<T: ShallowCloneGat> ShallowCloneGat for Vec<T> {
+	type Target<'a>
+		= Vec<T::Target<'a>>
+	where
+		Self: 'a;
+
+	fn shallow_clone_gat(&self) -> Self::Target<'_> {
+		self.iter().map(|x| x.shallow_clone_gat()).collect()
+	}
+}
+
+impl<'a, T: ShallowClone<'a>> ShallowClone<'a> for Box<T> {
+	type Target = Box<T::Target>;
+
+	fn shallow_clone(&'a self) -> Self::Target {
+		Box::new(self.as_ref().shallow_clone())
+	}
+}
+impl<T: ShallowCloneGat> ShallowCloneGat for Box<T> {
+	type Target<'a>
+		= Box<T::Target<'a>>
+	where
+		Self: 'a;
+
+	fn shallow_clone_gat(&self) -> Self::Target<'_> {
+		Box::new(self.as_ref().shallow_clone_gat())
+	}
+}
+
+// the key bounds of the ShallowCloneGat impls of maps have to be higher-ranked (`for<'a> K::Target<'a>: Hash`),
+// which the compiler can only prove for keys that don't borrow anything, like `String`
+
+impl<'a, K: ShallowClone<'a>, V: ShallowClone<'a>> ShallowClone<'a> for HashMap<K, V>
+where
+	K::Target: Eq + Hash,
+{
+	type Target = HashMap<K::Target, V::Target>;
+
+	fn shallow_clone(&'a self) -> Self::Target {
+		self.iter()
+			.map(|(k, v)| (k.shallow_clone(), v.shallow_clone()))
+			.collect()
+	}
+}
+impl<K: ShallowCloneGat, V: ShallowCloneGat> ShallowCloneGat for HashMap<K, V>
+where
+	for<'a> K::Target<'a>: Eq + Hash,
+{
+	type Target<'a>
+		= HashMap<K::Target<'a>, V::Target<'a>>
+	where
+		Self: 'a;
+
+	fn shallow_clone_gat(&self) -> Self::Target<'_> {
+		self.iter()
+			.map(|(k, v)| (k.shallow_clone_gat(), v.shallow_clone_gat()))
+			.collect()
+	}
+}
+
+impl<'a, K: ShallowClone<'a>, V: ShallowClone<'a>> ShallowClone<'a> for BTreeMap<K, V>
+where
+	K::Target: Eq + Ord,
+{
+	type Target = BTreeMap<K::Target, V::Target>;
+
+	fn shallow_clone(&'a self) -> Self::Target {
+		self.iter()
+			.map(|(k, v)| (k.shallow_clone(), v.shallow_clone()))
+			.collect()
+	}
+}
+impl<K: ShallowCloneGat, V: ShallowCloneGat> ShallowCloneGat for BTreeMap<K, V>
+where
+	for<'a> K::Target<'a>: Eq + Ord,
+{
+	type Target<'a>
+		= BTreeMap<K::Target<'a>, V::Target<'a>>
+	where
+		Self: 'a;
+
+	fn shallow_clone_gat(&self) -> Self::Target<'_> {
+		self.iter()
+			.map(|(k, v)| (k.shallow_clone_gat(), v.shallow_clone_gat()))
+			.collect()
+	}
+}
+
+#[cfg(feature = "indexmap")]
+impl<'a, K: ShallowClone<'a>, V: ShallowClone<'a>> ShallowClone<'a> for indexmap::IndexMap<K, V>
+where
+	K::Target: Hash + Eq,
+{
+	type Target = indexmap::IndexMap<K::Target, V::Target>;
+
+	fn shallow_clone(&'a self) -> Self::Target {
+		self.iter()
+			.map(|(k, v)| (k.shallow_clone(), v.shallow_clone()))
+			.collect()
+	}
+}
+#[cfg(feature = "indexmap")]
+impl<K: ShallowCloneGat, V: ShallowCloneGat> ShallowCloneGat for indexmap::IndexMap<K, V>
+where
+	for<'a> K::Target<'a>: Hash + Eq,
+{
+	type Target<'a>
+		= indexmap::IndexMap<K::Target<'a>, V::Target<'a>>
+	where
+		Self: 'a;
+
+	fn shallow_clone_gat(&self) -> Self::Target<'_> {
+		self.iter()
+			.map(|(k, v)| (k.shallow_clone_gat(), v.shallow_clone_gat()))
+			.collect()
+	}
+}
@@ -0,0 +1,372 @@
+#![allow(dead_code, clippy::enum_variant_names)]
+
+use crate::ShallowClone;
+use std::borrow::Cow;
+
+#[derive(ShallowClone)]
+struct UnitStruct;
+
+#[derive(ShallowClone)]
+struct EmptyStruct {}
+
+#[derive(ShallowClone)]
+struct TupleStruct(u16, u32, String);
+
+#[derive(ShallowClone)]
+struct Struct {
+	field1: u16,
+	field2: u32,
+	field3: String,
+}
+
+#[derive(ShallowClone)]
+struct StructGeneric<T> {
+	field: Option<T>,
+}
+
+#[derive(ShallowClone)]
+enum Enum<'a, T> {
+	UnitVariant,
+	TupleVariant(u16, u32, String),
+	StructVariant {
+		field1: &'a u16,
+		field2: T,
+		field3: Cow<'a, str>,
+	},
+}
+
+#[derive(ShallowClone)]
+struct Array<'a, #[shallowclone(skip)] T: Clone> {
+	pub data: Cow<'a, [T]>,
+}
+
+#[derive(ShallowClone)]
+struct ArrayInferred<'a, T: Clone> {
+	pub data: Cow<'a, [T]>,
+}
+
+#[derive(ShallowClone)]
+struct WithPhantom<T> {
+	phantom: std::marker::PhantomData<T>,
+}
+
+#[derive(ShallowClone)]
+struct WithProjection<T: Iterator> {
+	item: <T as Iterator>::Item,
+	shorthand: Option<T::Item>,
+}
+
+#[derive(ShallowClone)]
+struct ConstGeneric<'a, T, const N: usize> {
+	items: [T; N],
+	name: Cow<'a, str>,
+}
+
+#[derive(ShallowClone, Clone, Copy)]
+union CopyUnion<'a> {
+	int: u32,
+	float: f32,
+	text: &'a str,
+}
+
+#[derive(ShallowClone)]
+#[repr(C, packed)]
+struct Packed<'a, T: Copy> {
+	id: u8,
+	value: T,
+	text: &'a str,
+}
+
+#[derive(ShallowClone, Clone)]
+struct Complex<'a> {
+	field: ComplexCow<'a>,
+}
+
+#[derive(ShallowClone, Clone)]
+enum ComplexCow<'a> {
+	Owned(Vec<Complex<'a>>),
+	Borrowed(&'a [Complex<'a>]),
+}
+
+#[derive(ShallowClone)]
+struct WithWhereClause<'a, T>
+where
+	T: PartialEq + 'a,
+{
+	field1: Option<T>,
+	field2: Cow<'a, str>,
+}
+
+#[derive(ShallowClone)]
+#[shallowclone(bound = "T: ShallowClone<'shallowclone, Target = T>")]
+struct WithCustomBound<T> {
+	field: T,
+}
+
+#[derive(Clone, Default)]
+struct NotShallowClone(u32);
+
+fn reborrow<'a>(s: &'a &str) -> &'a str {
+	s
+}
+
+#[derive(ShallowClone)]
+struct WithFieldAttrs<'a> {
+	#[shallowclone(clone)]
+	cloned: NotShallowClone,
+	#[shallowclone(with = "reborrow")]
+	with: &'a str,
+	#[shallowclone(default)]
+	cache: Option<Cow<'a, str>>,
+}
+
+#[derive(ShallowClone)]
+enum EnumWithFieldAttrs<'a> {
+	Tuple(#[shallowclone(default)] Vec<Cow<'a, str>>, Cow<'a, str>),
+	Struct {
+		#[shallowclone(clone)]
+		cloned: NotShallowClone,
+		#[shallowclone(default)]
+		cache: Vec<Cow<'a, str>>,
+	},
+}
+
+mod hygiene {
+	// nothing is imported here on purpose
+
+	#[derive(crate::ShallowClone)]
+	struct CollidingLifetimes<'shallowclone, 'any, T> {
+		field1: Option<T>,
+		field2: std::borrow::Cow<'shallowclone, str>,
+		field3: &'any str,
+	}
+
+	#[derive(crate::ShallowClone)]
+	#[shallowclone(crate = "crate")]
+	struct CustomCratePath<'a> {
+		field: std::borrow::Cow<'a, str>,
+	}
+}
+
+#[derive(ShallowClone)]
+#[shallowclone(target = "MessageRef<'shallowclone>")]
+struct MessageOwned {
+	id: u32,
+	#[shallowclone(with = "String::as_str")]
+	text: String,
+	#[shallowclone(with = "Vec::as_slice")]
+	items: Vec<u8>,
+}
+
+struct MessageRef<'a> {
+	text: &'a str,
+	items: &'a [u8],
+	id: u32,
+}
+
+#[derive(ShallowClone)]
+#[shallowclone(target = "ValueRef<'shallowclone>")]
+enum ValueOwned {
+	Text(#[shallowclone(with = "String::as_str")] String),
+	Number { value: u32 },
+	Nothing,
+}
+
+#[derive(PartialEq, Debug)]
+enum ValueRef<'a> {
+	Text(&'a str),
+	Number { value: u32 },
+	Nothing,
+}
+
+#[test]
+fn custom_target() {
+	let message = MessageOwned {
+		id: 1,
+		text: String::from("text"),
+		items: vec![1, 2, 3],
+	};
+	let message_ref = message.shallow_clone();
+	assert_eq!(message_ref.id, 1);
+	assert_eq!(message_ref.text, "text");
+	assert_eq!(message_ref.items, &[1, 2, 3]);
+
+	let value = ValueOwned::Text(String::from("text"));
+	assert_eq!(value.shallow_clone(), ValueRef::Text("text"));
+	let value = ValueOwned::Number { value: 5 };
+	assert_eq!(value.shallow_clone(), ValueRef::Number { value: 5 });
+	assert_eq!(ValueOwned::Nothing.shallow_clone(), ValueRef::Nothing);
+}
+
+#[derive(ShallowClone)]
+enum Expr<'a> {
+	Lit(Cow<'a, str>),
+	Neg(Box<Expr<'a>>),
+}
+
+#[test]
+fn recursive_box() {
+	let expr = Expr::Neg(Box::new(Expr::Lit(Cow::Owned("1".to_owned()))));
+	let Expr::Neg(inner) = expr.shallow_clone() else {
+		unreachable!()
+	};
+	assert!(matches!(*inner, Expr::Lit(Cow::Borrowed("1"))));
+
+	let list: Box<Vec<Cow<str>>> = Box::new(vec![Cow::Owned("item".to_owned())]);
+	assert!(matches!(list.shallow_clone()[0], Cow::Borrowed("item")));
+}
+
+#[test]
+fn inferred_bounds() {
+	let data = [NotShallowClone(1), NotShallowClone(2)];
+	let array = ArrayInferred {
+		data: Cow::Borrowed(&data[..]),
+	};
+	let cloned: ArrayInferred<NotShallowClone> = array.shallow_clone();
+	assert_eq!(cloned.data.len(), 2);
+
+	let projection = WithProjection::<std::vec::IntoIter<u32>> {
+		item: 1,
+		shorthand: Some(2),
+	};
+	let cloned: WithProjection<std::vec::IntoIter<u32>> = projection.shallow_clone();
+	assert_eq!(cloned.shorthand, Some(2));
+}
+
+mod remote {
+	use crate::ShallowClone;
+	use std::borrow::Cow;
+
+	mod foreign {
+		use std::borrow::Cow;
+
+		pub struct Name<'a> {
+			pub first: Cow<'a, str>,
+			pub last: Option<Cow<'a, str>>,
+		}
+
+		pub enum Shape<T> {
+			Point,
+			Circle(T),
+			Rect { width: T, height: T },
+		}
+	}
+
+	#[derive(ShallowClone)]
+	#[shallowclone(remote = "foreign::Name")]
+	struct NameDef<'a> {
+		first: Cow<'a, str>,
+		last: Option<Cow<'a, str>>,
+	}
+
+	#[derive(ShallowClone)]
+	#[shallowclone(remote = "foreign::Shape")]
+	enum ShapeDef<T> {
+		Point,
+		Circle(T),
+		Rect { width: T, height: T },
+	}
+
+	crate::impl_by_clone! { Id }
+
+	#[derive(Clone, PartialEq, Debug)]
+	struct Id(u64);
+
+	#[derive(ShallowClone)]
+	struct User<'a> {
+		id: Id,
+		#[shallowclone(with = "NameDef::shallow_clone")]
+		name: foreign::Name<'a>,
+		#[shallowclone(with = "ShapeDef::shallow_clone")]
+		avatar: foreign::Shape<Cow<'a, str>>,
+	}
+
+	#[test]
+	fn remote() {
+		let user = User {
+			id: Id(1),
+			name: foreign::Name {
+				first: Cow::Owned("first".to_owned()),
+				last: None,
+			},
+			avatar: foreign::Shape::Circle(Cow::Owned("red".to_owned())),
+		};
+		let cloned = user.shallow_clone();
+
+		assert_eq!(cloned.id, Id(1));
+		assert!(matches!(cloned.name.first, Cow::Borrowed("first")));
+		assert!(matches!(
+			cloned.avatar,
+			foreign::Shape::Circle(Cow::Borrowed("red"))
+		));
+	}
+}
+
+mod gat {
+	use crate::{ShallowClone, ShallowCloneGat};
+	use std::{borrow::Cow, collections::BTreeMap};
+
+	#[derive(ShallowClone)]
+	#[shallowclone(gat)]
+	struct Entry<'a, T> {
+		key: Cow<'a, str>,
+		value: Option<T>,
+		tags: Vec<&'a str>,
+		#[shallowclone(clone)]
+		hits: u32,
+		attrs: BTreeMap<String, Cow<'a, str>>,
+	}
+
+	#[derive(ShallowClone)]
+	#[shallowclone(gat)]
+	enum Node<'a, T: 'a> {
+		Leaf(Box<T>),
+		Named {
+			name: Cow<'a, str>,
+			children: Vec<Node<'a, T>>,
+		},
+	}
+
+	// doesn't need `for<'a> T: ShallowClone<'a>`, which would require `T: 'static`
+	fn shallow_clone_all<T: ShallowCloneGat>(items: &[T]) -> Vec<T::Target<'_>> {
+		items
+			.iter()
+			.map(ShallowCloneGat::shallow_clone_gat)
+			.collect()
+	}
+
+	#[test]
+	fn gat() {
+		let text = String::from("value");
+		let entries = vec![Entry {
+			key: Cow::Owned("key".to_owned()),
+			value: Some(Cow::Borrowed(text.as_str())),
+			tags: vec!["tag"],
+			hits: 3,
+			attrs: BTreeMap::from([("attr".to_owned(), Cow::Owned("attr".to_owned()))]),
+		}];
+		let cloned = shallow_clone_all(&entries);
+		assert!(matches!(cloned[0].key, Cow::Borrowed("key")));
+		assert!(matches!(cloned[0].value, Some(Cow::Borrowed("value"))));
+		assert_eq!(cloned[0].tags, ["tag"]);
+		assert_eq!(cloned[0].hits, 3);
+		assert!(matches!(cloned[0].attrs["attr"], Cow::Borrowed("attr")));
+		// not ambiguous with both traits imported
+		assert!(matches!(
+			entries[0].shallow_clone().key,
+			Cow::Borrowed("key")
+		));
+
+		let node = Node::Named {
+			name: Cow::Owned("root".to_owned()),
+			children: vec![Node::Leaf(Box::new(Cow::Owned(text.clone())))],
+		};
+		let Node::Named { name, children } = node.shallow_clone_gat() else {
+			unreachable!()
+		};
+		assert!(matches!(name, Cow::Borrowed("root")));
+		assert!(
+			matches!(&children[0], Node::Leaf(leaf) if matches!(**leaf, Cow::Borrowed("value")))
+		);
+	}
+}
@@ -0,0 +1,393 @@
+#![allow(dead_code)]
+
+use crate::{
+	cowify, owned_args, CoCow, CoCowSlice, CowFamily, MakeOwned, ShallowClone, ShallowView,
+};
+use std::{borrow::Cow, marker::PhantomData};
+
+/// Types shared by the tests of several modules
+pub(crate) mod fixtures {
+	use crate::{BorrowState, CoCowSlice, HeapSize, MakeOwned, Rebase, ShallowClone, VisitCows};
+	use std::{borrow::Cow, fs::File};
+
+	/// A value with borrowed fields at two levels
+	#[derive(ShallowClone, MakeOwned, BorrowState, HeapSize, VisitCows, Rebase, Clone, Debug)]
+	pub(crate) struct Header<'a> {
+		// a raw identifier, which the field paths of `VisitCows` don't keep the `r#` of
+		pub r#name: Cow<'a, str>,
+		pub values: CoCowSlice<'a, Cow<'a, str>>,
+	}
+
+	/// A leaf type which isn't `Clone`, registered with all the `impl_*!` macros
+	pub(crate) struct Handle(File);
+	crate::impl_makeowned_basic! { Handle }
+	crate::impl_no_heap_size! { Handle }
+	crate::impl_rebase_by_move! { Handle }
+	#[cfg(feature = "bumpalo")]
+	crate::impl_makeowned_in_by_move! { Handle }
+
+	impl Handle {
+		pub(crate) fn open() -> Self {
+			Handle(File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml")).unwrap())
+		}
+	}
+}
+
+#[derive(CowFamily)]
+struct Struct<'a> {
+	field1: Cow<'a, str>,
+	field2: Option<CoCow<'a, u32>>,
+}
+
+#[derive(CowFamily)]
+enum Enum<'a, T> {
+	Unit,
+	Tuple(Cow<'a, str>, T),
+	Struct { field: Vec<Cow<'a, [u8]>> },
+}
+
+#[derive(CowFamily)]
+struct WithSkipped<
+	'a,
+	#[shallowclone(skip)]
+	#[makeowned(skip)] T: 'static,
+> {
+	#[shallowclone(clone)]
+	#[makeowned(skip)]
+	phantom: PhantomData<T>,
+	field: Cow<'a, str>,
+}
+
+#[derive(CowFamily)]
+#[shallowclone(gat)]
+struct Gat<'a> {
+	field: Cow<'a, str>,
+}
+
+#[test]
+fn cow_family() {
+	let text = String::from("text");
+	let value = Struct {
+		field1: Cow::Borrowed(&text),
+		field2: Some(CoCow::Owned(5)),
+	};
+
+	let reborrowed: Struct = Struct::from(&value);
+	assert!(matches!(reborrowed.field2, Some(CoCow::Borrowed(5))));
+
+	let owned: StructOwned = value.reborrow().into_static();
+	assert!(matches!(owned.field1, Cow::Owned(ref x) if x == "text"));
+
+	let cloned = value.clone();
+	assert!(matches!(cloned.field1, Cow::Borrowed("text")));
+
+	let value: Enum<u8> = Enum::Tuple(Cow::Borrowed(&text), 1);
+	let owned: EnumOwned<u8> = value.clone().into_static();
+	assert!(matches!(owned, Enum::Tuple(Cow::Owned(ref x), 1) if x == "text"));
+	assert!(matches!(
+		value.shallow_clone(),
+		Enum::Tuple(Cow::Borrowed("text"), 1)
+	));
+	assert!(matches!(value.make_owned(), Enum::Tuple(Cow::Owned(_), 1)));
+
+	let _: WithSkipped<'static, u8> = WithSkipped::from(&WithSkipped {
+		phantom: PhantomData::<u8>,
+		field: Cow::Borrowed("text"),
+	});
+
+	let value = Gat {
+		field: Cow::Owned(text.clone()),
+	};
+	assert!(matches!(value.reborrow().field, Cow::Borrowed("text")));
+	assert!(matches!(Gat::from(&value).field, Cow::Borrowed("text")));
+	let owned: GatOwned = value.into_static();
+	assert!(matches!(owned.field, Cow::Owned(ref x) if x == "text"));
+}
+
+#[derive(ShallowView, Clone, PartialEq, Debug)]
+#[shallowview(derive(Debug, Clone))]
+struct Request<'a, T> {
+	path: String,
+	headers: Vec<(String, String)>,
+	body: Option<Box<[u8]>>,
+	method: Cow<'a, str>,
+	version: u8,
+	#[shallowview(clone)]
+	id: Option<u64>,
+	extra: T,
+}
+
+#[derive(ShallowView)]
+enum Payload<'a> {
+	Empty,
+	Text(String),
+	Binary { data: Vec<u8>, name: &'a str },
+}
+
+#[derive(ShallowView)]
+struct Primitives(u32, bool);
+
+#[test]
+fn shallow_view() {
+	let request = Request {
+		path: "/".to_owned(),
+		headers: vec![("Accept".to_owned(), "*/*".to_owned())],
+		body: Some(Box::new([1, 2, 3])),
+		method: Cow::Owned("GET".to_owned()),
+		version: 2,
+		id: Some(5),
+		extra: vec![1],
+	};
+
+	let view: RequestView<Vec<u8>> = request.view();
+	assert_eq!(view.path, "/");
+	assert_eq!(view.headers.len(), 1);
+	assert_eq!(view.body, Some(&[1, 2, 3][..]));
+	assert!(matches!(view.method, Cow::Borrowed("GET")));
+	assert_eq!(view.version, 2);
+	assert_eq!(view.id, Some(5));
+	assert_eq!(view.extra, &vec![1]);
+	assert_eq!(view.to_owned(), request);
+
+	let name = String::from("name");
+	let payload = Payload::Binary {
+		data: vec![1],
+		name: &name,
+	};
+	assert!(matches!(
+		payload.view(),
+		PayloadView::Binary {
+			data: [1],
+			name: "name"
+		}
+	));
+	assert!(matches!(
+		PayloadView::Text("text").to_owned(),
+		Payload::Text(ref x) if x == "text"
+	));
+
+	let PrimitivesView(number, boolean) = Primitives(1, true).view();
+	assert_eq!((number, boolean), (1, true));
+}
+
+#[cowify]
+#[derive(Clone)]
+struct Parsed<'a> {
+	name: &'a str,
+	data: &'a [u8],
+	value: &'a u32,
+	nested: Option<Vec<&'a str>>,
+}
+
+#[cowify]
+#[derive(Clone)]
+enum ParsedEnum<'a> {
+	Text(&'a str),
+	Skipped(
+		#[cowify(skip)]
+		#[shallowclone(clone)]
+		#[makeowned(skip)]
+		&'static str,
+	),
+}
+
+#[test]
+fn cowify() {
+	let name = String::from("name");
+	let parsed = Parsed {
+		name: Cow::Borrowed(&name),
+		data: CoCowSlice::Borrowed(&[1, 2]),
+		value: CoCow::Borrowed(&5),
+		nested: Some(vec![Cow::Borrowed(&name)]),
+	};
+	let owned: Parsed<'static> = parsed.shallow_clone().make_owned();
+	assert!(matches!(owned.name, Cow::Owned(ref x) if x == "name"));
+	assert!(matches!(owned.data, CoCowSlice::Owned(ref x) if x == &[1, 2]));
+	assert!(matches!(owned.value, CoCow::Owned(5)));
+
+	let skipped: ParsedEnum<'static> = ParsedEnum::Skipped("skipped").make_owned();
+	assert!(matches!(skipped, ParsedEnum::Skipped("skipped")));
+}
+
+crate::define_cocow! {
+	#[derive(Debug, PartialEq)]
+	enum CoCowStr<'a> {
+		Owned(String),
+		Borrowed(&'a str),
+	}
+}
+
+crate::define_cocow! {
+	#[derive(Debug, PartialEq)]
+	enum CoCowVec<'a, T> {
+		Owned(Vec<T>),
+		Borrowed(&'a [T]),
+	}
+	borrow = Vec::as_slice;
+}
+
+struct Node(u32);
+
+// the borrowed type doesn't have to be `ToOwned`, like a handle into an arena
+crate::define_cocow! {
+	enum CoCowNode<'a> {
+		Owned(std::rc::Rc<Node>),
+		Borrowed(&'a Node),
+	}
+	borrow = std::rc::Rc::as_ref;
+}
+
+// the lifetimes of the generated impls don't collide with the one of the enum
+crate::define_cocow! {
+	enum CollidesWithShallowClone<'shallowclone> {
+		Owned(String),
+		Borrowed(&'shallowclone str),
+	}
+}
+
+crate::define_cocow! {
+	enum CollidesWithRebase<'new> {
+		Owned(String),
+		Borrowed(&'new str),
+	}
+}
+
+crate::define_cocow! {
+	#[derive(Debug)]
+	enum CollidesWithArena<'arena> {
+		Owned(u64),
+		Borrowed(&'arena u64),
+	}
+}
+
+crate::assert_makeowned_subtype!(CoCowStr<'_>);
+crate::assert_makeowned_subtype!(<T: Clone + 'static> CoCowVec<'_, T>);
+crate::assert_makeowned_subtype!(crate = "crate"; <'a> CoCowStr<'a>);
+
+#[derive(ShallowClone, MakeOwned, Clone)]
+struct Document<'a> {
+	title: CoCowStr<'a>,
+	lines: CoCowVec<'a, u32>,
+}
+
+#[test]
+fn define_cocow() {
+	let title = String::from("title");
+	let mut doc = Document {
+		title: CoCowStr::from(title.as_str()),
+		lines: CoCowVec::from(vec![1, 2, 3]),
+	};
+	assert!(doc.title.is_borrowed());
+	assert_eq!(&*doc.title, "title");
+	assert_eq!(doc.title.to_string(), "title");
+	assert_eq!((&doc.lines).into_iter().sum::<u32>(), 6);
+
+	let shallow = doc.shallow_clone();
+	assert!(shallow.lines.is_borrowed());
+	assert_eq!(shallow.lines, CoCowVec::Borrowed(&[1, 2, 3]));
+
+	let owned: Document<'static> = shallow.make_owned();
+	assert!(owned.title.is_owned());
+	assert_eq!(owned.title.into_owned(), "title");
+
+	doc.lines.to_mut().push(4);
+	assert_eq!(doc.lines.len(), 4);
+	assert_eq!(CoCowStr::from(Cow::Borrowed("x")), CoCowStr::Borrowed("x"));
+	assert_eq!(CoCowStr::default(), CoCowStr::Owned(String::new()));
+
+	let node = CoCowNode::from(std::rc::Rc::new(Node(7)));
+	assert_eq!(node.shallow_clone().0, 7);
+}
+
+#[test]
+fn define_cocow_traits() {
+	use crate::{Budget, HeapSize, HeapUsage, Limit, Rebase, TryMakeOwned};
+	use std::mem::size_of;
+
+	// the owned value is measured by what it allocates
+	let owned = CoCowVec::<String>::Owned(vec![String::with_capacity(8)]);
+	assert_eq!(
+		owned.heap_size(),
+		HeapUsage {
+			owned: size_of::<String>() + 8,
+			borrowed: 0
+		}
+	);
+	let strings = [String::from("abc")];
+	assert_eq!(
+		CoCowVec::Borrowed(&strings).heap_size(),
+		HeapUsage {
+			owned: 0,
+			borrowed: size_of::<String>() + 3
+		}
+	);
+
+	// copying the strings counts too
+	let mut budget = Budget::new(size_of::<String>() + 2, 10);
+	let error = CoCowVec::Borrowed(&strings)
+		.try_make_owned(&mut budget)
+		.unwrap_err();
+	assert_eq!(error.limit(), Limit::Bytes);
+	let mut budget = Budget::new(size_of::<String>() + 3, 10);
+	let owned = CoCowVec::Borrowed(&strings)
+		.try_make_owned(&mut budget)
+		.unwrap();
+	assert_eq!(owned, CoCowVec::Owned(strings.to_vec()));
+
+	let buffer = String::from("title");
+	let rebased = CoCowStr::Borrowed(&buffer).rebase(buffer.as_bytes(), b"title");
+	assert_eq!(rebased, CoCowStr::Owned(buffer.clone()));
+
+	#[cfg(feature = "bumpalo")]
+	{
+		use crate::MakeOwnedIn;
+
+		let arena = bumpalo::Bump::new();
+		// `String` has to be dropped, so it's owned on the heap
+		let title = CoCowStr::Borrowed(&buffer).make_owned_in(&arena);
+		assert_eq!(title, CoCowStr::Owned(buffer.clone()));
+		let value = 5;
+		let id = CollidesWithArena::Borrowed(&value).make_owned_in(&arena);
+		assert!(matches!(id, CollidesWithArena::Borrowed(&5)));
+	}
+}
+
+#[owned_args]
+fn owned_pattern([first, mut second]: [Cow<'_, str>; 2]) -> [Cow<'static, str>; 2] {
+	second.to_mut().push('!');
+	[second, first]
+}
+
+#[owned_args]
+async fn owned_async<T: MakeOwned<Owned = T>>(
+	name: Cow<'_, str>,
+	value: T,
+	#[borrowed] suffix: &'static str,
+) -> String {
+	let _: Cow<'static, str> = name.clone();
+	let _ = value;
+	format!("{name}{suffix}")
+}
+
+fn assert_static<F: std::future::Future + 'static>(future: F) -> F {
+	future
+}
+
+#[test]
+fn owned_args() {
+	use std::future::Future;
+	use std::task::{Context, Poll, Waker};
+
+	let name = String::from("name");
+	let values = owned_pattern([Cow::Borrowed(&name), Cow::Borrowed("value")]);
+	assert_eq!(values, ["value!", "name"]);
+
+	let future = assert_static(owned_async(Cow::Borrowed(&name), 5u32, "!"));
+	drop(name);
+	let mut future = std::pin::pin!(future);
+	let mut context = Context::from_waker(Waker::noop());
+	assert_eq!(
+		future.as_mut().poll(&mut context),
+		Poll::Ready(String::from("name!"))
+	);
+}
@@ -0,0 +1,284 @@
+use crate::{
+	field_path::{write_segments, PathSegment},
+	HeapSize, MakeOwned,
+};
+use std::{
+	borrow::Cow,
+	collections::{BTreeMap, HashMap},
+	error::Error,
+	fmt::{self, Display, Formatter},
+	hash::Hash,
+	marker::PhantomData,
+	mem, slice,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Like [`MakeOwned`], but fails instead of copying more borrowed data than the [`Budget`] allows.
+///
+/// Useful for data parsed from untrusted input, where a borrowed slice could be arbitrarily large.
+/// Owned parts are moved as they are and don't count against the budget. Values cloned out of a borrowed
+/// `Cow`, [`CoCow`][crate::CoCow] or [`CoCowSlice`][crate::CoCowSlice] must implement [`HeapSize`], so that
+/// the heap memory copied along with them is charged before they're cloned.
+pub trait TryMakeOwned: MakeOwned {
+	fn try_make_owned(self, budget: &mut Budget) -> Result<Self::Owned, OwnError>;
+}
+
+/// Limits on how much borrowed data [`TryMakeOwned`] may copy, and how much it has copied so far.
+///
+/// The bytes are the sizes of the copied buffers, plus the heap memory owned by the values in them, as
+/// reported by [`HeapSize`]. The elements are one for every copied string or value, and one for every
+/// element of a copied slice.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Budget {
+	max_bytes: usize,
+	max_elements: usize,
+	bytes: usize,
+	elements: usize,
+}
+
+impl Budget {
+	pub fn new(max_bytes: usize, max_elements: usize) -> Self {
+		Self {
+			max_bytes,
+			max_elements,
+			bytes: 0,
+			elements: 0,
+		}
+	}
+	pub fn unlimited() -> Self {
+		Self::new(usize::MAX, usize::MAX)
+	}
+	/// Bytes copied so far
+	pub fn bytes(&self) -> usize {
+		self.bytes
+	}
+	/// Elements copied so far
+	pub fn elements(&self) -> usize {
+		self.elements
+	}
+	/// Records a copy, or returns an error if it would exceed a limit, in which case nothing is recorded.
+	pub fn charge(&mut self, bytes: usize, elements: usize) -> Result<(), OwnError> {
+		let new_bytes = self.bytes.saturating_add(bytes);
+		if new_bytes > self.max_bytes {
+			return Err(OwnError::new(Limit::Bytes, self.max_bytes));
+		}
+		let new_elements = self.elements.saturating_add(elements);
+		if new_elements > self.max_elements {
+			return Err(OwnError::new(Limit::Elements, self.max_elements));
+		}
+
+		self.bytes = new_bytes;
+		self.elements = new_elements;
+		Ok(())
+	}
+}
+
+/// The limit of a [`Budget`] which was exceeded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+	Bytes,
+	Elements,
+}
+
+/// Error returned by [`TryMakeOwned`] when the [`Budget`] is exceeded, with the path of the field
+/// that exceeded it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnError {
+	limit: Limit,
+	max: usize,
+	/// From the innermost field outwards
+	path: Vec<PathSegment>,
+}
+
+impl OwnError {
+	pub fn new(limit: Limit, max: usize) -> Self {
+		Self {
+			limit,
+			max,
+			path: Vec::new(),
+		}
+	}
+	pub fn limit(&self) -> Limit {
+		self.limit
+	}
+	/// The value of the limit that was exceeded
+	pub fn max(&self) -> usize {
+		self.max
+	}
+	/// The path of the field that exceeded the limit, like `items[2].name`, empty if it was the value itself
+	pub fn path(&self) -> String {
+		let mut path = String::new();
+		// writing to a string can't fail
+		let _ = write_segments(&mut path, self.path.iter().rev());
+		path
+	}
+	/// Prepends a field name to the path, used when propagating the error out of a field
+	pub fn in_field(mut self, name: &'static str) -> Self {
+		self.path.push(PathSegment::Field(name));
+		self
+	}
+	/// Prepends an index to the path, used when propagating the error out of an element of a collection
+	pub fn at_index(mut self, index: usize) -> Self {
+		self.path.push(PathSegment::Index(index));
+		self
+	}
+}
+
+impl Display for OwnError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		let limit = match self.limit {
+			Limit::Bytes => "bytes",
+			Limit::Elements => "elements",
+		};
+		match self.path.is_empty() {
+			true => write!(f, "copying exceeds the limit of {} {limit}", self.max),
+			false => write!(
+				f,
+				"copying `{}` exceeds the limit of {} {limit}",
+				self.path(),
+				self.max
+			),
+		}
+	}
+}
+
+impl Error for OwnError {}
+
+impl<'a> TryMakeOwned for Cow<'a, str> {
+	fn try_make_owned(self, budget: &mut Budget) -> Result<<Self as MakeOwned>::Owned, OwnError> {
+		if let Cow::Borrowed(bor) = &self {
+			budget.charge(bor.len(), 1)?;
+		}
+		Ok(self.make_owned())
+	}
+}
+impl<'a, T: TryMakeOwned + HeapSize + Clone> TryMakeOwned for Cow<'a, [T]>
+where
+	<T as MakeOwned>::Owned: Clone,
+{
+	fn try_make_owned(self, budget: &mut Budget) -> Result<<Self as MakeOwned>::Owned, OwnError> {
+		Ok(Cow::Owned(match self {
+			Cow::Borrowed(bor) => {
+				charge_clone(bor, budget)?;
+				try_elements(bor.iter().cloned(), budget)?
+			}
+			Cow::Owned(owned) => try_elements(owned, budget)?,
+		}))
+	}
+}
+impl<'a, A: MakeOwned + Clone + 'static, T: HeapSize + Clone> TryMakeOwned for Cow<'a, T>
+where
+	T: TryMakeOwned<Owned = A>,
+{
+	fn try_make_owned(self, budget: &mut Budget) -> Result<<Self as MakeOwned>::Owned, OwnError> {
+		let owned = match self {
+			Cow::Borrowed(bor) => {
+				charge_clone(slice::from_ref(bor), budget)?;
+				bor.clone()
+			}
+			Cow::Owned(owned) => owned,
+		};
+		Ok(Cow::Owned(owned.try_make_owned(budget)?))
+	}
+}
+
+impl<T: 'static> TryMakeOwned for PhantomData<T> {
+	fn try_make_owned(self, _budget: &mut Budget) -> Result<Self::Owned, OwnError> {
+		Ok(self)
+	}
+}
+
+// primitives and String are implemented by `impl_makeowned_basic!`
+
+impl<const N: usize, T: TryMakeOwned> TryMakeOwned for [T; N] {
+	fn try_make_owned(self, budget: &mut Budget) -> Result<Self::Owned, OwnError> {
+		let owned: Vec<_> = try_elements(self, budget)?;
+		Ok(owned.try_into().unwrap_or_else(|_| unreachable!()))
+	}
+}
+
+impl<T: TryMakeOwned> TryMakeOwned for Option<T> {
+	fn try_make_owned(self, budget: &mut Budget) -> Result<Self::Owned, OwnError> {
+		self.map(|x| x.try_make_owned(budget)).transpose()
+	}
+}
+
+impl<T: TryMakeOwned> TryMakeOwned for Vec<T> {
+	fn try_make_owned(self, budget: &mut Budget) -> Result<Self::Owned, OwnError> {
+		try_elements(self, budget)
+	}
+}
+
+impl<T: TryMakeOwned> TryMakeOwned for Box<T> {
+	fn try_make_owned(self, budget: &mut Budget) -> Result<Self::Owned, OwnError> {
+		Ok(Box::new((*self).try_make_owned(budget)?))
+	}
+}
+
+// the entries of maps are identified by their position, since the keys can't be displayed in general
+
+impl<K: TryMakeOwned, V: TryMakeOwned> TryMakeOwned for HashMap<K, V>
+where
+	K::Owned: Eq + Hash,
+{
+	fn try_make_owned(self, budget: &mut Budget) -> Result<Self::Owned, OwnError> {
+		try_entries(self, budget)
+	}
+}
+
+impl<K: TryMakeOwned, V: TryMakeOwned> TryMakeOwned for BTreeMap<K, V>
+where
+	K::Owned: Eq + Ord,
+{
+	fn try_make_owned(self, budget: &mut Budget) -> Result<Self::Owned, OwnError> {
+		try_entries(self, budget)
+	}
+}
+
+#[cfg(feature = "indexmap")]
+impl<K: TryMakeOwned, V: TryMakeOwned> TryMakeOwned for indexmap::IndexMap<K, V>
+where
+	K::Owned: Hash + Eq,
+{
+	fn try_make_owned(self, budget: &mut Budget) -> Result<Self::Owned, OwnError> {
+		try_entries(self, budget)
+	}
+}
+
+/// Charges for cloning borrowed values before they're cloned, since cloning copies their heap memory too
+pub(crate) fn charge_clone<T: HeapSize>(values: &[T], budget: &mut Budget) -> Result<(), OwnError> {
+	let owned = values
+		.iter()
+		.map(|value| value.heap_size().owned)
+		.fold(0, usize::saturating_add);
+	budget.charge(mem::size_of_val(values).saturating_add(owned), values.len())
+}
+
+/// Converts the elements of a sequence, adding their index to the path of an error
+pub(crate) fn try_elements<T: TryMakeOwned, C: FromIterator<T::Owned>>(
+	elements: impl IntoIterator<Item = T>,
+	budget: &mut Budget,
+) -> Result<C, OwnError> {
+	elements
+		.into_iter()
+		.enumerate()
+		.map(|(i, x)| x.try_make_owned(budget).map_err(|e| e.at_index(i)))
+		.collect()
+}
+
+fn try_entries<K: TryMakeOwned, V: TryMakeOwned, C: FromIterator<(K::Owned, V::Owned)>>(
+	entries: impl IntoIterator<Item = (K, V)>,
+	budget: &mut Budget,
+) -> Result<C, OwnError> {
+	entries
+		.into_iter()
+		.enumerate()
+		.map(|(i, (k, v))| {
+			let k = k.try_make_owned(budget).map_err(|e| e.at_index(i))?;
+			let v = v.try_make_owned(budget).map_err(|e| e.at_index(i))?;
+			Ok((k, v))
+		})
+		.collect()
+}
@@ -0,0 +1,98 @@
+use crate::{Budget, CoCowSlice, Limit, MakeOwned, TryMakeOwned};
+use std::borrow::Cow;
+
+#[derive(MakeOwned, TryMakeOwned, Clone, Debug)]
+struct Item<'a> {
+	name: Cow<'a, str>,
+	#[makeowned(skip)]
+	count: u32,
+}
+
+#[derive(MakeOwned, TryMakeOwned, Clone, Debug)]
+struct Order<'a, T> {
+	id: T,
+	items: Vec<Item<'a>>,
+	tags: CoCowSlice<'a, Cow<'a, str>>,
+}
+
+#[derive(MakeOwned, TryMakeOwned, Clone, Debug)]
+enum Event<'a> {
+	Order(Order<'a, u64>),
+	Note { text: Cow<'a, str> },
+}
+
+#[test]
+fn within_budget() {
+	let tags = [Cow::Borrowed("a"), Cow::Owned(String::from("b"))];
+	let order = Order {
+		id: 1u64,
+		items: vec![Item {
+			name: Cow::Borrowed("name"),
+			count: 2,
+		}],
+		tags: CoCowSlice::Borrowed(&tags),
+	};
+
+	let mut budget = Budget::unlimited();
+	let owned: Order<'static, u64> = order.try_make_owned(&mut budget).unwrap();
+	assert!(matches!(owned.items[0].name, Cow::Owned(ref x) if x == "name"));
+	assert_eq!(owned.items[0].count, 2);
+	assert_eq!(owned.tags.len(), 2);
+	// the name, the slice with its two elements and the owned tag in it, and the borrowed tag
+	assert_eq!(budget.bytes(), 4 + std::mem::size_of_val(&tags) + 1 + 1);
+	assert_eq!(budget.elements(), 1 + 2 + 1);
+}
+
+#[test]
+fn exceeded() {
+	let long = "x".repeat(100);
+	let order = Order {
+		id: 1u64,
+		items: vec![
+			Item {
+				name: Cow::Borrowed("short"),
+				count: 1,
+			},
+			Item {
+				name: Cow::Borrowed(&long),
+				count: 1,
+			},
+		],
+		tags: CoCowSlice::Owned(Vec::new()),
+	};
+
+	let mut budget = Budget::new(50, 10);
+	let error = Event::Order(order).try_make_owned(&mut budget).unwrap_err();
+	assert_eq!(error.limit(), Limit::Bytes);
+	assert_eq!(error.max(), 50);
+	assert_eq!(error.path(), "0.items[1].name");
+	assert_eq!(
+		error.to_string(),
+		"copying `0.items[1].name` exceeds the limit of 50 bytes"
+	);
+	// only the first name was copied
+	assert_eq!(budget.bytes(), 5);
+
+	let mut budget = Budget::new(50, 0);
+	let note = Event::Note {
+		text: Cow::Borrowed("text"),
+	};
+	let error = note.try_make_owned(&mut budget).unwrap_err();
+	assert_eq!(error.limit(), Limit::Elements);
+	assert_eq!(error.path(), "text");
+}
+
+#[test]
+fn exceeded_by_owned_elements() {
+	// the strings are copied when the borrowed slice is cloned, so they count too
+	let strings = vec!["x".repeat(100); 2];
+	let slice: Cow<[String]> = Cow::Borrowed(&strings);
+	let mut budget = Budget::new(200, 10);
+	let error = slice.try_make_owned(&mut budget).unwrap_err();
+	assert_eq!(error.limit(), Limit::Bytes);
+	assert_eq!(budget.bytes(), 0);
+
+	let slice: Cow<[String]> = Cow::Borrowed(&strings);
+	let mut budget = Budget::new(200 + std::mem::size_of_val(&strings[..]), 10);
+	assert_eq!(slice.try_make_owned(&mut budget).unwrap(), strings);
+}
@@ -0,0 +1,260 @@
+use crate::{CoCow, CoCowSlice, FieldPath};
+use std::{
+	borrow::Cow,
+	collections::{BTreeMap, HashMap},
+	marker::PhantomData,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Called by [`VisitCows::visit_cows`] for every copy-on-write value inside, before its contents are
+/// visited.
+///
+/// All of the methods call [`visit_any`][Self::visit_any] by default, so a visitor which doesn't care
+/// about the contents only needs to implement that one.
+// the values are passed as `Cow`s on purpose, so that the visitor can tell whether they're borrowed
+#[allow(clippy::ptr_arg)]
+pub trait CowVisitor {
+	/// Called for every copy-on-write value, and for the types defined with
+	/// [`define_cocow!`][crate::define_cocow], unless the specific method is implemented
+	fn visit_any(&mut self, path: &FieldPath, borrowed: bool) {
+		let _ = (path, borrowed);
+	}
+
+	fn visit_str(&mut self, path: &FieldPath, value: &Cow<'_, str>) {
+		self.visit_any(path, matches!(value, Cow::Borrowed(_)));
+	}
+
+	fn visit_slice<T: Clone>(&mut self, path: &FieldPath, value: &Cow<'_, [T]>) {
+		self.visit_any(path, matches!(value, Cow::Borrowed(_)));
+	}
+
+	fn visit_cow<T: Clone>(&mut self, path: &FieldPath, value: &Cow<'_, T>) {
+		self.visit_any(path, matches!(value, Cow::Borrowed(_)));
+	}
+
+	fn visit_cocow<T: Clone>(&mut self, path: &FieldPath, value: &CoCow<'_, T>) {
+		self.visit_any(path, value.is_borrowed());
+	}
+
+	fn visit_cocow_slice<T: Clone>(&mut self, path: &FieldPath, value: &CoCowSlice<'_, T>) {
+		self.visit_any(path, value.is_borrowed());
+	}
+}
+
+/// Like [`CowVisitor`], but it can modify the values, e.g. make some of them owned.
+///
+/// The contents of a value are visited after it, and only if it's owned at that point, since borrowed
+/// contents can't be modified.
+pub trait CowVisitorMut {
+	/// Called for every copy-on-write value, and for the types defined with
+	/// [`define_cocow!`][crate::define_cocow], unless the specific method is implemented
+	fn visit_any(&mut self, path: &FieldPath, borrowed: bool) {
+		let _ = (path, borrowed);
+	}
+
+	fn visit_str(&mut self, path: &FieldPath, value: &mut Cow<'_, str>) {
+		self.visit_any(path, matches!(value, Cow::Borrowed(_)));
+	}
+
+	fn visit_slice<T: Clone>(&mut self, path: &FieldPath, value: &mut Cow<'_, [T]>) {
+		self.visit_any(path, matches!(value, Cow::Borrowed(_)));
+	}
+
+	fn visit_cow<T: Clone>(&mut self, path: &FieldPath, value: &mut Cow<'_, T>) {
+		self.visit_any(path, matches!(value, Cow::Borrowed(_)));
+	}
+
+	fn visit_cocow<T: Clone>(&mut self, path: &FieldPath, value: &mut CoCow<'_, T>) {
+		self.visit_any(path, value.is_borrowed());
+	}
+
+	fn visit_cocow_slice<T: Clone>(&mut self, path: &FieldPath, value: &mut CoCowSlice<'_, T>) {
+		self.visit_any(path, value.is_borrowed());
+	}
+}
+
+/// Walks a value and calls a [`CowVisitor`] for every copy-on-write value inside, together with its
+/// [`FieldPath`], so that operations over whole structures can be written once as visitors.
+pub trait VisitCows {
+	/// Visits the copy-on-write values inside, `path` is the path of the value itself
+	fn visit_cows_at<V: CowVisitor>(&self, visitor: &mut V, path: &mut FieldPath);
+
+	/// Visits the copy-on-write values inside mutably, `path` is the path of the value itself
+	fn visit_cows_mut_at<V: CowVisitorMut>(&mut self, visitor: &mut V, path: &mut FieldPath);
+
+	fn visit_cows<V: CowVisitor>(&self, visitor: &mut V) {
+		self.visit_cows_at(visitor, &mut FieldPath::new());
+	}
+
+	fn visit_cows_mut<V: CowVisitorMut>(&mut self, visitor: &mut V) {
+		self.visit_cows_mut_at(visitor, &mut FieldPath::new());
+	}
+}
+
+impl<'a> VisitCows for Cow<'a, str> {
+	fn visit_cows_at<V: CowVisitor>(&self, visitor: &mut V, path: &mut FieldPath) {
+		visitor.visit_str(path, self);
+	}
+	fn visit_cows_mut_at<V: CowVisitorMut>(&mut self, visitor: &mut V, path: &mut FieldPath) {
+		visitor.visit_str(path, self);
+	}
+}
+impl<'a, T: VisitCows + Clone> VisitCows for Cow<'a, [T]> {
+	fn visit_cows_at<V: CowVisitor>(&self, visitor: &mut V, path: &mut FieldPath) {
+		visitor.visit_slice(path, self);
+		(**self).visit_cows_at(visitor, path);
+	}
+	fn visit_cows_mut_at<V: CowVisitorMut>(&mut self, visitor: &mut V, path: &mut FieldPath) {
+		visitor.visit_slice(path, self);
+		if let Cow::Owned(owned) = self {
+			owned.visit_cows_mut_at(visitor, path);
+		}
+	}
+}
+impl<'a, T: VisitCows + Clone> VisitCows for Cow<'a, T> {
+	fn visit_cows_at<V: CowVisitor>(&self, visitor: &mut V, path: &mut FieldPath) {
+		visitor.visit_cow(path, self);
+		(**self).visit_cows_at(visitor, path);
+	}
+	fn visit_cows_mut_at<V: CowVisitorMut>(&mut self, visitor: &mut V, path: &mut FieldPath) {
+		visitor.visit_cow(path, self);
+		if let Cow::Owned(owned) = self {
+			owned.visit_cows_mut_at(visitor, path);
+		}
+	}
+}
+
+// the contents of a shared reference can only be visited immutably
+impl<T: VisitCows + ?Sized> VisitCows for &T {
+	fn visit_cows_at<V: CowVisitor>(&self, visitor: &mut V, path: &mut FieldPath) {
+		(**self).visit_cows_at(visitor, path);
+	}
+	fn visit_cows_mut_at<V: CowVisitorMut>(&mut self, _visitor: &mut V, _path: &mut FieldPath) {}
+}
+
+impl<T: ?Sized> VisitCows for PhantomData<T> {
+	fn visit_cows_at<V: CowVisitor>(&self, _visitor: &mut V, _path: &mut FieldPath) {}
+	fn visit_cows_mut_at<V: CowVisitorMut>(&mut self, _visitor: &mut V, _path: &mut FieldPath) {}
+}
+
+// primitives and String are implemented by `impl_makeowned_basic!`
+
+impl VisitCows for str {
+	fn visit_cows_at<V: CowVisitor>(&self, _visitor: &mut V, _path: &mut FieldPath) {}
+	fn visit_cows_mut_at<V: CowVisitorMut>(&mut self, _visitor: &mut V, _path: &mut FieldPath) {}
+}
+
+impl<T: VisitCows> VisitCows for [T] {
+	fn visit_cows_at<V: CowVisitor>(&self, visitor: &mut V, path: &mut FieldPath) {
+		for (i, x) in self.iter().enumerate() {
+			path.push_index(i);
+			x.visit_cows_at(visitor, path);
+			path.pop();
+		}
+	}
+	fn visit_cows_mut_at<V: CowVisitorMut>(&mut self, visitor: &mut V, path: &mut FieldPath) {
+		for (i, x) in self.iter_mut().enumerate() {
+			path.push_index(i);
+			x.visit_cows_mut_at(visitor, path);
+			path.pop();
+		}
+	}
+}
+
+impl<const N: usize, T: VisitCows> VisitCows for [T; N] {
+	fn visit_cows_at<V: CowVisitor>(&self, visitor: &mut V, path: &mut FieldPath) {
+		self.as_slice().visit_cows_at(visitor, path);
+	}
+	fn visit_cows_mut_at<V: CowVisitorMut>(&mut self, visitor: &mut V, path: &mut FieldPath) {
+		self.as_mut_slice().visit_cows_mut_at(visitor, path);
+	}
+}
+
+impl<T: VisitCows> VisitCows for Option<T> {
+	fn visit_cows_at<V: CowVisitor>(&self, visitor: &mut V, path: &mut FieldPath) {
+		if let Some(x) = self {
+			x.visit_cows_at(visitor, path);
+		}
+	}
+	fn visit_cows_mut_at<V: CowVisitorMut>(&mut self, visitor: &mut V, path: &mut FieldPath) {
+		if let Some(x) = self {
+			x.visit_cows_mut_at(visitor, path);
+		}
+	}
+}
+
+impl<T: VisitCows> VisitCows for Vec<T> {
+	fn visit_cows_at<V: CowVisitor>(&self, visitor: &mut V, path: &mut FieldPath) {
+		self.as_slice().visit_cows_at(visitor, path);
+	}
+	fn visit_cows_mut_at<V: CowVisitorMut>(&mut self, visitor: &mut V, path: &mut FieldPath) {
+		self.as_mut_slice().visit_cows_mut_at(visitor, path);
+	}
+}
+
+impl<T: VisitCows + ?Sized> VisitCows for Box<T> {
+	fn visit_cows_at<V: CowVisitor>(&self, visitor: &mut V, path: &mut FieldPath) {
+		(**self).visit_cows_at(visitor, path);
+	}
+	fn visit_cows_mut_at<V: CowVisitorMut>(&mut self, visitor: &mut V, path: &mut FieldPath) {
+		(**self).visit_cows_mut_at(visitor, path);
+	}
+}
+
+// the entries of maps are visited at their position, and the keys can't be modified, so they are
+// skipped when visiting mutably
+
+impl<K: VisitCows, V: VisitCows, S> VisitCows for HashMap<K, V, S> {
+	fn visit_cows_at<X: CowVisitor>(&self, visitor: &mut X, path: &mut FieldPath) {
+		visit_entries(self, visitor, path);
+	}
+	fn visit_cows_mut_at<X: CowVisitorMut>(&mut self, visitor: &mut X, path: &mut FieldPath) {
+		visit_values_mut(self.values_mut(), visitor, path);
+	}
+}
+
+impl<K: VisitCows, V: VisitCows> VisitCows for BTreeMap<K, V> {
+	fn visit_cows_at<X: CowVisitor>(&self, visitor: &mut X, path: &mut FieldPath) {
+		visit_entries(self, visitor, path);
+	}
+	fn visit_cows_mut_at<X: CowVisitorMut>(&mut self, visitor: &mut X, path: &mut FieldPath) {
+		visit_values_mut(self.values_mut(), visitor, path);
+	}
+}
+
+#[cfg(feature = "indexmap")]
+impl<K: VisitCows, V: VisitCows, S> VisitCows for indexmap::IndexMap<K, V, S> {
+	fn visit_cows_at<X: CowVisitor>(&self, visitor: &mut X, path: &mut FieldPath) {
+		visit_entries(self, visitor, path);
+	}
+	fn visit_cows_mut_at<X: CowVisitorMut>(&mut self, visitor: &mut X, path: &mut FieldPath) {
+		visit_values_mut(self.values_mut(), visitor, path);
+	}
+}
+
+fn visit_entries<'a, K: VisitCows + 'a, V: VisitCows + 'a, X: CowVisitor>(
+	entries: impl IntoIterator<Item = (&'a K, &'a V)>,
+	visitor: &mut X,
+	path: &mut FieldPath,
+) {
+	for (i, (k, v)) in entries.into_iter().enumerate() {
+		path.push_index(i);
+		k.visit_cows_at(visitor, path);
+		v.visit_cows_at(visitor, path);
+		path.pop();
+	}
+}
+
+fn visit_values_mut<'a, V: VisitCows + 'a, X: CowVisitorMut>(
+	values: impl IntoIterator<Item = &'a mut V>,
+	visitor: &mut X,
+	path: &mut FieldPath,
+) {
+	for (i, v) in values.into_iter().enumerate() {
+		path.push_index(i);
+		v.visit_cows_mut_at(visitor, path);
+		path.pop();
+	}
+}
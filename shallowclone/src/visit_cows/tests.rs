@@ -0,0 +1,88 @@
+use crate::{
+	tests::fixtures::Header, BorrowState, CoCow, CoCowSlice, CowVisitor, CowVisitorMut, FieldPath,
+	PathSegment, VisitCows,
+};
+use std::{borrow::Cow, collections::BTreeMap};
+
+#[derive(VisitCows, BorrowState)]
+enum Part<'a> {
+	Header(CoCow<'a, Header<'a>>),
+	Map {
+		entries: BTreeMap<u32, Cow<'a, str>>,
+		// left as it is by `MakeOwned`, but still visited
+		#[makeowned(skip)]
+		skipped: Cow<'a, str>,
+	},
+	Empty,
+}
+
+#[derive(Default)]
+struct Paths(Vec<(String, bool)>);
+
+impl CowVisitor for Paths {
+	fn visit_any(&mut self, path: &FieldPath, borrowed: bool) {
+		self.0.push((path.to_string(), borrowed));
+	}
+}
+
+/// Makes the strings under `values` owned
+struct OwnValues;
+
+impl CowVisitorMut for OwnValues {
+	fn visit_str(&mut self, path: &FieldPath, value: &mut Cow<'_, str>) {
+		if path.segments().contains(&PathSegment::Field("values")) {
+			value.to_mut();
+		}
+	}
+
+	fn visit_cocow<T: Clone>(&mut self, _path: &FieldPath, value: &mut CoCow<'_, T>) {
+		value.to_mut();
+	}
+
+	fn visit_cocow_slice<T: Clone>(&mut self, path: &FieldPath, value: &mut CoCowSlice<'_, T>) {
+		if path.to_string().ends_with("values") {
+			value.to_mut();
+		}
+	}
+}
+
+#[test]
+fn visit_cows() {
+	let values = [Cow::Borrowed("a"), Cow::Owned(String::from("b"))];
+	let header = Header {
+		name: Cow::Borrowed("name"),
+		values: CoCowSlice::Borrowed(&values),
+	};
+	let mut parts = vec![
+		Part::Header(CoCow::Borrowed(&header)),
+		Part::Map {
+			entries: BTreeMap::from([(1, Cow::Borrowed("x"))]),
+			skipped: Cow::Borrowed("skipped"),
+		},
+		Part::Empty,
+	];
+
+	let mut paths = Paths::default();
+	parts.visit_cows(&mut paths);
+	let expected = [
+		("[0].0", true),
+		("[0].0.name", true),
+		("[0].0.values", true),
+		("[0].0.values[0]", true),
+		("[0].0.values[1]", false),
+		("[1].entries[0]", true),
+		("[1].skipped", true),
+	];
+	let expected = expected.map(|(path, borrowed)| (path.to_string(), borrowed));
+	assert_eq!(paths.0, expected);
+
+	parts.visit_cows_mut(&mut OwnValues);
+	let Part::Header(header) = &parts[0] else {
+		unreachable!()
+	};
+	assert!(header.is_owned() && header.values.is_owned());
+	assert!(header.values.is_fully_owned());
+	assert!(!header.name.is_fully_owned());
+	// the map isn't under `values`
+	assert!(!parts[1].is_fully_owned());
+}